@@ -0,0 +1,434 @@
+use crate::base::encrypt::{Encrypt, KdfParams, KeySecurity};
+use crate::base::error::Error;
+use crate::base::storage::{DATA_FOLDER_FILES, IDENTITIES_FOLDER, TEMP_FILE_SUFFIX};
+use crate::base::storage_backend::StorageBackend;
+
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Magic prefix of backup archive files
+const BACKUP_MAGIC: &[u8] = b"KEYSTRBK";
+/// Version of the backup archive format
+const BACKUP_VERSION: u8 = 1;
+/// Max total size of the files in a backup (the data folder is normally a few KiBs)
+const BACKUP_MAX_SIZE: usize = 16 * 1024 * 1024;
+/// Folder inside the data folder where the restored files are written first
+const RESTORE_STAGING_FOLDER: &str = ".restore";
+/// Folder inside the data folder where the replaced files are kept during a restore
+const RESTORE_PREVIOUS_FOLDER: &str = ".previous";
+
+/// Backup of the files of Keystr in the data folder (keys of all identities, settings,
+/// delegation ledger), as a single file encrypted with a password.
+/// Other files in the data folder are neither backed up nor touched by a restore.
+/// File format: magic ('KEYSTRBK'), version byte, then the archive contents (JSON)
+/// encrypted the same way as secret keys.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct BackupArchive {
+    /// Creation time, unix timestamp
+    created_at: u64,
+    /// Files: path relative to the data folder (with '/' separators), and contents (hex)
+    files: BTreeMap<String, String>,
+}
+
+impl BackupArchive {
+    /// Collect the files of Keystr in the folder into an archive
+    pub fn from_folder(
+        storage: &dyn StorageBackend,
+        folder: &Path,
+        created_at: u64,
+    ) -> Result<Self, Error> {
+        let mut archive = Self {
+            created_at,
+            files: BTreeMap::new(),
+        };
+        let mut total_size = 0;
+        for path in storage.list_files(folder)? {
+            let relative_path = match Self::relative_path(folder, &path) {
+                Some(relative_path) => relative_path,
+                // not created by us
+                None => continue,
+            };
+            if !Self::is_own_path(&relative_path) || relative_path.ends_with(TEMP_FILE_SUFFIX) {
+                continue;
+            }
+            let mut contents = storage.read(&path)?;
+            total_size += contents.len();
+            if total_size > BACKUP_MAX_SIZE {
+                contents.zeroize();
+                return Err(Error::BackupTooLarge);
+            }
+            archive.files.insert(relative_path, hex::encode(&contents));
+            contents.zeroize();
+        }
+        if archive.files.is_empty() {
+            return Err(Error::BackupNothingToBackup);
+        }
+        Ok(archive)
+    }
+
+    /// Path of a file relative to the folder, with '/' separators; None if not valid UTF-8
+    fn relative_path(folder: &Path, path: &Path) -> Option<String> {
+        let names = path
+            .strip_prefix(folder)
+            .ok()?
+            .components()
+            .map(|c| c.as_os_str().to_str())
+            .collect::<Option<Vec<&str>>>()?;
+        Some(names.join("/"))
+    }
+
+    pub fn created_at(&self) -> u64 {
+        self.created_at
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Names of the identities that have files in the archive
+    pub fn identities(&self) -> Vec<String> {
+        let prefix = format!("{}/", IDENTITIES_FOLDER);
+        let mut names: Vec<String> = self
+            .files
+            .keys()
+            .filter_map(|path| path.strip_prefix(&prefix))
+            .filter_map(|rest| rest.split_once('/').map(|(name, _file)| name.to_string()))
+            .collect();
+        names.dedup();
+        names
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Encrypt the archive with the password, using the given key derivation
+    /// It is recommend to zeroize() the password after use.
+    pub fn encrypt(&self, password: &str, kdf: &KdfParams) -> Result<Vec<u8>, Error> {
+        let mut serialized = serde_json::to_string(self)?;
+        let res = Encrypt::encrypt_data(serialized.as_bytes(), password, kdf, KeySecurity::Medium);
+        serialized.zeroize();
+        let mut data = BACKUP_MAGIC.to_vec();
+        data.push(BACKUP_VERSION);
+        data.extend(res?);
+        Ok(data)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Decrypt an archive, and validate it: format version, and all paths have to be
+    /// inside the data folder.
+    /// It is recommend to zeroize() the password after use.
+    pub fn decrypt(data: &[u8], password: &str) -> Result<Self, Error> {
+        if data.len() <= BACKUP_MAGIC.len() || !data.starts_with(BACKUP_MAGIC) {
+            return Err(Error::BackupInvalid);
+        }
+        if data[BACKUP_MAGIC.len()] != BACKUP_VERSION {
+            return Err(Error::BackupUnsupportedVersion);
+        }
+        let (mut decrypted, _key_security) =
+            Encrypt::decrypt_data(&data[BACKUP_MAGIC.len() + 1..], password)?;
+        let res = serde_json::from_slice::<Self>(&decrypted).map_err(|_e| Error::BackupInvalid);
+        decrypted.zeroize();
+        let archive = res?;
+        if archive.files.is_empty() {
+            return Err(Error::BackupInvalid);
+        }
+        for (path, contents) in &archive.files {
+            if !Self::is_valid_path(path) || hex::decode(contents).is_err() {
+                return Err(Error::BackupInvalid);
+            }
+        }
+        Ok(archive)
+    }
+
+    /// A relative path, with only normal components (no '..', not absolute),
+    /// of a file of Keystr
+    fn is_valid_path(path: &str) -> bool {
+        !path.is_empty()
+            && !path.contains('\\')
+            && Path::new(path)
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)))
+            && Self::is_own_path(path)
+    }
+
+    /// A path (relative to the data folder) written by Keystr: one of its files, or a file
+    /// in the identities folder
+    fn is_own_path(path: &str) -> bool {
+        DATA_FOLDER_FILES.contains(&path)
+            || path
+                .strip_prefix(IDENTITIES_FOLDER)
+                .map_or(false, |rest| rest.starts_with('/'))
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Replace the files of Keystr in the folder with the files of the archive; its files not in
+    /// the archive are removed, other files of the folder are left alone.
+    /// The files are written into a staging folder first, and moved in entry by entry
+    /// (each file, and the identities folder) only if all writes succeed. The replaced entries are
+    /// kept until all are moved: on failure the folder is left as it was.
+    pub fn restore_to(&self, storage: &dyn StorageBackend, folder: &Path) -> Result<(), Error> {
+        let staging = folder.join(RESTORE_STAGING_FOLDER);
+        let previous = folder.join(RESTORE_PREVIOUS_FOLDER);
+        storage.remove_folder(&staging)?;
+        if let Err(e) = self.write_files(storage, &staging) {
+            let _res = storage.remove_folder(&staging);
+            return Err(e);
+        }
+        storage.remove_folder(&previous)?;
+        let entries = Self::own_entries();
+        for (idx, (entry, is_folder)) in entries.iter().enumerate() {
+            let res = Self::move_entry(
+                storage,
+                &folder.join(entry),
+                &previous.join(entry),
+                *is_folder,
+            )
+            .and_then(|_| {
+                Self::move_entry(
+                    storage,
+                    &staging.join(entry),
+                    &folder.join(entry),
+                    *is_folder,
+                )
+            });
+            if let Err(e) = res {
+                // put the old entries back
+                for (entry, is_folder) in &entries[..=idx] {
+                    let _res = Self::remove_entry(storage, &folder.join(entry), *is_folder);
+                    let _res = Self::move_entry(
+                        storage,
+                        &previous.join(entry),
+                        &folder.join(entry),
+                        *is_folder,
+                    );
+                }
+                let _res = storage.remove_folder(&staging);
+                let _res = storage.remove_folder(&previous);
+                return Err(e);
+            }
+        }
+        storage.remove_folder(&staging)?;
+        storage.remove_folder(&previous)
+    }
+
+    /// Entries of Keystr in the data folder, and whether each is a folder
+    fn own_entries() -> Vec<(&'static str, bool)> {
+        DATA_FOLDER_FILES
+            .iter()
+            .map(|file| (*file, false))
+            .chain([(IDENTITIES_FOLDER, true)])
+            .collect()
+    }
+
+    /// Move a file or a folder, if it exists
+    fn move_entry(
+        storage: &dyn StorageBackend,
+        from: &Path,
+        to: &Path,
+        is_folder: bool,
+    ) -> Result<(), Error> {
+        if is_folder {
+            storage.rename_folder(from, to)
+        } else if storage.exists(from) {
+            storage.rename(from, to)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn remove_entry(
+        storage: &dyn StorageBackend,
+        path: &Path,
+        is_folder: bool,
+    ) -> Result<(), Error> {
+        if is_folder {
+            storage.remove_folder(path)
+        } else {
+            storage.remove_file(path)
+        }
+    }
+
+    fn write_files(&self, storage: &dyn StorageBackend, folder: &Path) -> Result<(), Error> {
+        for (path, contents) in &self.files {
+            let full_path: PathBuf = folder.join(path);
+            let mut contents = hex::decode(contents).map_err(|_e| Error::BackupInvalid)?;
+            let res = storage.write(&full_path, &contents, true);
+            contents.zeroize();
+            res?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for BackupArchive {
+    fn drop(&mut self) {
+        for contents in self.files.values_mut() {
+            contents.zeroize();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::storage_backend::memory::MemoryStorage;
+
+    #[test]
+    fn test_backup_and_restore() {
+        let storage = MemoryStorage::default();
+        let folder = PathBuf::from("/from");
+        let write = |path: &str, contents: &str| {
+            storage
+                .write(&folder.join(path), contents.as_bytes(), false)
+                .unwrap()
+        };
+        write("settings.json", "{}");
+        write("keyring.json", r#"{"identities":["main","bot"]}"#);
+        write("identities/main/.ncrypt", "0102");
+        write("identities/main/npub", "npub1main");
+        write("identities/bot/npub", "npub1bot");
+        write("identities/bot/npub.tmp", "half-written");
+        // not ours, e.g. in a data folder chosen by the user
+        write("notes.txt", "unrelated");
+        write("earlier.keystrbk", "earlier backup");
+        assert!(matches!(
+            BackupArchive::from_folder(&storage, Path::new("/empty"), 0),
+            Err(Error::BackupNothingToBackup)
+        ));
+
+        let archive = BackupArchive::from_folder(&storage, &folder, 1678875600).unwrap();
+        assert_eq!(archive.file_count(), 5);
+        assert_eq!(archive.identities(), vec!["bot", "main"]);
+        let data = archive
+            .encrypt("password", &KdfParams::Scrypt { log2_rounds: 10 })
+            .unwrap();
+        assert!(data.starts_with(BACKUP_MAGIC));
+        assert!(BackupArchive::decrypt(&data, "wrong").is_err());
+        let restored = BackupArchive::decrypt(&data, "password").unwrap();
+        assert_eq!(restored.created_at(), 1678875600);
+
+        // identities and files not in the backup are removed, other files are kept
+        let to = PathBuf::from("/to");
+        storage
+            .write(&to.join("notes.txt"), b"unrelated", false)
+            .unwrap();
+        storage
+            .write(&to.join("identities/other/npub"), b"npub1other", false)
+            .unwrap();
+        storage
+            .write(&to.join("settings.json"), b"old", false)
+            .unwrap();
+        storage
+            .write(&to.join("delegations.json"), b"[]", false)
+            .unwrap();
+        restored.restore_to(&storage, &to).unwrap();
+        assert_eq!(
+            storage.read_to_string(&to.join("settings.json")).unwrap(),
+            "{}"
+        );
+        assert_eq!(
+            storage
+                .read_to_string(&to.join("identities/main/.ncrypt"))
+                .unwrap(),
+            "0102"
+        );
+        assert!(storage
+            .is_private(&to.join("identities/main/.ncrypt"))
+            .unwrap());
+        assert!(!storage.exists(&to.join("identities/other/npub")));
+        assert!(!storage.exists(&to.join("identities/bot/npub.tmp")));
+        assert!(!storage.exists(&to.join("delegations.json")));
+        assert_eq!(
+            storage.read_to_string(&to.join("notes.txt")).unwrap(),
+            "unrelated"
+        );
+        assert_eq!(storage.list_files(&to).unwrap().len(), 6);
+        assert!(storage
+            .list_files(Path::new("/"))
+            .unwrap()
+            .iter()
+            .all(|path| path.starts_with(&folder) || path.starts_with(&to)));
+    }
+
+    #[test]
+    fn test_restore_write_fails() {
+        let storage = MemoryStorage::default();
+        let mut archive = BackupArchive {
+            created_at: 0,
+            files: BTreeMap::new(),
+        };
+        for path in [
+            "identities/main/.ncrypt",
+            "identities/main/npub",
+            "keyring.json",
+            "settings.json",
+        ] {
+            archive.files.insert(path.to_string(), hex::encode("new"));
+        }
+        let folder = PathBuf::from("/keystr");
+        let ncrypt = folder.join("identities/main/.ncrypt");
+        storage.write(&ncrypt, b"original key", true).unwrap();
+        storage
+            .write(&folder.join("delegations.json"), b"[]", false)
+            .unwrap();
+        storage
+            .write(&folder.join("notes.txt"), b"unrelated", false)
+            .unwrap();
+
+        // the third write fails
+        storage.fail_writes_after(Some(2));
+        assert!(archive.restore_to(&storage, &folder).is_err());
+        assert_eq!(storage.read(&ncrypt).unwrap(), b"original key".to_vec());
+        assert!(storage.exists(&folder.join("delegations.json")));
+        assert_eq!(storage.list_files(Path::new("/")).unwrap().len(), 3);
+
+        storage.fail_writes_after(None);
+        archive.restore_to(&storage, &folder).unwrap();
+        assert_eq!(storage.read(&ncrypt).unwrap(), b"new".to_vec());
+        assert!(!storage.exists(&folder.join("delegations.json")));
+        assert!(storage.exists(&folder.join("notes.txt")));
+        assert_eq!(storage.list_files(Path::new("/")).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_decrypt_invalid() {
+        let kdf = KdfParams::Scrypt { log2_rounds: 10 };
+        assert!(matches!(
+            BackupArchive::decrypt(b"not a backup", "password"),
+            Err(Error::BackupInvalid)
+        ));
+        let mut archive = BackupArchive {
+            created_at: 0,
+            files: BTreeMap::new(),
+        };
+        archive
+            .files
+            .insert("settings.json".to_string(), hex::encode("{}"));
+        let mut data = archive.encrypt("password", &kdf).unwrap();
+        data[BACKUP_MAGIC.len()] = BACKUP_VERSION + 1;
+        assert!(matches!(
+            BackupArchive::decrypt(&data, "password"),
+            Err(Error::BackupUnsupportedVersion)
+        ));
+
+        // paths outside the data folder, or not of Keystr
+        for path in [
+            "../settings.json",
+            "/etc/passwd",
+            "identities/../../x",
+            "",
+            "notes.txt",
+            "identities",
+            "identitiesx/main/npub",
+        ] {
+            archive.files.clear();
+            archive.files.insert(path.to_string(), hex::encode("x"));
+            let data = archive.encrypt("password", &kdf).unwrap();
+            assert!(matches!(
+                BackupArchive::decrypt(&data, "password"),
+                Err(Error::BackupInvalid)
+            ));
+        }
+    }
+}
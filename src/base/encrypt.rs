@@ -1,13 +1,200 @@
 use crate::base::error::Error;
+use bech32::{FromBase32, ToBase32, Variant};
 use chacha20poly1305::{
     aead::{Aead, AeadCore, KeyInit, Payload},
     XChaCha20Poly1305,
 };
 use nostr::prelude::SecretKey;
 use rand_core::{OsRng, RngCore};
+use unicode_normalization::UnicodeNormalization;
 use zeroize::Zeroize;
 
-/// Two-way encryption, used for secret keys
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Version byte of the Keystr encrypted format with scrypt (+ XChaCha20-Poly1305)
+const VERSION_KEYSTR_SCRYPT: u8 = 0x1;
+/// Version byte of the Keystr encrypted format with Argon2id (+ XChaCha20-Poly1305)
+const VERSION_KEYSTR_ARGON2ID: u8 = 0x2;
+/// Version byte of NIP-49 encrypted keys; the layout is the same as the Keystr scrypt format.
+/// (It is only used inside 'ncryptsec' strings, not to be confused with the Keystr version 2.)
+const VERSION_NIP49: u8 = 0x2;
+/// Bech32 prefix of NIP-49 encrypted keys
+const NCRYPTSEC_HRP: &str = "ncryptsec";
+/// Length of the fields around the KDF parameters: 1 version + 16 salt + 24 nonce + 1 key security
+const FIXED_HEADER_LEN: usize = 42;
+/// Length of the authentication tag appended to the ciphertext
+const TAG_LEN: usize = 16;
+/// Highest accepted Argon2 memory cost (in KiB, 1 GiB); the parameters come from the
+/// (untrusted) encrypted data, so opening a crafted file must not cause a huge allocation
+const ARGON2_MAX_M_COST: u32 = 1024 * 1024;
+/// Highest accepted Argon2 time cost (iterations), to bound the time of opening a file
+const ARGON2_MAX_T_COST: u32 = 16;
+
+/// How the secret key was handled before being encrypted, stored in the
+/// key security byte of the encrypted data (as defined in NIP-49)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum KeySecurity {
+    /// Key is known to have been handled insecurely (e.g. shown or pasted in plain text)
+    Weak = 0,
+    /// Key is not known to have been handled insecurely
+    Medium = 1,
+    /// It is not tracked how the key was handled
+    Unknown = 2,
+}
+
+impl KeySecurity {
+    fn from_byte(b: u8) -> Result<Self, Error> {
+        match b {
+            0 => Ok(KeySecurity::Weak),
+            1 => Ok(KeySecurity::Medium),
+            2 => Ok(KeySecurity::Unknown),
+            _ => Err(Error::KeyInvalidEncrypted),
+        }
+    }
+}
+
+/// Password-based key derivation function, with its parameters (stored in the encrypted data)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum KdfParams {
+    /// scrypt with cost N = 2^log2_rounds (r = 8, p = 1); format version 1
+    Scrypt { log2_rounds: u8 },
+    /// Argon2id with memory cost in KiB, time cost (iterations) and parallelism; format version 2
+    Argon2id {
+        m_cost: u32,
+        t_cost: u32,
+        p_cost: u32,
+    },
+}
+
+impl KdfParams {
+    /// Argon2id parameters used for new encryptions (64 MiB, 3 iterations, as in RFC 9106)
+    pub(crate) fn default_argon2id() -> Self {
+        KdfParams::Argon2id {
+            m_cost: 64 * 1024,
+            t_cost: 3,
+            p_cost: 1,
+        }
+    }
+
+    /// True if these parameters are weaker than the other ones (of the policy).
+    /// scrypt is considered weaker than Argon2id, and Argon2id is not weaker than scrypt.
+    pub(crate) fn is_weaker_than(&self, other: &KdfParams) -> bool {
+        match (self, other) {
+            (
+                KdfParams::Scrypt { log2_rounds },
+                KdfParams::Scrypt {
+                    log2_rounds: other_log2_rounds,
+                },
+            ) => log2_rounds < other_log2_rounds,
+            (KdfParams::Scrypt { .. }, KdfParams::Argon2id { .. }) => true,
+            (KdfParams::Argon2id { .. }, KdfParams::Scrypt { .. }) => false,
+            (
+                KdfParams::Argon2id { m_cost, t_cost, .. },
+                KdfParams::Argon2id {
+                    m_cost: other_m_cost,
+                    t_cost: other_t_cost,
+                    ..
+                },
+            ) => m_cost < other_m_cost || t_cost < other_t_cost,
+        }
+    }
+
+    /// Check that the parameters are in the accepted ranges
+    fn validate(&self) -> Result<(), Error> {
+        let valid = match self {
+            KdfParams::Scrypt { log2_rounds } => *log2_rounds <= Encrypt::max_log2_rounds(),
+            KdfParams::Argon2id {
+                m_cost,
+                t_cost,
+                p_cost,
+            } => {
+                *m_cost <= ARGON2_MAX_M_COST
+                    && *t_cost >= 1
+                    && *t_cost <= ARGON2_MAX_T_COST
+                    && *p_cost >= 1
+                    && *p_cost <= 255
+            }
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::KeyEncryptionCostInvalid)
+        }
+    }
+
+    /// Serialized parameters, as stored after the version byte
+    fn to_bytes(self) -> Vec<u8> {
+        match self {
+            KdfParams::Scrypt { log2_rounds } => vec![log2_rounds],
+            KdfParams::Argon2id {
+                m_cost,
+                t_cost,
+                p_cost,
+            } => {
+                let mut bytes = Vec::new();
+                bytes.extend(m_cost.to_le_bytes());
+                bytes.extend(t_cost.to_le_bytes());
+                bytes.extend(p_cost.to_le_bytes());
+                bytes
+            }
+        }
+    }
+
+    /// Parse the parameters from the bytes after the version byte, return also their length
+    fn from_bytes_argon2id(bytes: &[u8]) -> Result<(Self, usize), Error> {
+        if bytes.len() < 12 {
+            return Err(Error::KeyInvalidEncrypted);
+        }
+        let read_u32 =
+            |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        Ok((
+            KdfParams::Argon2id {
+                m_cost: read_u32(0),
+                t_cost: read_u32(4),
+                p_cost: read_u32(8),
+            },
+            12,
+        ))
+    }
+
+    fn version(&self) -> u8 {
+        match self {
+            KdfParams::Scrypt { .. } => VERSION_KEYSTR_SCRYPT,
+            KdfParams::Argon2id { .. } => VERSION_KEYSTR_ARGON2ID,
+        }
+    }
+}
+
+impl fmt::Display for KdfParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KdfParams::Scrypt { log2_rounds } => write!(f, "scrypt, log2 rounds {}", log2_rounds),
+            KdfParams::Argon2id {
+                m_cost,
+                t_cost,
+                p_cost,
+            } => write!(
+                f,
+                "Argon2id, {} MiB, {} iterations, parallelism {}",
+                m_cost / 1024,
+                t_cost,
+                p_cost
+            ),
+        }
+    }
+}
+
+/// Container of the encrypted data, determines how the version byte is interpreted
+#[derive(Clone, Copy)]
+enum Format {
+    /// Keystr's own format (hex in files), version 1 (scrypt) or 2 (Argon2id)
+    Keystr,
+    /// NIP-49 'ncryptsec', version 2 with scrypt
+    Nip49,
+}
+
+/// Two-way encryption, used for secret keys (and other secrets, such as the mnemonic)
 pub(crate) struct Encrypt {}
 
 impl Encrypt {
@@ -15,13 +202,196 @@ impl Encrypt {
         13
     }
 
-    /// Encrypt a key.
+    /// Lowest scrypt cost (log2 of N) that can be set for saving
+    pub(crate) fn min_log2_rounds() -> u8 {
+        10
+    }
+
+    /// Highest accepted scrypt cost (log2 of N); above this derivation takes too long
+    pub(crate) fn max_log2_rounds() -> u8 {
+        22
+    }
+
+    /// Measure the speed of key derivation on this machine, and return the scrypt cost
+    /// (log2 of N) for which decryption takes about the target duration.
+    /// Each increment doubles the time, so a fast, low-cost derivation is measured and scaled.
+    pub(crate) fn calibrate_log2_rounds(target: Duration) -> u8 {
+        let probe_log2_rounds: u8 = 12;
+        let start = Instant::now();
+        let _key = Self::password_to_key(
+            "calibration",
+            &[0; 16],
+            &KdfParams::Scrypt {
+                log2_rounds: probe_log2_rounds,
+            },
+        );
+        let elapsed = start.elapsed().as_secs_f64().max(0.000_001);
+        let log2_rounds =
+            probe_log2_rounds as f64 + (target.as_secs_f64() / elapsed).log2().floor();
+        log2_rounds
+            .max(Self::min_log2_rounds() as f64)
+            .min(Self::max_log2_rounds() as f64) as u8
+    }
+
+    /// The key derivation parameters encrypted key or data was encrypted with
+    pub(crate) fn get_kdf_params(encrypted: &[u8]) -> Result<KdfParams, Error> {
+        let (kdf, _params_len) = Self::parse_kdf_params(Format::Keystr, encrypted)?;
+        Ok(kdf)
+    }
+
+    /// Encrypt a key, using scrypt.
     /// It is recommend to zeroize() the password after use.
+    #[cfg(test)]
     pub(crate) fn encrypt_key(
         key: &SecretKey,
         password: &str,
         log2_rounds: u8,
+        key_security: KeySecurity,
+    ) -> Result<Vec<u8>, Error> {
+        Self::encrypt_key_kdf(
+            key,
+            password,
+            &KdfParams::Scrypt { log2_rounds },
+            key_security,
+        )
+    }
+
+    /// Encrypt a key, using the given key derivation function.
+    /// It is recommend to zeroize() the password after use.
+    pub(crate) fn encrypt_key_kdf(
+        key: &SecretKey,
+        password: &str,
+        kdf: &KdfParams,
+        key_security: KeySecurity,
+    ) -> Result<Vec<u8>, Error> {
+        let mut inner_secret: Vec<u8> = key.secret_bytes().to_vec();
+        let res = Self::encrypt_bytes(kdf.version(), &inner_secret, password, kdf, key_security);
+        inner_secret.zeroize();
+        res
+    }
+
+    /// Decrypt a key encrypted using `encrypt_key` or `encrypt_key_kdf`
+    /// It is recommend to zeroize() the password after use.
+    #[cfg(test)]
+    pub(crate) fn decrypt_key(encrypted: &[u8], password: &str) -> Result<SecretKey, Error> {
+        let (secret_key, _key_security) = Self::decrypt_key_with_security(encrypted, password)?;
+        Ok(secret_key)
+    }
+
+    /// Decrypt a key encrypted using `encrypt_key` or `encrypt_key_kdf`, return also its key security
+    /// It is recommend to zeroize() the password after use.
+    pub(crate) fn decrypt_key_with_security(
+        encrypted: &[u8],
+        password: &str,
+    ) -> Result<(SecretKey, KeySecurity), Error> {
+        Self::decrypt_key_format(Format::Keystr, encrypted, password)
+    }
+
+    /// Encrypt arbitrary secret data, in the same format as keys (but variable length).
+    /// It is recommend to zeroize() the password and the data after use.
+    pub(crate) fn encrypt_data(
+        data: &[u8],
+        password: &str,
+        kdf: &KdfParams,
+        key_security: KeySecurity,
+    ) -> Result<Vec<u8>, Error> {
+        Self::encrypt_bytes(kdf.version(), data, password, kdf, key_security)
+    }
+
+    /// Decrypt data encrypted using `encrypt_data`, return also its key security
+    /// It is recommend to zeroize() the password and the result data after use.
+    pub(crate) fn decrypt_data(
+        encrypted: &[u8],
+        password: &str,
+    ) -> Result<(Vec<u8>, KeySecurity), Error> {
+        Self::decrypt_bytes(Format::Keystr, encrypted, password)
+    }
+
+    /// Encrypt a key into a NIP-49 'ncryptsec' bech32 string.
+    /// Password is Unicode-normalized (NFKC), as required by NIP-49.
+    /// It is recommend to zeroize() the password after use.
+    pub(crate) fn encrypt_key_ncryptsec(
+        key: &SecretKey,
+        password: &str,
+        log2_rounds: u8,
+        key_security: KeySecurity,
+    ) -> Result<String, Error> {
+        let mut password_nfkc: String = password.nfkc().collect();
+        let mut inner_secret: Vec<u8> = key.secret_bytes().to_vec();
+        let encrypted = Self::encrypt_bytes(
+            VERSION_NIP49,
+            &inner_secret,
+            &password_nfkc,
+            &KdfParams::Scrypt { log2_rounds },
+            key_security,
+        );
+        inner_secret.zeroize();
+        password_nfkc.zeroize();
+        bech32::encode(NCRYPTSEC_HRP, encrypted?.to_base32(), Variant::Bech32)
+            .map_err(|_e| Error::KeyInvalidNcryptsec)
+    }
+
+    /// Decrypt a NIP-49 'ncryptsec' bech32 string, return the key and its key security
+    /// It is recommend to zeroize() the password after use.
+    pub(crate) fn decrypt_key_ncryptsec(
+        ncryptsec: &str,
+        password: &str,
+    ) -> Result<(SecretKey, KeySecurity), Error> {
+        let (hrp, data, _variant) =
+            bech32::decode(ncryptsec.trim()).map_err(|_e| Error::KeyInvalidNcryptsec)?;
+        if hrp != NCRYPTSEC_HRP {
+            return Err(Error::KeyInvalidNcryptsec);
+        }
+        let encrypted = Vec::<u8>::from_base32(&data).map_err(|_e| Error::KeyInvalidNcryptsec)?;
+        let mut password_nfkc: String = password.nfkc().collect();
+        let res = Self::decrypt_key_format(Format::Nip49, &encrypted, &password_nfkc);
+        password_nfkc.zeroize();
+        res
+    }
+
+    fn decrypt_key_format(
+        format: Format,
+        encrypted: &[u8],
+        password: &str,
+    ) -> Result<(SecretKey, KeySecurity), Error> {
+        let (mut inner_secret, key_security) = Self::decrypt_bytes(format, encrypted, password)?;
+        let secret_key = SecretKey::from_slice(&inner_secret);
+        inner_secret.zeroize();
+        Ok((secret_key?, key_security))
+    }
+
+    /// Parse the key derivation parameters, return also the length of the parameters
+    fn parse_kdf_params(format: Format, encrypted: &[u8]) -> Result<(KdfParams, usize), Error> {
+        if encrypted.len() < 2 {
+            return Err(Error::KeyInvalidEncrypted);
+        }
+        let (kdf, params_len) = match (format, encrypted[0]) {
+            (Format::Keystr, VERSION_KEYSTR_SCRYPT) | (Format::Nip49, VERSION_NIP49) => (
+                KdfParams::Scrypt {
+                    log2_rounds: encrypted[1],
+                },
+                1,
+            ),
+            (Format::Keystr, VERSION_KEYSTR_ARGON2ID) => {
+                KdfParams::from_bytes_argon2id(&encrypted[1..])?
+            }
+            _ => return Err(Error::KeyInvalidEncryptionVersion),
+        };
+        if encrypted.len() < FIXED_HEADER_LEN + params_len + TAG_LEN {
+            return Err(Error::KeyInvalidEncrypted);
+        }
+        Ok((kdf, params_len))
+    }
+
+    fn encrypt_bytes(
+        version: u8,
+        inner_secret: &[u8],
+        password: &str,
+        kdf: &KdfParams,
+        key_security: KeySecurity,
     ) -> Result<Vec<u8>, Error> {
+        kdf.validate()?;
+
         // Generate a random 16-byte salt
         let salt = {
             let mut salt: [u8; 16] = [0; 16];
@@ -31,67 +401,56 @@ impl Encrypt {
 
         let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
 
-        // placeholder for key security
-        let associated_data: Vec<u8> = vec![1];
+        let associated_data: Vec<u8> = vec![key_security as u8];
 
         let ciphertext = {
             let cipher = {
-                let symmetric_key = Self::password_to_key(password, &salt, log2_rounds)?;
+                let symmetric_key = Self::password_to_key(password, &salt, kdf)?;
                 XChaCha20Poly1305::new((&symmetric_key).into())
             };
 
-            // The inner secret. We don't have to drop this because we are encrypting-in-place
-            let mut inner_secret: Vec<u8> = key.secret_bytes().to_vec();
-
             let payload = Payload {
-                msg: &inner_secret,
+                msg: inner_secret,
                 aad: &associated_data,
             };
 
-            let ciphertext = cipher
+            cipher
                 .encrypt(&nonce, payload)
-                .map_err(|_e| Error::KeyEncryption)?;
-
-            inner_secret.zeroize();
-
-            ciphertext
+                .map_err(|_e| Error::KeyEncryption)?
         };
 
         // Combine salt, IV and ciphertext
         let mut concat: Vec<u8> = Vec::new();
-        concat.push(0x1); // 1 byte version number
-        concat.push(log2_rounds); // 1 byte for scrypt N (rounds)
+        concat.push(version); // 1 byte version number
+        concat.extend(kdf.to_bytes()); // 1 byte for scrypt N (rounds), or 12 bytes for Argon2id
         concat.extend(salt); // 16 bytes of salt
         concat.extend(nonce); // 24 bytes of nonce
         concat.extend(associated_data); // 1 byte of key security
-        concat.extend(ciphertext); // 48 bytes of ciphertext expected
-                                   // Total length is 91 = 1 + 1 + 16 + 24 + 1 + 32
+        concat.extend(ciphertext); // data length + 16 bytes of ciphertext
+                                   // For a key with scrypt, total length is 91 = 1 + 1 + 16 + 24 + 1 + 48
 
         Ok(concat)
     }
 
-    /// Decrypt a key encrypted using `encrypt_key`
-    /// It is recommend to zeroize() the password after use.
-    pub(crate) fn decrypt_key(encrypted: &Vec<u8>, password: &str) -> Result<SecretKey, Error> {
-        if encrypted.len() < 91 {
-            return Err(Error::KeyInvalidEncrypted);
-        }
+    fn decrypt_bytes(
+        format: Format,
+        encrypted: &[u8],
+        password: &str,
+    ) -> Result<(Vec<u8>, KeySecurity), Error> {
+        let (kdf, params_len) = Self::parse_kdf_params(format, encrypted)?;
+        kdf.validate()?;
 
         // Break into parts
-        let version: u8 = encrypted[0];
-        if version != 1 {
-            return Err(Error::KeyInvalidEncryptionVersion);
-        }
-        let log2_rounds: u8 = encrypted[1];
-        let salt: [u8; 16] = encrypted[2..2 + 16]
+        let salt_start = 1 + params_len;
+        let salt: [u8; 16] = encrypted[salt_start..salt_start + 16]
             .try_into()
             .map_err(|_e| Error::KeyInvalidEncrypted)?;
-        let nonce = &encrypted[2 + 16..2 + 16 + 24];
-        let associated_data = &encrypted[2 + 16 + 24..2 + 16 + 24 + 1];
-        let ciphertext = &encrypted[2 + 16 + 24 + 1..];
+        let nonce = &encrypted[salt_start + 16..salt_start + 16 + 24];
+        let associated_data = &encrypted[salt_start + 16 + 24..salt_start + 16 + 24 + 1];
+        let ciphertext = &encrypted[salt_start + 16 + 24 + 1..];
 
         let cipher = {
-            let symmetric_key = Self::password_to_key(password, &salt, log2_rounds)?;
+            let symmetric_key = Self::password_to_key(password, &salt, &kdf)?;
             XChaCha20Poly1305::new((&symmetric_key).into())
         };
 
@@ -100,30 +459,49 @@ impl Encrypt {
             aad: associated_data,
         };
 
-        let mut inner_secret = cipher
+        let inner_secret = cipher
             .decrypt(nonce.into(), payload)
             .map_err(|_e| Error::KeyEncryption)?;
 
-        if associated_data.is_empty() {
-            return Err(Error::KeyInvalidEncrypted);
-        }
-        let key_security = associated_data[0];
-        if key_security != 1 {
-            return Err(Error::KeyEncryption);
-        }
+        let key_security = KeySecurity::from_byte(associated_data[0])?;
 
-        let secret_key = SecretKey::from_slice(&inner_secret)?;
-        inner_secret.zeroize();
-
-        Ok(secret_key)
+        Ok((inner_secret, key_security))
     }
 
-    // Hash/Stretch password with scrypt into a 32-byte (256-bit) key
-    fn password_to_key(password: &str, salt: &[u8; 16], log_n: u8) -> Result<[u8; 32], Error> {
-        let params = scrypt::Params::new(log_n, 8, 1).map_err(|_e| Error::KeyEncryption)?;
+    // Hash/Stretch password with scrypt or Argon2id into a 32-byte (256-bit) key
+    fn password_to_key(
+        password: &str,
+        salt: &[u8; 16],
+        kdf: &KdfParams,
+    ) -> Result<[u8; 32], Error> {
         let mut key: [u8; 32] = [0; 32];
-        if scrypt::scrypt(password.as_bytes(), salt, &params, &mut key).is_err() {
-            return Err(Error::KeyEncryption);
+        match kdf {
+            KdfParams::Scrypt { log2_rounds } => {
+                let params =
+                    scrypt::Params::new(*log2_rounds, 8, 1).map_err(|_e| Error::KeyEncryption)?;
+                if scrypt::scrypt(password.as_bytes(), salt, &params, &mut key).is_err() {
+                    return Err(Error::KeyEncryption);
+                }
+            }
+            KdfParams::Argon2id {
+                m_cost,
+                t_cost,
+                p_cost,
+            } => {
+                let params = argon2::Params::new(*m_cost, *t_cost, *p_cost, Some(32))
+                    .map_err(|_e| Error::KeyEncryption)?;
+                let argon2 = argon2::Argon2::new(
+                    argon2::Algorithm::Argon2id,
+                    argon2::Version::V0x13,
+                    params,
+                );
+                if argon2
+                    .hash_password_into(password.as_bytes(), salt, &mut key)
+                    .is_err()
+                {
+                    return Err(Error::KeyEncryption);
+                }
+            }
         }
         Ok(key)
     }
@@ -141,7 +519,7 @@ mod test {
         )
         .unwrap();
         let password = "password".to_string();
-        let encrypted = Encrypt::encrypt_key(&sk, &password, 13).unwrap();
+        let encrypted = Encrypt::encrypt_key(&sk, &password, 13, KeySecurity::Medium).unwrap();
 
         let _decrypted = Encrypt::decrypt_key(&encrypted, &password).unwrap();
     }
@@ -153,10 +531,23 @@ mod test {
         )
         .unwrap();
         let password = "password".to_string();
-        let encrypted = Encrypt::encrypt_key(&sk, &password, 13).unwrap();
+        let encrypted = Encrypt::encrypt_key(&sk, &password, 13, KeySecurity::Medium).unwrap();
         // Encrypted result is variable, cannot compare to const
         assert_eq!(encrypted.len(), 91);
-        assert_eq!(hex::encode(encrypted)[0..4], "010d".to_string());
+        assert_eq!(hex::encode(&encrypted)[0..4], "010d".to_string());
+        assert_eq!(
+            Encrypt::get_kdf_params(&encrypted).unwrap(),
+            KdfParams::Scrypt { log2_rounds: 13 }
+        );
+    }
+
+    #[test]
+    fn test_calibrate_log2_rounds() {
+        let log2_rounds = Encrypt::calibrate_log2_rounds(Duration::from_millis(100));
+        assert!(log2_rounds >= Encrypt::min_log2_rounds());
+        assert!(log2_rounds <= Encrypt::max_log2_rounds());
+        // a longer target gives a higher (or the max) cost
+        assert!(Encrypt::calibrate_log2_rounds(Duration::from_secs(60)) >= log2_rounds);
     }
 
     #[test]
@@ -170,4 +561,182 @@ mod test {
             "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"
         );
     }
+
+    #[test]
+    fn test_encrypt_and_decrypt_argon2id() {
+        let sk = SecretKey::from_bech32(
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+        )
+        .unwrap();
+        let kdf = KdfParams::Argon2id {
+            m_cost: 256,
+            t_cost: 2,
+            p_cost: 1,
+        };
+        let encrypted =
+            Encrypt::encrypt_key_kdf(&sk, "password", &kdf, KeySecurity::Medium).unwrap();
+        assert_eq!(encrypted.len(), 102);
+        assert_eq!(hex::encode(&encrypted)[0..2], "02".to_string());
+        assert_eq!(Encrypt::get_kdf_params(&encrypted).unwrap(), kdf);
+
+        let (decrypted, key_security) =
+            Encrypt::decrypt_key_with_security(&encrypted, "password").unwrap();
+        assert_eq!(decrypted, sk);
+        assert_eq!(key_security, KeySecurity::Medium);
+        assert!(Encrypt::decrypt_key(&encrypted, "passw0rd").is_err());
+    }
+
+    #[test]
+    fn test_decrypt_argon2id() {
+        // Argon2id, m_cost 256 KiB, t_cost 2, p_cost 1
+        let encrypted = hex::decode("02000100000200000001000000fa067ccd3aa88008055f7320da140542f7df0f3ca8e3ad076002c104c05467c0cd074da9c43e9e110120afa50f34767cc5fc89fae4b29782c62b885eb3e961346d42a1158afe6250d730d4a504dc65dd6731786696f6ac80e5").unwrap();
+        let decrypted = Encrypt::decrypt_key(&encrypted, "password").unwrap();
+        assert_eq!(
+            decrypted.to_bech32().unwrap(),
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"
+        );
+    }
+
+    #[test]
+    fn test_decrypt_invalid_kdf_params() {
+        let sk = SecretKey::from_bech32(
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+        )
+        .unwrap();
+        let mut encrypted = Encrypt::encrypt_key_kdf(
+            &sk,
+            "password",
+            &KdfParams::default_argon2id(),
+            KeySecurity::Medium,
+        )
+        .unwrap();
+        // unreasonable memory cost is rejected before derivation
+        encrypted[1..5].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            Encrypt::decrypt_key(&encrypted, "password"),
+            Err(Error::KeyEncryptionCostInvalid)
+        ));
+        // unknown version
+        encrypted[0] = 3;
+        assert!(matches!(
+            Encrypt::decrypt_key(&encrypted, "password"),
+            Err(Error::KeyInvalidEncryptionVersion)
+        ));
+    }
+
+    #[test]
+    fn test_decrypt_out_of_range_kdf_params_fails_fast() {
+        let encrypted = Encrypt::encrypt_data(
+            b"data",
+            "password",
+            &KdfParams::default_argon2id(),
+            KeySecurity::Medium,
+        )
+        .unwrap();
+        for (m_cost, t_cost) in [
+            (4 * 1024 * 1024, 3),
+            (1024 * 1024 + 1, 1),
+            (64 * 1024, 17),
+            (64 * 1024, 64),
+        ] {
+            let mut crafted = encrypted.clone();
+            crafted[1..5].copy_from_slice(&u32::to_le_bytes(m_cost));
+            crafted[5..9].copy_from_slice(&u32::to_le_bytes(t_cost));
+            let start = std::time::Instant::now();
+            assert!(matches!(
+                Encrypt::decrypt_data(&crafted, "password"),
+                Err(Error::KeyEncryptionCostInvalid)
+            ));
+            assert!(start.elapsed() < std::time::Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn test_kdf_is_weaker_than() {
+        let scrypt13 = KdfParams::Scrypt { log2_rounds: 13 };
+        let scrypt16 = KdfParams::Scrypt { log2_rounds: 16 };
+        let argon2 = KdfParams::default_argon2id();
+        assert!(scrypt13.is_weaker_than(&scrypt16));
+        assert!(!scrypt16.is_weaker_than(&scrypt13));
+        assert!(!scrypt13.is_weaker_than(&scrypt13));
+        assert!(scrypt16.is_weaker_than(&argon2));
+        assert!(!argon2.is_weaker_than(&scrypt16));
+        assert!(KdfParams::Argon2id {
+            m_cost: 1024,
+            t_cost: 3,
+            p_cost: 1
+        }
+        .is_weaker_than(&argon2));
+    }
+
+    #[test]
+    fn test_encrypt_and_decrypt_key_security() {
+        let sk = SecretKey::from_bech32(
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+        )
+        .unwrap();
+        let encrypted = Encrypt::encrypt_key(&sk, "password", 13, KeySecurity::Weak).unwrap();
+        let (_decrypted, key_security) =
+            Encrypt::decrypt_key_with_security(&encrypted, "password").unwrap();
+        assert_eq!(key_security, KeySecurity::Weak);
+    }
+
+    #[test]
+    fn test_encrypt_and_decrypt_data() {
+        let data = "leader monkey parrot ring guide accident before fence cannon height naive bean";
+        let encrypted = Encrypt::encrypt_data(
+            data.as_bytes(),
+            "password",
+            &KdfParams::Scrypt { log2_rounds: 13 },
+            KeySecurity::Medium,
+        )
+        .unwrap();
+        assert_eq!(encrypted.len(), 43 + data.len() + 16);
+
+        let (decrypted, key_security) = Encrypt::decrypt_data(&encrypted, "password").unwrap();
+        assert_eq!(decrypted, data.as_bytes());
+        assert_eq!(key_security, KeySecurity::Medium);
+
+        assert!(Encrypt::decrypt_data(&encrypted, "passw0rd").is_err());
+        // data is not a valid key
+        assert!(Encrypt::decrypt_key(&encrypted, "password").is_err());
+    }
+
+    #[test]
+    fn test_ncryptsec_encrypt_and_decrypt() {
+        let sk = SecretKey::from_bech32(
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+        )
+        .unwrap();
+        let ncryptsec =
+            Encrypt::encrypt_key_ncryptsec(&sk, "password", 13, KeySecurity::Unknown).unwrap();
+        assert!(ncryptsec.starts_with("ncryptsec1"));
+
+        let (decrypted, key_security) =
+            Encrypt::decrypt_key_ncryptsec(&ncryptsec, "password").unwrap();
+        assert_eq!(decrypted, sk);
+        assert_eq!(key_security, KeySecurity::Unknown);
+
+        // wrong password
+        assert!(Encrypt::decrypt_key_ncryptsec(&ncryptsec, "passw0rd").is_err());
+        // not interchangeable with the Keystr format
+        let encrypted = Encrypt::encrypt_key(&sk, "password", 13, KeySecurity::Medium).unwrap();
+        assert!(Encrypt::decrypt_key_ncryptsec(
+            &bech32::encode(NCRYPTSEC_HRP, encrypted.to_base32(), Variant::Bech32).unwrap(),
+            "password"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_ncryptsec_decrypt() {
+        // Test vector from the NIP-49 spec
+        let ncryptsec = "ncryptsec1qgg9947rlpvqu76pj5ecreduf9jxhselq2nae2kghhvd5g7dgjtcxfqtd67p9m0w57lspw8gsq6yphnm8623nsl8xn9j4jdzz84zm3frztj3z7s35vpzmqf6ksu8r89qk5z2zxfmu5gv8th8wclt0h4p";
+        let (decrypted, key_security) = Encrypt::decrypt_key_ncryptsec(ncryptsec, "nostr").unwrap();
+        assert_eq!(
+            hex::encode(decrypted.secret_bytes()),
+            "3501454135014541350145413501453fefb02227e449e57cf4d3a3ce05378683"
+        );
+        assert_eq!(key_security, KeySecurity::Weak);
+    }
 }
@@ -24,18 +24,168 @@ pub enum Error {
     /// Encryption error
     #[error("Encryption error")]
     KeyEncryption,
+    /// Invalid NIP-49 encrypted key string
+    #[error("Invalid ncryptsec encrypted key")]
+    KeyInvalidNcryptsec,
     /// Invalid encryption version
     #[error("Invalid encryption version")]
     KeyInvalidEncryptionVersion,
     /// Mandatory encryption password missing
     #[error("Mandatory encryption password missing. Check password and security settings")]
     KeyEncryptionPasswordMissing,
+    /// Invalid scrypt cost for re-encryption
+    #[error("Invalid key derivation cost, it cannot be weaker than the current one, or unreasonably high (e.g. scrypt above 22)")]
+    KeyEncryptionCostInvalid,
+    /// Invalid scrypt cost in settings
+    #[error("Invalid encryption cost, should be a number between 10 and 22")]
+    SettingsInvalidKdfCost,
+    /// Invalid auto-lock timeout in settings
+    #[error("Invalid auto-lock timeout, should be a number of minutes between 0 (off) and 1440")]
+    SettingsInvalidAutoLock,
+    /// Operation needs the saved state, but there are unsaved changes
+    #[error("There are unsaved changes, save or reload keys first")]
+    KeyUnsavedChange,
+    /// Previous version of the saved key does not exist
+    #[error("Previous version of the saved key not found")]
+    KeyVersionNotFound,
     /// Encryption passwords don't match
     #[error("Encryption passwords don't match")]
     KeyEncryptionPasswordMismatch,
+    /// Invalid identity name
+    #[error("Invalid identity name, use 1-32 letters, digits, '-' or '_'")]
+    IdentityInvalidName,
+    /// Identity name already used
+    #[error("An identity with this name already exists")]
+    IdentityAlreadyExists,
+    /// No identity with the given name
+    #[error("Identity not found")]
+    IdentityNotFound,
+    /// The last identity cannot be removed
+    #[error("The last identity cannot be removed, clear its keys instead")]
+    IdentityCannotRemoveLast,
+    /// The identity has been retired (its key rotated)
+    #[error("This identity is retired, its key has already been rotated")]
+    IdentityRetired,
     /// Invalid BIP39 mnemonic
     #[error(transparent)]
     KeyMnemonic(#[from] bip39::Error),
+    /// Mnemonic backup words not confirmed
+    #[error("Entered words do not match the mnemonic, check your backup")]
+    MnemonicBackupNotConfirmed,
+    /// No encrypted mnemonic stored for the identity
+    #[error("No mnemonic is stored for this identity (only mnemonics imported or generated in Keystr are stored)")]
+    MnemonicNotStored,
+    /// Invalid account index for mnemonic derivation
+    #[error("Invalid account index, should be a number between 0 and 2147483647")]
+    MnemonicInvalidAccount,
+    /// Sub-keys need the mnemonic
+    #[error("Sub-keys are derived from the mnemonic: reveal the stored mnemonic first")]
+    SubkeyNoMnemonic,
+    /// Invalid or duplicate sub-key label
+    #[error("Invalid sub-key label, use 1-32 characters, different from the other labels")]
+    SubkeyInvalidLabel,
+    /// No sub-key with the given index
+    #[error("Sub-key not found")]
+    SubkeyNotFound,
+    /// Key derived from the mnemonic does not match, passphrase is wrong or missing
+    #[error("Key derived from the mnemonic does not match, check the BIP39 passphrase")]
+    SubkeyPassphraseMismatch,
+    /// Invalid Shamir share count or threshold
+    #[error("Invalid share count or threshold, threshold should be at least 2 and at most the count (max 16)")]
+    ShamirInvalidParameters,
+    /// Invalid Shamir share string
+    #[error("Invalid share, check the share text")]
+    ShamirInvalidShare,
+    /// Not enough distinct shares to restore
+    #[error("Not enough (distinct) shares to restore the key")]
+    ShamirNotEnoughShares,
+    /// Shares are of different splits
+    #[error("Shares do not belong together (different threshold or key)")]
+    ShamirSharesMismatch,
+    /// Restored key does not match the public key of the shares
+    #[error("Restored key does not match the shares, check the shares")]
+    ShamirVerificationFailed,
+    /// Paper backup needs an exported ncryptsec or a revealed mnemonic
+    #[error("Export the ncryptsec (or reveal the mnemonic) first, it is included in the backup")]
+    PaperBackupNoSecret,
+    /// Paper backup file path not given
+    #[error("Enter the file path for the backup")]
+    PaperBackupPathMissing,
+    /// Key file path not given
+    #[error("Enter the path of the key file")]
+    ImportFilePathMissing,
+    /// Key file does not exist
+    #[error("Key file not found")]
+    ImportFileNotFound,
+    /// Key file is too large to be a key file
+    #[error("File is too large for a key file")]
+    ImportFileTooLarge,
+    /// Key file is not text (binary file)
+    #[error("Key file is not a text file")]
+    ImportFileNotText,
+    /// Key file format not recognized
+    #[error("Unknown key file format, expected ncryptsec, JSON, nsec or Keystr encrypted key")]
+    ImportUnknownFormat,
+    /// Key file is a single hex key, it can't be told if it is secret or public
+    #[error("Key file contains a plain hex key, import it as secret or public key instead")]
+    ImportAmbiguousHex,
+    /// Key file looks like JSON but can't be parsed
+    #[error("Invalid JSON in key file")]
+    ImportInvalidJson,
+    /// JSON key file has no known key field
+    #[error("No key field found in JSON key file")]
+    ImportNoKeyInJson,
+    /// Key file contains several different keys
+    #[error("Key file contains more than one key")]
+    ImportMultipleKeys,
+    /// Secret and public key in key file do not belong together
+    #[error("Secret key and public key in key file do not match")]
+    ImportKeyMismatch,
+    /// Backup file path not given
+    #[error("Enter the file path for the backup")]
+    BackupPathMissing,
+    /// Backup file to restore does not exist
+    #[error("Backup file not found")]
+    BackupFileNotFound,
+    /// Data folder has no files to back up
+    #[error("Nothing to back up, no keys or settings saved yet")]
+    BackupNothingToBackup,
+    /// Data folder is too large for a backup
+    #[error("Data folder is too large to back up")]
+    BackupTooLarge,
+    /// Not a backup file, or corrupted
+    #[error("Invalid backup file")]
+    BackupInvalid,
+    /// Backup made by a newer version
+    #[error("Unsupported backup version, made by a newer version?")]
+    BackupUnsupportedVersion,
+    /// QR code generation error
+    #[error(transparent)]
+    QrCodeError(#[from] qrcode::types::QrError),
+    /// Invalid vanity pattern
+    #[error("Invalid pattern, use 1-10 bech32 characters (no '1', 'b', 'i', 'o')")]
+    VanityInvalidPattern,
+    /// Invalid FROST participant count or threshold
+    #[error("Invalid participant count or threshold, threshold should be at least 2 and at most the count (max 16)")]
+    FrostInvalidParameters,
+    /// Invalid or corrupted FROST key share
+    #[error("Invalid FROST key share")]
+    FrostInvalidShare,
+    /// Fewer signers (or local shares) than the threshold
+    #[error("Not enough FROST signers, at least threshold many shares are needed")]
+    FrostNotEnoughSigners,
+    /// Invalid or inconsistent signing commitments
+    #[error("Invalid FROST signing commitments")]
+    FrostInvalidCommitment,
+    /// A partial signature does not verify against the share of its signer
+    #[error("Invalid FROST partial signature")]
+    FrostInvalidPartialSignature,
+    /// The aggregated signature does not verify
+    #[error("Invalid FROST signature")]
+    FrostInvalidSignature,
+    /// Operation needs a single secret key, not available for a FROST group key
+    #[error("Not possible with a FROST group key, there is no single secret key")]
+    FrostNoSingleKey,
     /// BIP32 key derivation error
     #[error(transparent)]
     KeyDerivation(#[from] bip32::Error),
@@ -45,9 +195,48 @@ pub enum Error {
     /// Nip26 error
     #[error(transparent)]
     Nip26Error(#[from] nostr::nips::nip26::Error),
+    /// Invalid event kind
+    #[error("Invalid event kind, should be a number between 0 and 65535")]
+    DelegationInvalidKind,
+    /// Conditions string does not follow the NIP-26 grammar
+    #[error("Invalid delegation conditions, use 'kind=<n>', 'created_at<<time>' or 'created_at><time>', joined by '&'")]
+    DelegationInvalidConditions,
+    /// No delegation tag to verify
+    #[error("Enter a delegation tag, or an event with a delegation tag")]
+    DelegationTagMissing,
+    /// No delegatee to verify the delegation for
+    #[error("Enter the delegatee npub, or an event signed by the delegatee")]
+    DelegationDelegateeMissing,
+    /// Event to verify can't be parsed
+    #[error("Invalid event, paste the signed event as JSON")]
+    DelegationInvalidEvent,
+    /// Time input is not a date-time or a unix timestamp
+    #[error("Invalid time, use e.g. '2024-06-30 12:00' (local time), '2024-06-30T12:00:00Z' or a unix timestamp")]
+    DelegationInvalidTime,
+    /// Duration input can't be parsed
+    #[error("Invalid duration, use e.g. '2h', '90d' or '6 weeks'")]
+    DelegationInvalidDuration,
+    /// Validity end is not after the start
+    #[error("The end of validity should be after the start")]
+    DelegationEndBeforeStart,
+    /// Delegation ledger export file path not given
+    #[error("Enter the file path for the export")]
+    DelegationExportPathMissing,
+    /// Delegation ledger file could not be loaded, it is not overwritten
+    #[error("Delegation ledger file could not be loaded, fix or move it aside, then restart")]
+    DelegationLedgerNotLoaded,
     /// IO error, e.g. file/folder error
     #[error(transparent)]
     IoError(#[from] std::io::Error),
+    /// Data folder path exists, but it is not a folder
+    #[error("Data folder is not a folder")]
+    StorageFolderInvalid,
+    /// Data folder can be set only once, at startup
+    #[error("Data folder is already set")]
+    StorageFolderAlreadySet,
+    /// Portable mode needs the location of the executable
+    #[error("Portable mode: location of the executable is not known")]
+    StoragePortableNoExecutable,
     /// JSON serialization error
     #[error(transparent)]
     JsonError(#[from] serde_json::Error),
@@ -60,12 +249,18 @@ pub enum Error {
     /// Signer is already connected, disconnect first
     #[error("Signer is already connected, disconnect first")]
     SignerAlreadyConnected,
+    /// Signer is locked (secret key locked), requests are queued
+    #[error("Signer is locked, unlock the secret key first")]
+    SignerLocked,
     /// Internal event queue receive error
     #[error(transparent)]
     InternalEventQueueReceive(#[from] crossbeam::channel::RecvError),
     /// Internal event queue send error
     #[error("Internal event queue send error")]
     InternalEventQueueSend,
+    /// Unsigned event error (e.g. invalid signature)
+    #[error(transparent)]
+    UnsignedEventError(#[from] nostr::event::unsigned::Error),
     /// Internal EventBuilder error
     #[error(transparent)]
     EventBuilderError(#[from] nostr::event::builder::Error),
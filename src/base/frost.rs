@@ -0,0 +1,573 @@
+use crate::base::error::Error;
+
+use k256::elliptic_curve::ff::{Field, PrimeField};
+use k256::elliptic_curve::ops::Reduce;
+use k256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use k256::sha2::{Digest, Sha256};
+use k256::{AffinePoint, EncodedPoint, FieldBytes, ProjectivePoint, Scalar, U256};
+use rand_core::OsRng;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Version of the serialized key share format
+const SHARE_VERSION: u8 = 1;
+/// Max number of participants
+pub(crate) const MAX_PARTICIPANTS: u8 = 16;
+/// Length of a compressed point
+const POINT_LEN: usize = 33;
+/// Domain separation tag for the binding factors
+const TAG_BINDING: &str = "FROST/secp256k1/rho";
+/// BIP-340 challenge tag, the signature has to verify as a normal BIP-340 signature
+const TAG_CHALLENGE: &str = "BIP0340/challenge";
+
+/// FROST threshold Schnorr signatures (t-of-n) over secp256k1, producing BIP-340 signatures.
+///
+/// Key generation uses a trusted dealer: a random group secret key is split with Shamir's
+/// scheme into n shares (the secret is negated if needed, so that the group public key has
+/// even y, as BIP-340 requires); the group secret is not kept. Each share holds the group
+/// public key, and the verifying shares (public keys of the shares) of all participants.
+///
+/// Signing (two rounds, as in FROST, RFC 9591):
+/// 1. Each signer creates two nonces, and publishes their commitments (D_i, E_i).
+/// 2. With the commitments of all signers, binding factors
+///    rho_i = H(group key | message | commitments | i), the group commitment
+///    R = sum(D_i + rho_i * E_i), and the BIP-340 challenge c = H(R.x | group key | message)
+///    are computed. If R has odd y, the nonces are negated. Each signer returns
+///    z_i = d_i + rho_i * e_i + lambda_i * s_i * c, with lambda_i the Lagrange coefficient.
+///
+/// The signature is (R.x, sum(z_i)). Partial signatures are verified against the verifying
+/// shares, and the final signature as a BIP-340 signature.
+pub(crate) struct Frost {}
+
+/// The key share of a participant.
+/// Intentionally not Clone and not Debug, the secret share is zeroized on drop.
+pub(crate) struct FrostKeyShare {
+    /// Participant index, 1-based
+    index: u8,
+    threshold: u8,
+    secret: Scalar,
+    /// x-only group public key
+    group_public_key: [u8; 32],
+    /// Public keys of the shares of all participants (compressed), in index order
+    verifying_shares: Vec<[u8; POINT_LEN]>,
+}
+
+/// Secret nonces of a signer for one signing, zeroized on drop; use them only once
+pub(crate) struct FrostNonces {
+    hiding: Scalar,
+    binding: Scalar,
+}
+
+/// Public commitments to the nonces of a signer
+#[derive(Clone, Debug)]
+pub(crate) struct FrostCommitment {
+    pub index: u8,
+    hiding: [u8; POINT_LEN],
+    binding: [u8; POINT_LEN],
+}
+
+/// A partial signature of a signer
+#[derive(Clone, Debug)]
+pub(crate) struct FrostPartialSignature {
+    pub index: u8,
+    z: [u8; 32],
+}
+
+impl Drop for FrostKeyShare {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+impl Drop for FrostNonces {
+    fn drop(&mut self) {
+        self.hiding.zeroize();
+        self.binding.zeroize();
+    }
+}
+
+impl Frost {
+    /// Warning: Security-sensitive method!
+    /// Generate a new group key, as `count` shares, any `threshold` of them can sign
+    pub fn generate_with_dealer(threshold: u8, count: u8) -> Result<Vec<FrostKeyShare>, Error> {
+        if threshold < 2 || threshold > count || count > MAX_PARTICIPANTS {
+            return Err(Error::FrostInvalidParameters);
+        }
+        let mut coefficients = Zeroizing::new(
+            (0..threshold)
+                .map(|_| Scalar::random(&mut OsRng))
+                .collect::<Vec<Scalar>>(),
+        );
+        let (group_public_key, is_odd) =
+            Self::x_only(&(ProjectivePoint::GENERATOR * coefficients[0]));
+        if is_odd {
+            coefficients[0] = -coefficients[0];
+        }
+        let secrets: Vec<Scalar> = (1..=count)
+            .map(|index| Self::evaluate(&coefficients, index))
+            .collect();
+        let verifying_shares: Vec<[u8; POINT_LEN]> = secrets
+            .iter()
+            .map(|s| Self::encode_point(&(ProjectivePoint::GENERATOR * s)))
+            .collect();
+        Ok(secrets
+            .into_iter()
+            .zip(1..=count)
+            .map(|(secret, index)| FrostKeyShare {
+                index,
+                threshold,
+                secret,
+                group_public_key,
+                verifying_shares: verifying_shares.clone(),
+            })
+            .collect())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Signing round 1: create nonces, and the commitments to be sent to the other signers
+    pub fn commit(share: &FrostKeyShare) -> (FrostNonces, FrostCommitment) {
+        let nonces = FrostNonces {
+            hiding: Scalar::random(&mut OsRng),
+            binding: Scalar::random(&mut OsRng),
+        };
+        let commitment = FrostCommitment {
+            index: share.index,
+            hiding: Self::encode_point(&(ProjectivePoint::GENERATOR * nonces.hiding)),
+            binding: Self::encode_point(&(ProjectivePoint::GENERATOR * nonces.binding)),
+        };
+        (nonces, commitment)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Signing round 2: create the partial signature of a signer, with the commitments of all
+    /// signers (including its own). The nonces are consumed.
+    pub fn sign_partial(
+        share: &FrostKeyShare,
+        nonces: FrostNonces,
+        message: &[u8; 32],
+        commitments: &[FrostCommitment],
+    ) -> Result<FrostPartialSignature, Error> {
+        let session = Session::new(
+            &share.group_public_key,
+            share.threshold,
+            message,
+            commitments,
+        )?;
+        let own = session.signer(share.index)?;
+        // the nonces have to match the commitment sent in round 1 (both of them)
+        if Self::encode_point(&(ProjectivePoint::GENERATOR * nonces.hiding))
+            != commitments[own].hiding
+            || Self::encode_point(&(ProjectivePoint::GENERATOR * nonces.binding))
+                != commitments[own].binding
+        {
+            return Err(Error::FrostInvalidCommitment);
+        }
+        let (mut hiding, mut binding) = (nonces.hiding, nonces.binding);
+        if session.is_r_odd {
+            hiding = -hiding;
+            binding = -binding;
+        }
+        let z = hiding
+            + binding * session.binding_factors[own]
+            + session.lambdas[own] * share.secret * session.challenge;
+        hiding.zeroize();
+        binding.zeroize();
+        Ok(FrostPartialSignature {
+            index: share.index,
+            z: z.to_bytes().into(),
+        })
+    }
+
+    /// Combine the partial signatures into a BIP-340 signature (64 bytes).
+    /// Each partial signature is verified, and the result too.
+    /// Any key share of the group can be used, only its public data is needed.
+    pub fn aggregate(
+        share: &FrostKeyShare,
+        message: &[u8; 32],
+        commitments: &[FrostCommitment],
+        partial_signatures: &[FrostPartialSignature],
+    ) -> Result<[u8; 64], Error> {
+        let session = Session::new(
+            &share.group_public_key,
+            share.threshold,
+            message,
+            commitments,
+        )?;
+        if partial_signatures.len() != commitments.len() {
+            return Err(Error::FrostNotEnoughSigners);
+        }
+        let mut z_sum = Scalar::ZERO;
+        for partial in partial_signatures {
+            let i = session.signer(partial.index)?;
+            let z = Option::<Scalar>::from(Scalar::from_repr(partial.z.into()))
+                .ok_or(Error::FrostInvalidPartialSignature)?;
+            // z_i * G == +-(D_i + rho_i * E_i) + c * lambda_i * Y_i
+            let verifying_share = share
+                .verifying_shares
+                .get(partial.index as usize - 1)
+                .ok_or(Error::FrostInvalidShare)?;
+            let mut nonce_commitment = session.commitment_points[i].0
+                + session.commitment_points[i].1 * session.binding_factors[i];
+            if session.is_r_odd {
+                nonce_commitment = -nonce_commitment;
+            }
+            let expected = nonce_commitment
+                + Self::decode_point(verifying_share)? * (session.challenge * session.lambdas[i]);
+            if ProjectivePoint::GENERATOR * z != expected {
+                return Err(Error::FrostInvalidPartialSignature);
+            }
+            z_sum += z;
+        }
+        let mut signature = [0u8; 64];
+        signature[0..32].copy_from_slice(&session.r_x);
+        signature[32..64].copy_from_slice(&z_sum.to_bytes());
+        Self::verify(&share.group_public_key, message, &signature)?;
+        Ok(signature)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Run a complete signing ceremony locally, with the given shares (at least threshold many)
+    pub fn sign_local(shares: &[&FrostKeyShare], message: &[u8; 32]) -> Result<[u8; 64], Error> {
+        let first = shares.first().ok_or(Error::FrostNotEnoughSigners)?;
+        let (nonces, commitments): (Vec<FrostNonces>, Vec<FrostCommitment>) =
+            shares.iter().map(|share| Self::commit(share)).unzip();
+        let mut partial_signatures = Vec::new();
+        for (share, nonces) in shares.iter().zip(nonces) {
+            partial_signatures.push(Self::sign_partial(share, nonces, message, &commitments)?);
+        }
+        Self::aggregate(first, message, &commitments, &partial_signatures)
+    }
+
+    /// Verify a BIP-340 signature
+    pub fn verify(
+        public_key: &[u8; 32],
+        message: &[u8; 32],
+        signature: &[u8; 64],
+    ) -> Result<(), Error> {
+        let verifying_key = k256::schnorr::VerifyingKey::from_bytes(public_key)
+            .map_err(|_e| Error::FrostInvalidSignature)?;
+        let signature = k256::schnorr::Signature::try_from(&signature[..])
+            .map_err(|_e| Error::FrostInvalidSignature)?;
+        verifying_key
+            .verify_raw(message, &signature)
+            .map_err(|_e| Error::FrostInvalidSignature)
+    }
+
+    /// Evaluate polynomial (coefficients from constant term up) at x
+    fn evaluate(coefficients: &[Scalar], x: u8) -> Scalar {
+        let x = Scalar::from(x as u64);
+        coefficients
+            .iter()
+            .rev()
+            .fold(Scalar::ZERO, |acc, c| acc * x + c)
+    }
+
+    /// Lagrange coefficient of a participant at x=0, for the given set of participants
+    fn lagrange(index: u8, indexes: &[u8]) -> Result<Scalar, Error> {
+        let x_i = Scalar::from(index as u64);
+        let mut num = Scalar::ONE;
+        let mut den = Scalar::ONE;
+        for j in indexes.iter().filter(|j| **j != index) {
+            let x_j = Scalar::from(*j as u64);
+            num *= x_j;
+            den *= x_j - x_i;
+        }
+        Option::<Scalar>::from(den.invert())
+            .map(|den_inv| num * den_inv)
+            .ok_or(Error::FrostInvalidCommitment)
+    }
+
+    /// BIP-340 tagged hash, reduced to a scalar
+    fn tagged_hash(tag: &str, data: &[&[u8]]) -> Scalar {
+        let tag_hash = Sha256::digest(tag.as_bytes());
+        let mut hasher = Sha256::new();
+        hasher.update(tag_hash);
+        hasher.update(tag_hash);
+        for d in data {
+            hasher.update(d);
+        }
+        <Scalar as Reduce<U256>>::reduce_bytes(&hasher.finalize())
+    }
+
+    /// x coordinate of a point, and whether its y is odd
+    fn x_only(point: &ProjectivePoint) -> ([u8; 32], bool) {
+        let encoded = Self::encode_point(point);
+        let mut x = [0u8; 32];
+        x.copy_from_slice(&encoded[1..]);
+        (x, encoded[0] == 0x03)
+    }
+
+    fn encode_point(point: &ProjectivePoint) -> [u8; POINT_LEN] {
+        let mut encoded = [0u8; POINT_LEN];
+        encoded.copy_from_slice(point.to_affine().to_encoded_point(true).as_bytes());
+        encoded
+    }
+
+    fn decode_point(encoded: &[u8; POINT_LEN]) -> Result<ProjectivePoint, Error> {
+        let encoded =
+            EncodedPoint::from_bytes(encoded).map_err(|_e| Error::FrostInvalidCommitment)?;
+        Option::<AffinePoint>::from(AffinePoint::from_encoded_point(&encoded))
+            .map(ProjectivePoint::from)
+            .ok_or(Error::FrostInvalidCommitment)
+    }
+}
+
+/// Values of a signing session derived from the commitments, common to all signers
+struct Session {
+    /// Signer indexes, in increasing order
+    indexes: Vec<u8>,
+    commitment_points: Vec<(ProjectivePoint, ProjectivePoint)>,
+    binding_factors: Vec<Scalar>,
+    lambdas: Vec<Scalar>,
+    r_x: [u8; 32],
+    is_r_odd: bool,
+    challenge: Scalar,
+}
+
+impl Session {
+    fn new(
+        group_public_key: &[u8; 32],
+        threshold: u8,
+        message: &[u8; 32],
+        commitments: &[FrostCommitment],
+    ) -> Result<Self, Error> {
+        if commitments.len() < threshold as usize {
+            return Err(Error::FrostNotEnoughSigners);
+        }
+        // commitments have to be in increasing index order, without repetition
+        if commitments
+            .windows(2)
+            .any(|pair| pair[0].index >= pair[1].index)
+            || commitments[0].index == 0
+        {
+            return Err(Error::FrostInvalidCommitment);
+        }
+        let indexes: Vec<u8> = commitments.iter().map(|c| c.index).collect();
+        let mut encoded_commitments = Vec::new();
+        let mut commitment_points = Vec::new();
+        for c in commitments {
+            encoded_commitments.push(c.index);
+            encoded_commitments.extend_from_slice(&c.hiding);
+            encoded_commitments.extend_from_slice(&c.binding);
+            commitment_points.push((
+                Frost::decode_point(&c.hiding)?,
+                Frost::decode_point(&c.binding)?,
+            ));
+        }
+        let binding_factors: Vec<Scalar> = indexes
+            .iter()
+            .map(|index| {
+                Frost::tagged_hash(
+                    TAG_BINDING,
+                    &[group_public_key, message, &encoded_commitments, &[*index]],
+                )
+            })
+            .collect();
+        let group_commitment = commitment_points
+            .iter()
+            .zip(binding_factors.iter())
+            .fold(ProjectivePoint::IDENTITY, |acc, ((d, e), rho)| {
+                acc + d + *e * rho
+            });
+        let (r_x, is_r_odd) = Frost::x_only(&group_commitment);
+        let challenge = Frost::tagged_hash(TAG_CHALLENGE, &[&r_x, group_public_key, message]);
+        let lambdas = indexes
+            .iter()
+            .map(|index| Frost::lagrange(*index, &indexes))
+            .collect::<Result<Vec<Scalar>, Error>>()?;
+        Ok(Self {
+            indexes,
+            commitment_points,
+            binding_factors,
+            lambdas,
+            r_x,
+            is_r_odd,
+            challenge,
+        })
+    }
+
+    /// Position of a signer in the session
+    fn signer(&self, index: u8) -> Result<usize, Error> {
+        self.indexes
+            .iter()
+            .position(|i| *i == index)
+            .ok_or(Error::FrostNotEnoughSigners)
+    }
+}
+
+impl FrostKeyShare {
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    pub fn count(&self) -> u8 {
+        self.verifying_shares.len() as u8
+    }
+
+    /// The x-only group public key
+    pub fn group_public_key(&self) -> &[u8; 32] {
+        &self.group_public_key
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Serialize: version | threshold | count | index | secret | group public key | verifying shares
+    pub fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        let mut data = Zeroizing::new(Vec::with_capacity(
+            4 + 32 + 32 + POINT_LEN * self.verifying_shares.len(),
+        ));
+        data.extend_from_slice(&[SHARE_VERSION, self.threshold, self.count(), self.index]);
+        data.extend_from_slice(&self.secret.to_bytes());
+        data.extend_from_slice(&self.group_public_key);
+        for verifying_share in &self.verifying_shares {
+            data.extend_from_slice(verifying_share);
+        }
+        data
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Deserialize, and check that the secret matches the verifying share
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < 4 || data[0] != SHARE_VERSION {
+            return Err(Error::FrostInvalidShare);
+        }
+        let (threshold, count, index) = (data[1], data[2], data[3]);
+        if threshold < 2
+            || threshold > count
+            || count > MAX_PARTICIPANTS
+            || index == 0
+            || index > count
+            || data.len() != 4 + 32 + 32 + POINT_LEN * count as usize
+        {
+            return Err(Error::FrostInvalidShare);
+        }
+        let secret =
+            Option::<Scalar>::from(Scalar::from_repr(*FieldBytes::from_slice(&data[4..36])))
+                .ok_or(Error::FrostInvalidShare)?;
+        let mut share = FrostKeyShare {
+            index,
+            threshold,
+            secret,
+            group_public_key: [0; 32],
+            verifying_shares: Vec::new(),
+        };
+        share.group_public_key.copy_from_slice(&data[36..68]);
+        for chunk in data[68..].chunks(POINT_LEN) {
+            let mut verifying_share = [0; POINT_LEN];
+            verifying_share.copy_from_slice(chunk);
+            share.verifying_shares.push(verifying_share);
+        }
+        if Frost::encode_point(&(ProjectivePoint::GENERATOR * share.secret))
+            != share.verifying_shares[index as usize - 1]
+        {
+            return Err(Error::FrostInvalidShare);
+        }
+        Ok(share)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MESSAGE: [u8; 32] = [42u8; 32];
+
+    #[test]
+    fn test_ceremony_2_of_3() {
+        let shares = Frost::generate_with_dealer(2, 3).unwrap();
+        assert_eq!(shares.len(), 3);
+        let group_public_key = *shares[0].group_public_key();
+        assert!(shares
+            .iter()
+            .all(|s| *s.group_public_key() == group_public_key));
+
+        // round 1: signers 1 and 3 commit
+        let (nonces1, commitment1) = Frost::commit(&shares[0]);
+        let (nonces3, commitment3) = Frost::commit(&shares[2]);
+        let commitments = vec![commitment1, commitment3];
+        // round 2: partial signatures
+        let partial1 = Frost::sign_partial(&shares[0], nonces1, &MESSAGE, &commitments).unwrap();
+        let partial3 = Frost::sign_partial(&shares[2], nonces3, &MESSAGE, &commitments).unwrap();
+        // aggregation, by anyone in the group
+        let signature =
+            Frost::aggregate(&shares[1], &MESSAGE, &commitments, &[partial1, partial3]).unwrap();
+        Frost::verify(&group_public_key, &MESSAGE, &signature).unwrap();
+
+        // any 2 of 3, or all 3
+        for signers in [[0, 1].as_slice(), &[1, 2], &[0, 1, 2]] {
+            let signer_shares: Vec<&FrostKeyShare> = signers.iter().map(|i| &shares[*i]).collect();
+            let signature = Frost::sign_local(&signer_shares, &MESSAGE).unwrap();
+            Frost::verify(&group_public_key, &MESSAGE, &signature).unwrap();
+            assert!(Frost::verify(&group_public_key, &[0u8; 32], &signature).is_err());
+        }
+    }
+
+    #[test]
+    fn test_not_enough_signers() {
+        let shares = Frost::generate_with_dealer(2, 3).unwrap();
+        assert!(matches!(
+            Frost::sign_local(&[&shares[0]], &MESSAGE),
+            Err(Error::FrostNotEnoughSigners)
+        ));
+        // shares of another group
+        let other = Frost::generate_with_dealer(2, 3).unwrap();
+        assert!(Frost::sign_local(&[&shares[0], &other[1]], &MESSAGE).is_err());
+    }
+
+    #[test]
+    fn test_invalid_partial_signature() {
+        let shares = Frost::generate_with_dealer(2, 2).unwrap();
+        let (nonces1, commitment1) = Frost::commit(&shares[0]);
+        let (nonces2, commitment2) = Frost::commit(&shares[1]);
+        let commitments = vec![commitment1, commitment2];
+        let partial1 = Frost::sign_partial(&shares[0], nonces1, &MESSAGE, &commitments).unwrap();
+        let mut partial2 =
+            Frost::sign_partial(&shares[1], nonces2, &MESSAGE, &commitments).unwrap();
+        partial2.z[31] ^= 1;
+        assert!(matches!(
+            Frost::aggregate(&shares[0], &MESSAGE, &commitments, &[partial1, partial2]),
+            Err(Error::FrostInvalidPartialSignature)
+        ));
+    }
+
+    #[test]
+    fn test_commitment_mismatch() {
+        let shares = Frost::generate_with_dealer(2, 2).unwrap();
+        let (nonces1, mut commitment1) = Frost::commit(&shares[0]);
+        let (_nonces2, commitment2) = Frost::commit(&shares[1]);
+        // binding commitment replaced by one of other nonces, the hiding one is still correct
+        let (_other_nonces, other_commitment) = Frost::commit(&shares[0]);
+        commitment1.binding = other_commitment.binding;
+        assert!(matches!(
+            Frost::sign_partial(&shares[0], nonces1, &MESSAGE, &[commitment1, commitment2]),
+            Err(Error::FrostInvalidCommitment)
+        ));
+    }
+
+    #[test]
+    fn test_share_serialization() {
+        let shares = Frost::generate_with_dealer(3, 5).unwrap();
+        let data = shares[3].to_bytes();
+        let share = FrostKeyShare::from_bytes(&data).unwrap();
+        assert_eq!(share.index(), 4);
+        assert_eq!(share.threshold(), 3);
+        assert_eq!(share.count(), 5);
+        assert_eq!(share.group_public_key(), shares[3].group_public_key());
+        let signature = Frost::sign_local(&[&shares[0], &share, &shares[4]], &MESSAGE).unwrap();
+        Frost::verify(shares[0].group_public_key(), &MESSAGE, &signature).unwrap();
+
+        let mut corrupted = data.to_vec();
+        corrupted[10] ^= 1;
+        assert!(FrostKeyShare::from_bytes(&corrupted).is_err());
+        assert!(FrostKeyShare::from_bytes(&data[0..40]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        assert!(Frost::generate_with_dealer(1, 3).is_err());
+        assert!(Frost::generate_with_dealer(4, 3).is_err());
+        assert!(Frost::generate_with_dealer(2, MAX_PARTICIPANTS + 1).is_err());
+    }
+}
@@ -0,0 +1,366 @@
+use crate::base::encrypt::Encrypt;
+use crate::base::error::Error;
+use crate::base::secret::SecretKeyHolder;
+
+use nostr::prelude::{FromPkStr, Keys};
+use serde_json::Value;
+use zeroize::{Zeroize, Zeroizing};
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Max size of a key file; key files are small, a larger file is likely something else
+const MAX_FILE_SIZE: u64 = 64 * 1024;
+/// Prefix of NIP-49 encrypted secret keys
+const NCRYPTSEC_PREFIX: &str = "ncryptsec1";
+/// Prefix of bech32 secret keys
+const NSEC_PREFIX: &str = "nsec1";
+/// Prefix of bech32 public keys
+const NPUB_PREFIX: &str = "npub1";
+/// JSON field names (lowercase) holding a secret key, in nsec or hex format
+const JSON_SECRET_KEY_FIELDS: &[&str] = &[
+    "nsec",
+    "privkey",
+    "private_key",
+    "privatekey",
+    "private_key_hex",
+    "secret_key",
+    "secretkey",
+    "seckey",
+    "sk",
+];
+/// JSON field names (lowercase) holding a NIP-49 encrypted secret key
+const JSON_ENCRYPTED_KEY_FIELDS: &[&str] = &[
+    "ncryptsec",
+    "encrypted_private_key",
+    "encrypted_privkey",
+    "encryptedprivatekey",
+];
+/// JSON field names (lowercase) holding a public key, in npub or hex format
+const JSON_PUBLIC_KEY_FIELDS: &[&str] = &[
+    "npub",
+    "pubkey",
+    "public_key",
+    "publickey",
+    "public_key_hex",
+    "pk",
+];
+
+/// Format of a key file, as detected from its contents
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum KeyFileFormat {
+    /// NIP-49 'ncryptsec' string
+    Ncryptsec,
+    /// JSON export of a client (e.g. nostr-tool, gossip), with key fields
+    Json,
+    /// Text containing a key, e.g. an 'nsec'
+    PlainText,
+    /// Keystr encrypted secret key (hex, '.ncrypt' file), e.g. from another machine
+    KeystrEncrypted,
+}
+
+impl fmt::Display for KeyFileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyFileFormat::Ncryptsec => write!(f, "ncryptsec"),
+            KeyFileFormat::Json => write!(f, "JSON"),
+            KeyFileFormat::PlainText => write!(f, "plain text"),
+            KeyFileFormat::KeystrEncrypted => write!(f, "Keystr encrypted"),
+        }
+    }
+}
+
+/// Key found in a key file
+pub(crate) enum KeyFileKey {
+    /// Secret key, in 'nsec' or hex format
+    SecretKey(Zeroizing<String>),
+    /// NIP-49 encrypted secret key, needs the password
+    Ncryptsec(String),
+    /// Keystr encrypted secret key, needs the password
+    KeystrEncrypted(Vec<u8>),
+    /// Public key only, in 'npub' or hex format
+    PublicKey(String),
+}
+
+/// Reading keys from key files of other clients (or of Keystr on another machine),
+/// with format auto-detection
+pub(crate) struct KeyFile {}
+
+impl KeyFile {
+    /// Warning: Security-sensitive method!
+    /// Read a key file, detect its format and extract the key
+    pub fn read(path: &Path) -> Result<(KeyFileFormat, KeyFileKey), Error> {
+        if !path.is_file() {
+            return Err(Error::ImportFileNotFound);
+        }
+        if fs::metadata(path)?.len() > MAX_FILE_SIZE {
+            return Err(Error::ImportFileTooLarge);
+        }
+        let contents = Zeroizing::new(fs::read(path)?);
+        let text = std::str::from_utf8(&contents).map_err(|_e| Error::ImportFileNotText)?;
+        Self::parse(text)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Detect the format of key file contents, and extract the key
+    pub fn parse(contents: &str) -> Result<(KeyFileFormat, KeyFileKey), Error> {
+        let contents = contents.trim_start_matches('\u{feff}').trim();
+        if contents.starts_with('{') || contents.starts_with('[') {
+            return Ok((KeyFileFormat::Json, Self::parse_json(contents)?));
+        }
+        if contents.starts_with(NCRYPTSEC_PREFIX) && !contents.contains(char::is_whitespace) {
+            return Ok((
+                KeyFileFormat::Ncryptsec,
+                KeyFileKey::Ncryptsec(contents.to_string()),
+            ));
+        }
+        if let Ok(data) = hex::decode(contents) {
+            // 32 bytes could be a secret or a public key
+            if data.len() == 32 {
+                return Err(Error::ImportAmbiguousHex);
+            }
+            if Encrypt::get_kdf_params(&data).is_ok() {
+                return Ok((
+                    KeyFileFormat::KeystrEncrypted,
+                    KeyFileKey::KeystrEncrypted(data),
+                ));
+            }
+            return Err(Error::ImportUnknownFormat);
+        }
+        Ok((KeyFileFormat::PlainText, Self::parse_text(contents)?))
+    }
+
+    /// Look for a (single) bech32 key in text, secret key preferred
+    fn parse_text(contents: &str) -> Result<KeyFileKey, Error> {
+        if let Some(nsec) = Self::find_token(contents, NSEC_PREFIX)? {
+            return Ok(KeyFileKey::SecretKey(Zeroizing::new(nsec.to_string())));
+        }
+        if let Some(ncryptsec) = Self::find_token(contents, NCRYPTSEC_PREFIX)? {
+            return Ok(KeyFileKey::Ncryptsec(ncryptsec.to_string()));
+        }
+        if let Some(npub) = Self::find_token(contents, NPUB_PREFIX)? {
+            return Ok(KeyFileKey::PublicKey(npub.to_string()));
+        }
+        Err(Error::ImportUnknownFormat)
+    }
+
+    /// Find the word starting with the prefix; the same word may be repeated, but not different ones
+    fn find_token<'a>(contents: &'a str, prefix: &str) -> Result<Option<&'a str>, Error> {
+        let mut found: Option<&str> = None;
+        for token in contents
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|t| t.starts_with(prefix))
+        {
+            match found {
+                Some(f) if f != token => return Err(Error::ImportMultipleKeys),
+                _ => found = Some(token),
+            }
+        }
+        Ok(found)
+    }
+
+    /// Look for key fields in JSON (at any depth); secret key preferred, then encrypted, then public key.
+    /// If both a secret and a public key is present, they have to match.
+    fn parse_json(contents: &str) -> Result<KeyFileKey, Error> {
+        let mut value: Value =
+            serde_json::from_str(contents).map_err(|_e| Error::ImportInvalidJson)?;
+        let res = Self::key_from_json(&value);
+        Self::zeroize_json(&mut value);
+        res
+    }
+
+    fn key_from_json(value: &Value) -> Result<KeyFileKey, Error> {
+        let secret = Self::find_json_field(value, JSON_SECRET_KEY_FIELDS);
+        let encrypted = Self::find_json_field(value, JSON_ENCRYPTED_KEY_FIELDS);
+        let public = Self::find_json_field(value, JSON_PUBLIC_KEY_FIELDS);
+        match (secret, encrypted, public) {
+            // some exports put an ncryptsec in the secret key field
+            (Some(secret), _, _) if secret.starts_with(NCRYPTSEC_PREFIX) => {
+                Ok(KeyFileKey::Ncryptsec(secret.to_string()))
+            }
+            (Some(secret), _, public) => {
+                let secret = Zeroizing::new(secret.to_string());
+                if let Some(public) = public {
+                    let secret_key = SecretKeyHolder::from_sk_str(&secret)?;
+                    if secret_key.public_key() != Keys::from_pk_str(public)?.public_key() {
+                        return Err(Error::ImportKeyMismatch);
+                    }
+                }
+                Ok(KeyFileKey::SecretKey(secret))
+            }
+            (None, Some(encrypted), _) => Ok(KeyFileKey::Ncryptsec(encrypted.to_string())),
+            (None, None, Some(public)) => Ok(KeyFileKey::PublicKey(public.to_string())),
+            (None, None, None) => Err(Error::ImportNoKeyInJson),
+        }
+    }
+
+    /// Find the first non-empty string field with one of the names (case-insensitive),
+    /// top-level fields first
+    fn find_json_field<'a>(value: &'a Value, names: &[&str]) -> Option<&'a str> {
+        match value {
+            Value::Object(map) => map
+                .iter()
+                .find_map(|(k, v)| match v {
+                    Value::String(s)
+                        if !s.is_empty() && names.contains(&k.to_lowercase().as_str()) =>
+                    {
+                        Some(s.trim())
+                    }
+                    _ => None,
+                })
+                .or_else(|| map.values().find_map(|v| Self::find_json_field(v, names))),
+            Value::Array(items) => items.iter().find_map(|v| Self::find_json_field(v, names)),
+            _ => None,
+        }
+    }
+
+    /// Overwrite all strings of parsed JSON, as it may contain a secret key
+    fn zeroize_json(value: &mut Value) {
+        match value {
+            Value::String(s) => s.zeroize(),
+            Value::Object(map) => map.values_mut().for_each(Self::zeroize_json),
+            Value::Array(items) => items.iter_mut().for_each(Self::zeroize_json),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::encrypt::KeySecurity;
+
+    const NSEC: &str = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+    const NSEC_HEX: &str = "b2f3673ee3a659283e6599080e0ab0e669a3c2640914375a9b0b357faae08b17";
+    const NPUB: &str = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+    const OTHER_NSEC: &str = "nsec16awa8nftexjs4nk8zfl5wrrtc6a7hhycj7p8ztlf4dfy5xa9dcnslmfkz5";
+    const OTHER_NPUB: &str = "npub1h652adkpv4lr8k66cadg8yg0wl5wcc29z4lyw66m3rrwskcl4v6qr82xez";
+    const NCRYPTSEC: &str = "ncryptsec1qgg9947rlpvqu76pj5ecreduf9jxhselq2nae2kghhvd5g7dgjtcxfqtd67p9m0w57lspw8gsq6yphnm8623nsl8xn9j4jdzz84zm3frztj3z7s35vpzmqf6ksu8r89qk5z2zxfmu5gv8th8wclt0h4p";
+
+    #[test]
+    fn test_parse_ncryptsec() {
+        let (format, key) = KeyFile::parse(&format!("{}\n", NCRYPTSEC)).unwrap();
+        assert_eq!(format, KeyFileFormat::Ncryptsec);
+        assert!(matches!(key, KeyFileKey::Ncryptsec(s) if s == NCRYPTSEC));
+    }
+
+    #[test]
+    fn test_parse_plain_text() {
+        let (format, key) = KeyFile::parse(&format!("My key:\n  nsec: {}\n", NSEC)).unwrap();
+        assert_eq!(format, KeyFileFormat::PlainText);
+        assert!(matches!(key, KeyFileKey::SecretKey(s) if s.as_str() == NSEC));
+
+        // same key repeated is fine, different keys are not
+        assert!(KeyFile::parse(&format!("{} {}", NSEC, NSEC)).is_ok());
+        assert!(matches!(
+            KeyFile::parse(&format!("{}\n{}", NSEC, OTHER_NSEC)),
+            Err(Error::ImportMultipleKeys)
+        ));
+
+        let (_format, key) = KeyFile::parse(&format!("npub: {}", NPUB)).unwrap();
+        assert!(matches!(key, KeyFileKey::PublicKey(s) if s == NPUB));
+
+        assert!(matches!(
+            KeyFile::parse("hello world"),
+            Err(Error::ImportUnknownFormat)
+        ));
+        assert!(matches!(
+            KeyFile::parse(""),
+            Err(Error::ImportUnknownFormat)
+        ));
+    }
+
+    #[test]
+    fn test_parse_json() {
+        // nostr-tool style
+        let json = format!(
+            r#"{{"private_key_hex": "{}", "npub": "{}"}}"#,
+            NSEC_HEX, NPUB
+        );
+        let (format, key) = KeyFile::parse(&json).unwrap();
+        assert_eq!(format, KeyFileFormat::Json);
+        assert!(matches!(key, KeyFileKey::SecretKey(s) if s.as_str() == NSEC_HEX));
+
+        // gossip style, nested
+        let json = format!(
+            r#"{{"settings": {{"public_key": "{}", "Encrypted_Private_Key": "{}"}}}}"#,
+            NPUB, NCRYPTSEC
+        );
+        let (_format, key) = KeyFile::parse(&json).unwrap();
+        assert!(matches!(key, KeyFileKey::Ncryptsec(s) if s == NCRYPTSEC));
+
+        // public key only
+        let (_format, key) = KeyFile::parse(&format!(r#"[{{"pubkey": "{}"}}]"#, NPUB)).unwrap();
+        assert!(matches!(key, KeyFileKey::PublicKey(s) if s == NPUB));
+
+        // secret and public key of different keys
+        let json = format!(r#"{{"nsec": "{}", "npub": "{}"}}"#, NSEC, OTHER_NPUB);
+        assert!(matches!(
+            KeyFile::parse(&json),
+            Err(Error::ImportKeyMismatch)
+        ));
+
+        assert!(matches!(
+            KeyFile::parse(r#"{"name": "alice"}"#),
+            Err(Error::ImportNoKeyInJson)
+        ));
+        assert!(matches!(
+            KeyFile::parse(r#"{"nsec": "#),
+            Err(Error::ImportInvalidJson)
+        ));
+    }
+
+    #[test]
+    fn test_parse_keystr_encrypted() {
+        let encrypted = SecretKeyHolder::from_sk_str(NSEC)
+            .unwrap()
+            .with_secret_key(|sk| {
+                Encrypt::encrypt_key(
+                    sk,
+                    "password",
+                    Encrypt::min_log2_rounds(),
+                    KeySecurity::Medium,
+                )
+            })
+            .unwrap()
+            .unwrap();
+        let (format, key) = KeyFile::parse(&hex::encode(&encrypted)).unwrap();
+        assert_eq!(format, KeyFileFormat::KeystrEncrypted);
+        assert!(matches!(key, KeyFileKey::KeystrEncrypted(d) if d == encrypted));
+
+        // plain hex key: secret or public?
+        assert!(matches!(
+            KeyFile::parse(&hex::encode([1u8; 32])),
+            Err(Error::ImportAmbiguousHex)
+        ));
+        assert!(matches!(
+            KeyFile::parse("00ff00ff"),
+            Err(Error::ImportUnknownFormat)
+        ));
+    }
+
+    #[test]
+    fn test_read() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("keystr-test-key-file-{}", std::process::id()));
+        assert!(matches!(
+            KeyFile::read(&path),
+            Err(Error::ImportFileNotFound)
+        ));
+        fs::write(&path, [0xffu8, 0xfe, 0x00]).unwrap();
+        assert!(matches!(
+            KeyFile::read(&path),
+            Err(Error::ImportFileNotText)
+        ));
+        fs::write(&path, vec![b'a'; MAX_FILE_SIZE as usize + 1]).unwrap();
+        assert!(matches!(
+            KeyFile::read(&path),
+            Err(Error::ImportFileTooLarge)
+        ));
+        fs::write(&path, NSEC).unwrap();
+        let (format, _key) = KeyFile::read(&path).unwrap();
+        assert_eq!(format, KeyFileFormat::PlainText);
+        fs::remove_file(&path).unwrap();
+    }
+}
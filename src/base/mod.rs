@@ -1,4 +1,13 @@
+pub mod backup_archive;
 pub mod encrypt;
 pub mod error;
+pub mod frost;
+pub mod key_file;
+pub mod nip06;
+pub mod paper_backup;
 // mod nostr_libs;
+pub mod secret;
+pub mod shamir;
 pub mod storage;
+pub mod storage_backend;
+pub mod time_input;
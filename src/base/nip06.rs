@@ -0,0 +1,162 @@
+use crate::base::error::Error;
+use nostr::prelude::SecretKey;
+use rand_core::{OsRng, RngCore};
+use std::ops::Range;
+use zeroize::Zeroize;
+
+/// Largest account index; the account is a hardened BIP32 index, below 2^31
+pub(crate) const MAX_ACCOUNT: u32 = (1 << 31) - 1;
+
+/// Key derivation from BIP39 mnemonic, as in NIP-06
+pub(crate) struct Nip06 {}
+
+impl Nip06 {
+    /// BIP32 derivation path of the key of an account, as in NIP-06
+    pub(crate) fn derivation_path(account: u32) -> String {
+        format!("m/44'/1237'/{account}'/0/0")
+    }
+
+    /// Parse an account index, a number between 0 and MAX_ACCOUNT
+    pub(crate) fn parse_account(account: &str) -> Result<u32, Error> {
+        match account.trim().parse::<u32>() {
+            Ok(account) if account <= MAX_ACCOUNT => Ok(account),
+            _ => Err(Error::MnemonicInvalidAccount),
+        }
+    }
+
+    /// Generate a new random mnemonic, with 12 or 24 words
+    pub(crate) fn generate_mnemonic(word_count: usize) -> Result<bip39::Mnemonic, Error> {
+        let mut entropy: Vec<u8> = match word_count {
+            12 => vec![0; 16],
+            24 => vec![0; 32],
+            _ => return Err(Error::KeyMnemonic(bip39::Error::BadWordCount(word_count))),
+        };
+        OsRng.fill_bytes(&mut entropy);
+        let mnemonic = bip39::Mnemonic::from_entropy(&entropy);
+        entropy.zeroize();
+        Ok(mnemonic?)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Derive the secret key of an account from a mnemonic and an optional passphrase
+    pub(crate) fn derive_secret_key(
+        mnemonic: &bip39::Mnemonic,
+        passphrase: &str,
+        account: u32,
+    ) -> Result<SecretKey, Error> {
+        let end = account
+            .checked_add(1)
+            .ok_or(Error::MnemonicInvalidAccount)?;
+        let mut keys = Self::derive_secret_keys(mnemonic, passphrase, account..end)?;
+        keys.pop().ok_or(Error::KeyNotSet)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Derive the secret keys of a range of accounts (the seed is computed only once)
+    pub(crate) fn derive_secret_keys(
+        mnemonic: &bip39::Mnemonic,
+        passphrase: &str,
+        accounts: Range<u32>,
+    ) -> Result<Vec<SecretKey>, Error> {
+        if accounts.end > MAX_ACCOUNT + 1 {
+            return Err(Error::MnemonicInvalidAccount);
+        }
+        let mut seed = mnemonic.to_seed(passphrase);
+        let keys = accounts
+            .map(|account| Self::derive_from_seed(&seed, account))
+            .collect();
+        seed.zeroize();
+        keys
+    }
+
+    fn derive_from_seed(seed: &[u8; 64], account: u32) -> Result<SecretKey, Error> {
+        let child_xprv =
+            bip32::XPrv::derive_from_path(seed, &Self::derivation_path(account).parse()?)?;
+        let private_key = child_xprv.private_key().to_bytes();
+        Ok(SecretKey::from_slice(&private_key)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_derive_secret_key() {
+        // Test vectors from the NIP-06 spec
+        let mnemonic = bip39::Mnemonic::parse(
+            "leader monkey parrot ring guide accident before fence cannon height naive bean",
+        )
+        .unwrap();
+        let sk = Nip06::derive_secret_key(&mnemonic, "", 0).unwrap();
+        assert_eq!(
+            hex::encode(sk.secret_bytes()),
+            "7f7ff03d123792d6ac594bfa67bf6d0c0ab55b6b1fdb6249303fe861f1ccba9a"
+        );
+
+        let mnemonic = bip39::Mnemonic::parse("what bleak badge arrange retreat wolf trade produce cricket blur garlic valid proud rude strong choose busy staff weather area salt hollow arm fade").unwrap();
+        let sk = Nip06::derive_secret_key(&mnemonic, "", 0).unwrap();
+        assert_eq!(
+            hex::encode(sk.secret_bytes()),
+            "c15d739894c81a2fcfd3a2df85a0d2c0dbc47a280d092799f144d73d7ae78add"
+        );
+    }
+
+    #[test]
+    fn test_derive_secret_keys() {
+        let mnemonic = bip39::Mnemonic::parse(
+            "leader monkey parrot ring guide accident before fence cannon height naive bean",
+        )
+        .unwrap();
+        let keys = Nip06::derive_secret_keys(&mnemonic, "", 0..3).unwrap();
+        assert_eq!(keys.len(), 3);
+        assert_eq!(
+            hex::encode(keys[0].secret_bytes()),
+            "7f7ff03d123792d6ac594bfa67bf6d0c0ab55b6b1fdb6249303fe861f1ccba9a"
+        );
+        assert_eq!(keys[2], Nip06::derive_secret_key(&mnemonic, "", 2).unwrap());
+        assert_ne!(keys[1], keys[0]);
+
+        // passphrase gives different keys
+        assert_ne!(
+            Nip06::derive_secret_key(&mnemonic, "passphrase", 0).unwrap(),
+            keys[0]
+        );
+    }
+
+    #[test]
+    fn test_account_limit() {
+        let mnemonic = bip39::Mnemonic::parse(
+            "leader monkey parrot ring guide accident before fence cannon height naive bean",
+        )
+        .unwrap();
+        assert!(Nip06::derive_secret_key(&mnemonic, "", MAX_ACCOUNT).is_ok());
+        for account in [MAX_ACCOUNT + 1, u32::MAX] {
+            assert!(matches!(
+                Nip06::derive_secret_key(&mnemonic, "", account),
+                Err(Error::MnemonicInvalidAccount)
+            ));
+        }
+        assert!(Nip06::derive_secret_keys(&mnemonic, "", MAX_ACCOUNT..u32::MAX).is_err());
+
+        assert_eq!(Nip06::parse_account(" 2 ").unwrap(), 2);
+        assert_eq!(Nip06::parse_account("2147483647").unwrap(), MAX_ACCOUNT);
+        for account in ["2147483648", "4294967295", "4294967296", "-1", "x", ""] {
+            assert!(Nip06::parse_account(account).is_err(), "{}", account);
+        }
+    }
+
+    #[test]
+    fn test_generate_mnemonic() {
+        let mnemonic = Nip06::generate_mnemonic(12).unwrap();
+        assert_eq!(mnemonic.word_count(), 12);
+        let mnemonic2 = Nip06::generate_mnemonic(24).unwrap();
+        assert_eq!(mnemonic2.word_count(), 24);
+        assert_ne!(
+            mnemonic.to_string(),
+            Nip06::generate_mnemonic(12).unwrap().to_string()
+        );
+
+        assert!(Nip06::generate_mnemonic(13).is_err());
+    }
+}
@@ -0,0 +1,344 @@
+use crate::base::error::Error;
+
+use qrcode::{Color, QrCode};
+use zeroize::Zeroizing;
+
+use std::fmt::Write;
+
+/// Page size (A4, in mm)
+const PAGE_WIDTH: u32 = 210;
+const PAGE_HEIGHT: u32 = 297;
+/// Size of the QR codes (in mm), including quiet zone
+const QR_SIZE: u32 = 80;
+/// Quiet zone around QR codes, in modules
+const QR_QUIET_ZONE: usize = 4;
+/// Characters per line when printing long strings (ncryptsec)
+const LINE_CHARS: usize = 54;
+/// Mnemonic words per line
+const WORDS_PER_LINE: usize = 4;
+
+/// The secret part of a paper backup
+pub(crate) enum PaperBackupSecret {
+    /// NIP-49 encrypted secret key, the password is not on the sheet
+    Ncryptsec(String),
+    /// BIP39 mnemonic (NIP-06), with its account and derivation path
+    Mnemonic {
+        words: Zeroizing<String>,
+        account: u32,
+        derivation_path: String,
+        has_passphrase: bool,
+    },
+}
+
+/// Printable paper backup of a key: a self-contained A4 SVG sheet with the npub,
+/// the ncryptsec or the mnemonic, their QR codes, creation date and recovery instructions.
+pub(crate) struct PaperBackup {
+    pub identity: String,
+    pub npub: String,
+    pub secret: PaperBackupSecret,
+    /// Creation time, unix timestamp (seconds)
+    pub created: u64,
+}
+
+impl PaperBackup {
+    /// Warning: Security-sensitive method!
+    /// Render the sheet as SVG
+    pub fn to_svg(&self) -> Result<Zeroizing<String>, Error> {
+        // reserve enough, so that the contents are not copied around on reallocation
+        let mut svg = Zeroizing::new(String::with_capacity(256 * 1024));
+        let (secret_qr_content, secret_title) = match &self.secret {
+            PaperBackupSecret::Ncryptsec(ncryptsec) => (
+                Zeroizing::new(ncryptsec.clone()),
+                "Encrypted secret key (NIP-49 ncryptsec)",
+            ),
+            PaperBackupSecret::Mnemonic { words, .. } => {
+                (words.clone(), "Mnemonic words (NIP-06), KEEP SECRET!")
+            }
+        };
+
+        let _ = write!(
+            svg,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}mm\" height=\"{h}mm\" viewBox=\"0 0 {w} {h}\">\n\
+            <rect width=\"{w}\" height=\"{h}\" fill=\"white\"/>\n",
+            w = PAGE_WIDTH,
+            h = PAGE_HEIGHT
+        );
+        Self::text(&mut svg, 20, 22, 8.0, true, "Nostr Key Backup");
+        Self::text(
+            &mut svg,
+            20,
+            31,
+            4.0,
+            false,
+            &format!(
+                "Identity: {}    Created: {}",
+                self.identity,
+                Self::format_date(self.created)
+            ),
+        );
+
+        Self::text(
+            &mut svg,
+            20,
+            42,
+            4.0,
+            true,
+            "Public key (npub), can be shared:",
+        );
+        Self::mono_text(&mut svg, 20, 48, &self.npub);
+
+        Self::qr_code(&mut svg, 15, 54, self.npub.as_bytes())?;
+        Self::qr_code(&mut svg, 115, 54, secret_qr_content.as_bytes())?;
+        Self::text(&mut svg, 35, 138, 4.0, false, "Public key (npub)");
+        Self::text(
+            &mut svg,
+            130,
+            138,
+            4.0,
+            false,
+            match self.secret {
+                PaperBackupSecret::Ncryptsec(_) => "Encrypted secret key",
+                PaperBackupSecret::Mnemonic { .. } => "Mnemonic (secret!)",
+            },
+        );
+
+        Self::text(&mut svg, 20, 152, 4.0, true, secret_title);
+        let mut y = 159;
+        let lines = self.secret_lines();
+        for line in lines.iter() {
+            Self::mono_text(&mut svg, 20, y, line);
+            y += 6;
+        }
+
+        y += 6;
+        Self::text(&mut svg, 20, y, 4.0, true, "Recovery instructions:");
+        for line in self.instructions() {
+            y += 6;
+            Self::text(&mut svg, 20, y, 3.6, false, &line);
+        }
+
+        Self::text(
+            &mut svg,
+            20,
+            PAGE_HEIGHT - 12,
+            3.0,
+            false,
+            "Generated by Keystr. Store this sheet in a safe place, and check that it can be read.",
+        );
+        svg.push_str("</svg>\n");
+        Ok(svg)
+    }
+
+    /// The secret in text form, in lines
+    fn secret_lines(&self) -> Zeroizing<Vec<String>> {
+        let mut lines = Zeroizing::new(Vec::new());
+        match &self.secret {
+            PaperBackupSecret::Ncryptsec(ncryptsec) => {
+                let chars: Vec<char> = ncryptsec.chars().collect();
+                for chunk in chars.chunks(LINE_CHARS) {
+                    lines.push(chunk.iter().collect());
+                }
+            }
+            PaperBackupSecret::Mnemonic { words, .. } => {
+                let words: Vec<&str> = words.split_whitespace().collect();
+                for (row, chunk) in words.chunks(WORDS_PER_LINE).enumerate() {
+                    let mut line = String::new();
+                    for (i, word) in chunk.iter().enumerate() {
+                        let _ = write!(line, "{:>2}. {:<11}", row * WORDS_PER_LINE + i + 1, word);
+                    }
+                    lines.push(line);
+                }
+            }
+        }
+        lines
+    }
+
+    fn instructions(&self) -> Vec<String> {
+        match &self.secret {
+            PaperBackupSecret::Ncryptsec(_) => vec![
+                "1. Scan or type the ncryptsec string above.".to_string(),
+                "2. In Keystr: Keys tab, 'Import ncryptsec', or use any NIP-49 compatible client."
+                    .to_string(),
+                "3. Enter the password used at export (it is NOT on this sheet, keep it separately)."
+                    .to_string(),
+                "4. Check that the npub of the restored key matches the npub above.".to_string(),
+            ],
+            PaperBackupSecret::Mnemonic {
+                account,
+                derivation_path,
+                has_passphrase,
+                ..
+            } => {
+                let mut lines = vec![
+                    "1. Anyone with these words has full control of the key, keep this sheet secret!"
+                        .to_string(),
+                    "2. In Keystr: Keys tab, 'Import Mnemonic', or use any NIP-06 compatible client."
+                        .to_string(),
+                    format!(
+                        "3. Use account {} (derivation path {}).",
+                        account, derivation_path
+                    ),
+                ];
+                if *has_passphrase {
+                    lines.push(
+                        "4. Enter the BIP39 passphrase too (it is NOT on this sheet).".to_string(),
+                    );
+                }
+                lines.push(format!(
+                    "{}. Check that the npub of the restored key matches the npub above.",
+                    lines.len() + 1
+                ));
+                lines
+            }
+        }
+    }
+
+    fn text(svg: &mut String, x: u32, y: u32, size: f32, bold: bool, content: &str) {
+        let _ = write!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\"{}>",
+            x,
+            y,
+            size,
+            if bold { " font-weight=\"bold\"" } else { "" }
+        );
+        Self::push_escaped(svg, content);
+        svg.push_str("</text>\n");
+    }
+
+    fn mono_text(svg: &mut String, x: u32, y: u32, content: &str) {
+        let _ = write!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" font-family=\"monospace\" font-size=\"3.6\" xml:space=\"preserve\">",
+            x, y
+        );
+        Self::push_escaped(svg, content);
+        svg.push_str("</text>\n");
+    }
+
+    /// Render a QR code at the given position (top-left, mm), as a single path
+    fn qr_code(svg: &mut String, x: u32, y: u32, data: &[u8]) -> Result<(), Error> {
+        let qr = QrCode::new(data)?;
+        let width = qr.width();
+        let module_size = QR_SIZE as f32 / (width + 2 * QR_QUIET_ZONE) as f32;
+        let _ = write!(
+            svg,
+            "<g transform=\"translate({} {}) scale({})\">\n\
+            <rect width=\"{}\" height=\"{}\" fill=\"white\"/>\n\
+            <path fill=\"black\" shape-rendering=\"crispEdges\" d=\"",
+            x,
+            y,
+            module_size,
+            width + 2 * QR_QUIET_ZONE,
+            width + 2 * QR_QUIET_ZONE
+        );
+        for (i, color) in qr.to_colors().iter().enumerate() {
+            if *color == Color::Dark {
+                let _ = write!(
+                    svg,
+                    "M{} {}h1v1h-1z",
+                    i % width + QR_QUIET_ZONE,
+                    i / width + QR_QUIET_ZONE
+                );
+            }
+        }
+        svg.push_str("\"/>\n</g>\n");
+        Ok(())
+    }
+
+    fn push_escaped(svg: &mut String, content: &str) {
+        for c in content.chars() {
+            match c {
+                '&' => svg.push_str("&amp;"),
+                '<' => svg.push_str("&lt;"),
+                '>' => svg.push_str("&gt;"),
+                '"' => svg.push_str("&quot;"),
+                _ => svg.push(c),
+            }
+        }
+    }
+
+    /// Format a unix timestamp as UTC date and time, e.g. '2023-03-15 10:20 UTC'
+    pub fn format_date(timestamp: u64) -> String {
+        let days = (timestamp / 86400) as i64;
+        let seconds = timestamp % 86400;
+        // civil date from days since 1970-01-01 (H. Hinnant's algorithm)
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z.rem_euclid(146097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+            year,
+            month,
+            day,
+            seconds / 3600,
+            seconds % 3600 / 60
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const NPUB: &str = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(PaperBackup::format_date(0), "1970-01-01 00:00 UTC");
+        assert_eq!(PaperBackup::format_date(951782400), "2000-02-29 00:00 UTC");
+        assert_eq!(PaperBackup::format_date(1678875600), "2023-03-15 10:20 UTC");
+    }
+
+    #[test]
+    fn test_svg_ncryptsec() {
+        let ncryptsec = "ncryptsec1qgg9947rlpvqu76pj5ecreduf9jxhselq2nae2kghhvd5g7dgjtcxfqtd67p9m0w57lspw8gsq6yphnm8623nsl8xn9j4jdzz84zm3frztj3z7s35vpzmqf6ksu8r89qk5z2zxfmu5gv8th8wclt0h4p";
+        let backup = PaperBackup {
+            identity: "main<&>".to_string(),
+            npub: NPUB.to_string(),
+            secret: PaperBackupSecret::Ncryptsec(ncryptsec.to_string()),
+            created: 1678875600,
+        };
+        let svg = backup.to_svg().unwrap();
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains(NPUB));
+        assert!(svg.contains(&ncryptsec[0..LINE_CHARS]));
+        assert!(svg.contains(&ncryptsec[LINE_CHARS..2 * LINE_CHARS]));
+        assert!(svg.contains("Identity: main&lt;&amp;&gt;"));
+        assert!(svg.contains("2023-03-15 10:20 UTC"));
+        assert!(svg.contains("Import ncryptsec"));
+        assert_eq!(svg.matches("<path").count(), 2);
+    }
+
+    #[test]
+    fn test_svg_mnemonic() {
+        let words =
+            "leader monkey parrot ring guide accident before fence cannon height naive bean";
+        let backup = PaperBackup {
+            identity: "main".to_string(),
+            npub: NPUB.to_string(),
+            secret: PaperBackupSecret::Mnemonic {
+                words: Zeroizing::new(words.to_string()),
+                account: 0,
+                derivation_path: "m/44'/1237'/0'/0/0".to_string(),
+                has_passphrase: true,
+            },
+            created: 0,
+        };
+        let svg = backup.to_svg().unwrap();
+        assert!(svg.contains(" 1. leader"));
+        assert!(svg.contains("12. bean"));
+        assert!(svg.contains("m/44'/1237'/0'/0/0"));
+        assert!(svg.contains("passphrase"));
+        assert!(svg.contains("5. Check that the npub"));
+    }
+}
@@ -0,0 +1,196 @@
+use crate::base::error::Error;
+
+use nostr::prelude::{FromSkStr, Keys, SecretKey, ToBech32, XOnlyPublicKey, SECP256K1};
+use nostr::secp256k1::schnorr::Signature;
+use nostr::secp256k1::{rand, KeyPair, Message};
+use zeroize::{Zeroize, Zeroizing};
+
+use std::mem::MaybeUninit;
+
+/// Warning: Security-sensitive type!
+/// Holds a secret key in memory, the secret is zeroized on drop.
+/// Intentionally not Clone and not Debug, share it with Arc if needed.
+/// The secret key is used through closures: the (secp256k1) key copies created for an operation
+/// live only for the duration of the closure, and are erased afterwards.
+pub(crate) struct SecretKeyHolder {
+    secret: [u8; 32],
+    public_key: XOnlyPublicKey,
+}
+
+impl SecretKeyHolder {
+    pub fn new(secret_key: &SecretKey) -> Self {
+        let (public_key, _parity) = secret_key.x_only_public_key(SECP256K1);
+        Self {
+            secret: secret_key.secret_bytes(),
+            public_key,
+        }
+    }
+
+    /// Generate a new random secret key
+    pub fn generate() -> Self {
+        Self::new(&SecretKey::new(&mut rand::thread_rng()))
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Parse secret key, in 'nsec' bech32 or hex format
+    pub fn from_sk_str(secret_key_str: &str) -> Result<Self, Error> {
+        let keys = Keys::from_sk_str(secret_key_str)?;
+        Ok(Self::new(&keys.secret_key()?))
+    }
+
+    pub fn public_key(&self) -> XOnlyPublicKey {
+        self.public_key
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Call f with the secret key. The temporary key is erased after f returns.
+    pub fn with_secret_key<R>(&self, f: impl FnOnce(&SecretKey) -> R) -> Result<R, Error> {
+        let mut secret_key = SecretKey::from_slice(&self.secret)?;
+        let result = f(&secret_key);
+        secret_key.non_secure_erase();
+        Ok(result)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Call f with a Keys instance, for nostr operations that need one (e.g. delegation).
+    /// Keys has no way to erase its secret, so its memory is zeroized after f returns
+    /// (it holds no heap data, nothing is leaked by not dropping it).
+    pub fn with_keys<R>(&self, f: impl FnOnce(&Keys) -> R) -> Result<R, Error> {
+        self.with_secret_key(|secret_key| {
+            let mut keys = MaybeUninit::new(Keys::new(*secret_key));
+            // SAFETY: initialized just above, and not zeroized yet
+            let result = f(unsafe { keys.assume_init_ref() });
+            keys.zeroize();
+            result
+        })
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Return the secret key in 'nsec' bech32 format
+    pub fn to_nsec(&self) -> Result<Zeroizing<String>, Error> {
+        Ok(Zeroizing::new(
+            self.with_secret_key(|secret_key| secret_key.to_bech32())??,
+        ))
+    }
+
+    /// Perform Schnorr signing of a hash
+    pub fn sign_schnorr(&self, hash: &[u8]) -> Result<Signature, Error> {
+        let message = Message::from_slice(hash)?;
+        self.with_secret_key(|secret_key| {
+            let mut key_pair = KeyPair::from_secret_key(SECP256K1, secret_key);
+            let signature = SECP256K1.sign_schnorr(&message, &key_pair);
+            key_pair.non_secure_erase();
+            signature
+        })
+    }
+}
+
+impl Drop for SecretKeyHolder {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+/// Warning: Security-sensitive type!
+/// A secret string, e.g. a password input, zeroized when replaced, cleared or dropped.
+/// Intentionally not Clone and not Debug.
+#[derive(Default)]
+pub(crate) struct SecretString {
+    value: String,
+}
+
+impl SecretString {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a new value, the previous one is zeroized
+    pub fn set(&mut self, value: String) {
+        self.value.zeroize();
+        self.value = value;
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.value
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.value.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.value.zeroize();
+    }
+
+    /// A copy that is zeroized on drop, for using the value while the owner is modified
+    pub fn to_zeroizing(&self) -> Zeroizing<String> {
+        Zeroizing::new(self.value.clone())
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nostr::prelude::FromBech32;
+
+    const NSEC: &str = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+    const NPUB: &str = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+
+    #[test]
+    fn test_from_sk_str() {
+        let secret = SecretKeyHolder::from_sk_str(NSEC).unwrap();
+        assert_eq!(secret.public_key().to_bech32().unwrap(), NPUB);
+        assert_eq!(secret.to_nsec().unwrap().as_str(), NSEC);
+        let sk = SecretKey::from_bech32(NSEC).unwrap();
+        assert_eq!(SecretKeyHolder::new(&sk).public_key(), secret.public_key());
+        assert!(SecretKeyHolder::from_sk_str("nsec1invalid").is_err());
+    }
+
+    #[test]
+    fn test_sign_schnorr() {
+        let secret = SecretKeyHolder::generate();
+        let hash = [7u8; 32];
+        let signature: Signature = secret.sign_schnorr(&hash).unwrap();
+        assert!(SECP256K1
+            .verify_schnorr(
+                &signature,
+                &Message::from_slice(&hash).unwrap(),
+                &secret.public_key()
+            )
+            .is_ok());
+        assert!(secret.sign_schnorr(&[1u8; 5]).is_err());
+    }
+
+    #[test]
+    fn test_with_keys() {
+        let secret = SecretKeyHolder::from_sk_str(NSEC).unwrap();
+        assert_eq!(
+            secret.with_keys(|keys| keys.public_key()).unwrap(),
+            secret.public_key()
+        );
+        assert_eq!(
+            secret
+                .with_secret_key(|secret_key| secret_key.to_bech32())
+                .unwrap()
+                .unwrap(),
+            NSEC
+        );
+    }
+
+    #[test]
+    fn test_secret_string() {
+        let mut s = SecretString::new();
+        assert!(s.is_empty());
+        s.set("password".to_string());
+        assert_eq!(s.as_str(), "password");
+        assert_eq!(s.to_zeroizing().as_str(), "password");
+        s.clear();
+        assert!(s.is_empty());
+    }
+}
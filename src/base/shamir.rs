@@ -0,0 +1,293 @@
+use crate::base::error::Error;
+
+use bech32::{FromBase32, ToBase32, Variant};
+use rand_core::{OsRng, RngCore};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Human-readable part of the bech32 share strings
+const SHARE_HRP: &str = "nshare";
+/// Version of the share format
+const SHARE_VERSION: u8 = 1;
+/// Length of the shared secret (a secret key)
+const SECRET_LEN: usize = 32;
+/// Length of the public key prefix included in the shares
+pub(crate) const PUBKEY_PREFIX_LEN: usize = 4;
+/// Share payload: version, threshold, index, public key prefix, share value
+const SHARE_LEN: usize = 3 + PUBKEY_PREFIX_LEN + SECRET_LEN;
+/// Max number of shares
+pub(crate) const MAX_SHARES: u8 = 16;
+
+/// Shamir secret sharing of a 32-byte secret key, M-of-N.
+///
+/// Scheme: each byte of the secret is shared separately, over GF(2^8) with the reduction
+/// polynomial x^8 + x^4 + x^3 + x + 1 (as in AES). For each byte a random polynomial of degree
+/// M-1 is taken, with the secret byte as constant term; share i (i = 1..N) holds its value at x=i.
+/// Any M shares restore the secret by Lagrange interpolation at x=0, fewer reveal nothing.
+///
+/// A share is a bech32 string with prefix 'nshare', the data is:
+/// version (1) | threshold M | index i | first 4 bytes of the x-only public key | 32 value bytes.
+/// The public key prefix allows checking that shares belong together, and verifying the
+/// restored key.
+pub(crate) struct Shamir {}
+
+/// A decoded share
+struct Share {
+    threshold: u8,
+    index: u8,
+    pubkey_prefix: [u8; PUBKEY_PREFIX_LEN],
+    value: [u8; SECRET_LEN],
+}
+
+impl Drop for Share {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+impl Shamir {
+    /// Warning: Security-sensitive method!
+    /// Split a secret into `count` shares, any `threshold` of them can restore it
+    pub fn split(
+        secret: &[u8; SECRET_LEN],
+        pubkey_prefix: &[u8; PUBKEY_PREFIX_LEN],
+        threshold: u8,
+        count: u8,
+    ) -> Result<Vec<Zeroizing<String>>, Error> {
+        if threshold < 2 || threshold > count || count > MAX_SHARES {
+            return Err(Error::ShamirInvalidParameters);
+        }
+        // coefficients of the polynomials, per secret byte: secret byte, then random ones
+        let mut coefficients = Zeroizing::new(vec![0u8; SECRET_LEN * threshold as usize]);
+        OsRng.fill_bytes(&mut coefficients);
+        for (i, secret_byte) in secret.iter().enumerate() {
+            coefficients[i * threshold as usize] = *secret_byte;
+        }
+        let mut shares = Vec::new();
+        for index in 1..=count {
+            let mut share = Share {
+                threshold,
+                index,
+                pubkey_prefix: *pubkey_prefix,
+                value: [0; SECRET_LEN],
+            };
+            for i in 0..SECRET_LEN {
+                let poly = &coefficients[i * threshold as usize..(i + 1) * threshold as usize];
+                share.value[i] = Self::evaluate(poly, index);
+            }
+            shares.push(share.encode()?);
+        }
+        Ok(shares)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Restore the secret from shares (at least threshold many, distinct).
+    /// Returns the secret and the public key prefix from the shares (to be verified by the caller).
+    pub fn combine(
+        shares: &[&str],
+    ) -> Result<(Zeroizing<[u8; SECRET_LEN]>, [u8; PUBKEY_PREFIX_LEN]), Error> {
+        let mut decoded: Vec<Share> = Vec::new();
+        for share_str in shares {
+            let share = Share::decode(share_str)?;
+            if let Some(first) = decoded.first() {
+                if share.threshold != first.threshold || share.pubkey_prefix != first.pubkey_prefix
+                {
+                    return Err(Error::ShamirSharesMismatch);
+                }
+            }
+            // a repeated share does not count
+            if !decoded.iter().any(|s| s.index == share.index) {
+                decoded.push(share);
+            }
+        }
+        let first = decoded.first().ok_or(Error::ShamirNotEnoughShares)?;
+        let threshold = first.threshold as usize;
+        let pubkey_prefix = first.pubkey_prefix;
+        if decoded.len() < threshold {
+            return Err(Error::ShamirNotEnoughShares);
+        }
+        let used = &decoded[0..threshold];
+
+        // Lagrange basis polynomials at x=0; in GF(2^8) subtraction is XOR (addition)
+        let mut basis = Vec::new();
+        for (j, share_j) in used.iter().enumerate() {
+            let mut num = 1u8;
+            let mut den = 1u8;
+            for (m, share_m) in used.iter().enumerate() {
+                if m != j {
+                    num = Self::mul(num, share_m.index);
+                    den = Self::mul(den, share_m.index ^ share_j.index);
+                }
+            }
+            basis.push(Self::mul(num, Self::inverse(den)));
+        }
+        let mut secret = Zeroizing::new([0u8; SECRET_LEN]);
+        for i in 0..SECRET_LEN {
+            secret[i] = used
+                .iter()
+                .zip(basis.iter())
+                .fold(0u8, |acc, (share, b)| acc ^ Self::mul(share.value[i], *b));
+        }
+        Ok((secret, pubkey_prefix))
+    }
+
+    /// Evaluate polynomial (coefficients from constant term up) at x, using Horner's method
+    fn evaluate(coefficients: &[u8], x: u8) -> u8 {
+        coefficients
+            .iter()
+            .rev()
+            .fold(0u8, |acc, c| Self::mul(acc, x) ^ c)
+    }
+
+    /// Multiplication in GF(2^8), without table lookups
+    fn mul(a: u8, b: u8) -> u8 {
+        let (mut a, mut b, mut product) = (a, b, 0u8);
+        for _ in 0..8 {
+            // add a if lowest bit of b is set
+            product ^= a & 0u8.wrapping_sub(b & 1);
+            // multiply a by x, reduce by x^8 + x^4 + x^3 + x + 1
+            let carry = a >> 7;
+            a = (a << 1) ^ (0x1b & 0u8.wrapping_sub(carry));
+            b >>= 1;
+        }
+        product
+    }
+
+    /// Multiplicative inverse in GF(2^8), as a^254 (a must not be 0)
+    fn inverse(a: u8) -> u8 {
+        let mut result = 1u8;
+        let mut power = a;
+        let mut exp = 254u8;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = Self::mul(result, power);
+            }
+            power = Self::mul(power, power);
+            exp >>= 1;
+        }
+        result
+    }
+}
+
+impl Share {
+    fn encode(&self) -> Result<Zeroizing<String>, Error> {
+        let mut data = Zeroizing::new(Vec::with_capacity(SHARE_LEN));
+        data.push(SHARE_VERSION);
+        data.push(self.threshold);
+        data.push(self.index);
+        data.extend_from_slice(&self.pubkey_prefix);
+        data.extend_from_slice(&self.value);
+        let encoded = bech32::encode(SHARE_HRP, data.to_base32(), Variant::Bech32)
+            .map_err(|_e| Error::ShamirInvalidShare)?;
+        Ok(Zeroizing::new(encoded))
+    }
+
+    fn decode(share_str: &str) -> Result<Self, Error> {
+        let (hrp, data, variant) =
+            bech32::decode(share_str.trim()).map_err(|_e| Error::ShamirInvalidShare)?;
+        if hrp != SHARE_HRP || variant != Variant::Bech32 {
+            return Err(Error::ShamirInvalidShare);
+        }
+        let data =
+            Zeroizing::new(Vec::<u8>::from_base32(&data).map_err(|_e| Error::ShamirInvalidShare)?);
+        if data.len() != SHARE_LEN || data[0] != SHARE_VERSION {
+            return Err(Error::ShamirInvalidShare);
+        }
+        let (threshold, index) = (data[1], data[2]);
+        if threshold < 2 || index == 0 {
+            return Err(Error::ShamirInvalidShare);
+        }
+        let mut share = Share {
+            threshold,
+            index,
+            pubkey_prefix: [0; PUBKEY_PREFIX_LEN],
+            value: [0; SECRET_LEN],
+        };
+        share
+            .pubkey_prefix
+            .copy_from_slice(&data[3..3 + PUBKEY_PREFIX_LEN]);
+        share.value.copy_from_slice(&data[3 + PUBKEY_PREFIX_LEN..]);
+        Ok(share)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SECRET: [u8; 32] = [
+        0xb2, 0xf3, 0x67, 0x3e, 0xe3, 0xa6, 0x59, 0x28, 0x3e, 0x65, 0x99, 0x08, 0x0e, 0x0a, 0xb0,
+        0xe6, 0x69, 0xa3, 0xc2, 0x64, 0x09, 0x14, 0x37, 0x5a, 0x9b, 0x0b, 0x35, 0x7f, 0xaa, 0xe0,
+        0x8b, 0x17,
+    ];
+    const PREFIX: [u8; 4] = [0x1a, 0x45, 0x9a, 0x8a];
+
+    fn as_strs(shares: &[Zeroizing<String>]) -> Vec<&str> {
+        shares.iter().map(|s| s.as_str()).collect()
+    }
+
+    #[test]
+    fn test_gf_arithmetic() {
+        // known AES field values
+        assert_eq!(Shamir::mul(0x57, 0x83), 0xc1);
+        assert_eq!(Shamir::mul(0x57, 0x13), 0xfe);
+        for a in 1..=255u8 {
+            assert_eq!(Shamir::mul(a, Shamir::inverse(a)), 1);
+        }
+    }
+
+    #[test]
+    fn test_split_combine() {
+        let shares = Shamir::split(&SECRET, &PREFIX, 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+        assert!(shares[0].starts_with("nshare1"));
+        let shares = as_strs(&shares);
+
+        // any 3 shares restore the secret
+        for combination in [[0, 1, 2], [0, 2, 4], [4, 3, 1], [1, 2, 3]] {
+            let subset: Vec<&str> = combination.iter().map(|i| shares[*i]).collect();
+            let (secret, prefix) = Shamir::combine(&subset).unwrap();
+            assert_eq!(*secret, SECRET);
+            assert_eq!(prefix, PREFIX);
+        }
+        // more than needed is fine
+        assert_eq!(*Shamir::combine(&shares).unwrap().0, SECRET);
+        // too few, also with a repeated one
+        assert!(matches!(
+            Shamir::combine(&shares[0..2]),
+            Err(Error::ShamirNotEnoughShares)
+        ));
+        assert!(matches!(
+            Shamir::combine(&[shares[0], shares[1], shares[1]]),
+            Err(Error::ShamirNotEnoughShares)
+        ));
+    }
+
+    #[test]
+    fn test_combine_invalid() {
+        let shares = Shamir::split(&SECRET, &PREFIX, 2, 3).unwrap();
+        let other = Shamir::split(&SECRET, &[0, 0, 0, 0], 2, 3).unwrap();
+        assert!(matches!(
+            Shamir::combine(&[shares[0].as_str(), other[1].as_str()]),
+            Err(Error::ShamirSharesMismatch)
+        ));
+        // typo is detected by the checksum
+        let mut typo = shares[0].to_string();
+        typo.replace_range(20..21, if &typo[20..21] == "q" { "p" } else { "q" });
+        assert!(matches!(
+            Shamir::combine(&[typo.as_str(), shares[1].as_str()]),
+            Err(Error::ShamirInvalidShare)
+        ));
+        assert!(Shamir::combine(&[
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_split_invalid_parameters() {
+        assert!(Shamir::split(&SECRET, &PREFIX, 1, 3).is_err());
+        assert!(Shamir::split(&SECRET, &PREFIX, 4, 3).is_err());
+        assert!(Shamir::split(&SECRET, &PREFIX, 2, MAX_SHARES + 1).is_err());
+        assert!(Shamir::split(&SECRET, &PREFIX, MAX_SHARES, MAX_SHARES).is_ok());
+    }
+}
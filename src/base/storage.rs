@@ -1,41 +1,247 @@
 use crate::base::error::Error;
+use crate::base::storage_backend::StorageBackend;
+use once_cell::sync::OnceCell;
+use std::ffi::OsString;
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 pub(crate) struct Storage {}
 
 /// Folder used to store data, relative to user data dir (~/.local/share)
 const LOCAL_STORAGE_FOLDER: &str = "keystr";
-/// Public key storage file name, relative to data folder.
+/// Environment variable for setting the data folder
+pub(crate) const DATA_DIR_ENV_VAR: &str = "KEYSTR_DATA_DIR";
+/// Folder used to store data in portable mode, relative to the folder of the executable
+const PORTABLE_STORAGE_FOLDER: &str = "keystr-data";
+/// Marker file turning on portable mode, if present next to the executable
+const PORTABLE_MARKER_FILENAME: &str = "keystr-portable";
+/// Public key storage file name, relative to identity folder.
+/// (Also the legacy single-key file name, relative to data folder.)
 const PUBLIC_KEY_FILENAME: &str = "npub";
-/// Encrypted secret key storage file name, relative to data folder.
+/// Encrypted secret key storage file name, relative to identity folder.
+/// (Also the legacy single-key file name, relative to data folder.)
 const ENCRYPTED_SECRET_KEY_FILENAME: &str = ".ncrypt";
+/// Encrypted mnemonic storage file name, relative to identity folder.
+const ENCRYPTED_MNEMONIC_FILENAME: &str = ".nmnemonic";
+/// Encrypted FROST key share file name prefix (followed by the participant index),
+/// relative to identity folder.
+const FROST_SHARE_FILENAME_PREFIX: &str = ".nfrost-";
+/// Sub-keys list file name (labels and public keys), relative to identity folder.
+const SUBKEYS_FILENAME: &str = "subkeys.json";
 /// Public key storage file name, relative to data folder.
 const SETTINGS_FILENAME: &str = "settings.json";
+/// Keyring index file name (list of identities), relative to data folder.
+const KEYRING_FILENAME: &str = "keyring.json";
+/// Ledger of created delegations file name, relative to data folder.
+const DELEGATIONS_FILENAME: &str = "delegations.json";
+/// Suffix of temporary files written before replacing a file
+pub(crate) const TEMP_FILE_SUFFIX: &str = ".tmp";
+/// Folder containing one subfolder per identity, relative to data folder.
+pub(crate) const IDENTITIES_FOLDER: &str = "identities";
+/// Files written by Keystr directly in the data folder (besides the identities folder),
+/// including the legacy single-key files. The data folder may be shared with other files.
+pub(crate) const DATA_FOLDER_FILES: [&str; 5] = [
+    KEYRING_FILENAME,
+    SETTINGS_FILENAME,
+    DELEGATIONS_FILENAME,
+    PUBLIC_KEY_FILENAME,
+    ENCRYPTED_SECRET_KEY_FILENAME,
+];
+
+/// Data folder set at startup (command line, environment, portable mode), if not the default
+static STORAGE_FOLDER: OnceCell<PathBuf> = OnceCell::new();
 
 impl Storage {
-    pub fn public_key_file() -> PathBuf {
+    /// Set the data folder at startup, before anything is stored. In order of precedence:
+    /// - the given folder (from the command line)
+    /// - portable mode (flag from the command line, or a marker file next to the executable):
+    ///   a folder next to the executable
+    /// - the folder in the KEYSTR_DATA_DIR environment variable
+    /// - otherwise the default, in the user data dir.
+    ///
+    /// Returns the data folder used.
+    pub fn configure(data_dir: Option<PathBuf>, portable: bool) -> Result<PathBuf, Error> {
+        let exe_folder = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf));
+        let folder = match Self::resolve_storage_folder(
+            data_dir,
+            portable,
+            std::env::var_os(DATA_DIR_ENV_VAR),
+            exe_folder.as_deref(),
+        )? {
+            None => return Ok(Self::get_storage_folder()),
+            Some(folder) if folder.is_relative() => std::env::current_dir()?.join(folder),
+            Some(folder) => folder,
+        };
+        if folder.exists() && !folder.is_dir() {
+            return Err(Error::StorageFolderInvalid);
+        }
+        STORAGE_FOLDER
+            .set(folder)
+            .map_err(|_e| Error::StorageFolderAlreadySet)?;
+        Ok(Self::get_storage_folder())
+    }
+
+    /// The configured data folder, None for the default
+    fn resolve_storage_folder(
+        data_dir: Option<PathBuf>,
+        portable: bool,
+        env_data_dir: Option<OsString>,
+        exe_folder: Option<&Path>,
+    ) -> Result<Option<PathBuf>, Error> {
+        if let Some(folder) = data_dir {
+            return Ok(Some(folder));
+        }
+        let has_marker = match exe_folder {
+            Some(f) => f.join(PORTABLE_MARKER_FILENAME).is_file(),
+            None => false,
+        };
+        if portable || has_marker {
+            return match exe_folder {
+                None => Err(Error::StoragePortableNoExecutable),
+                Some(f) => Ok(Some(f.join(PORTABLE_STORAGE_FOLDER))),
+            };
+        }
+        Ok(env_data_dir
+            .filter(|folder| !folder.is_empty())
+            .map(PathBuf::from))
+    }
+
+    /// Public key file of the single-key storage used before the keyring
+    pub fn legacy_public_key_file() -> PathBuf {
         Self::full_file_path(PUBLIC_KEY_FILENAME)
     }
 
-    pub fn encrypted_secret_key_file() -> PathBuf {
+    /// Encrypted secret key file of the single-key storage used before the keyring
+    pub fn legacy_encrypted_secret_key_file() -> PathBuf {
         Self::full_file_path(ENCRYPTED_SECRET_KEY_FILENAME)
     }
 
+    pub fn public_key_file(identity: &str) -> PathBuf {
+        Self::identity_file_path(identity, PUBLIC_KEY_FILENAME)
+    }
+
+    pub fn encrypted_secret_key_file(identity: &str) -> PathBuf {
+        Self::identity_file_path(identity, ENCRYPTED_SECRET_KEY_FILENAME)
+    }
+
+    pub fn encrypted_mnemonic_file(identity: &str) -> PathBuf {
+        Self::identity_file_path(identity, ENCRYPTED_MNEMONIC_FILENAME)
+    }
+
+    pub fn frost_share_file(identity: &str, index: u8) -> PathBuf {
+        Self::identity_file_path(
+            identity,
+            &format!("{}{}", FROST_SHARE_FILENAME_PREFIX, index),
+        )
+    }
+
+    pub fn subkeys_file(identity: &str) -> PathBuf {
+        Self::identity_file_path(identity, SUBKEYS_FILENAME)
+    }
+
     pub fn settings_file() -> PathBuf {
         Self::full_file_path(SETTINGS_FILENAME)
     }
 
-    pub fn check_create_folder() -> Result<(), Error> {
-        let p = Self::get_storage_folder();
-        if p.is_dir() {
+    pub fn keyring_file() -> PathBuf {
+        Self::full_file_path(KEYRING_FILENAME)
+    }
+
+    pub fn delegations_file() -> PathBuf {
+        Self::full_file_path(DELEGATIONS_FILENAME)
+    }
+
+    /// Write a file readable only by the user (contains encrypted secrets), atomically
+    /// (see `write_file`). On Unix the file is created with owner-only permissions.
+    pub fn write_private_file(path: &Path, contents: &[u8]) -> Result<(), Error> {
+        Self::write_atomic(path, contents, true)
+    }
+
+    /// Write a file atomically: contents go to a temporary file first, which is synced
+    /// to disk, then it replaces the file. After a crash either the previous or the new
+    /// contents are there, never a partially written file.
+    pub fn write_file(path: &Path, contents: &[u8]) -> Result<(), Error> {
+        Self::write_atomic(path, contents, false)
+    }
+
+    fn write_atomic(path: &Path, contents: &[u8], private: bool) -> Result<(), Error> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(TEMP_FILE_SUFFIX);
+        let tmp_path = PathBuf::from(tmp_path);
+        // left over from an interrupted write; permissions are set only when creating
+        if tmp_path.is_file() {
+            fs::remove_file(&tmp_path)?;
+        }
+        {
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            // TODO restrict permissions on non-unix as well
+            #[cfg(unix)]
+            if private {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+            }
+            #[cfg(not(unix))]
+            let _ = private;
+            let mut file = options.open(&tmp_path)?;
+            file.write_all(contents)?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp_path, path)?;
+        // make the rename itself durable
+        #[cfg(unix)]
+        if let Some(parent) = path.parent() {
+            fs::File::open(parent)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// File of a previous version of a file, e.g. '.ncrypt.1' (1 is the latest)
+    pub fn version_file(path: &Path, version: u8) -> PathBuf {
+        let mut version_path = path.as_os_str().to_owned();
+        version_path.push(format!(".{}", version));
+        PathBuf::from(version_path)
+    }
+
+    /// Keep the current contents of a file as its latest previous version, before it is
+    /// overwritten; older versions are shifted, at most `versions` are kept.
+    /// If the file does not exist, its latest version will be missing as well
+    /// (so that versions of related files stay in step).
+    pub fn keep_version(
+        storage: &dyn StorageBackend,
+        path: &Path,
+        versions: u8,
+    ) -> Result<(), Error> {
+        if versions == 0 {
             return Ok(());
         }
-        fs::create_dir(p)?;
+        for version in (1..versions).rev() {
+            let from = Self::version_file(path, version);
+            let to = Self::version_file(path, version + 1);
+            if storage.exists(&from) {
+                storage.rename(&from, &to)?;
+            } else {
+                storage.remove_file(&to)?;
+            }
+        }
+        let latest = Self::version_file(path, 1);
+        if storage.exists(path) {
+            // copy, so the file stays in place until overwritten
+            storage.copy(path, &latest)?;
+        } else {
+            storage.remove_file(&latest)?;
+        }
         Ok(())
     }
 
-    fn get_storage_folder() -> PathBuf {
+    /// The data folder, containing all stored files
+    pub fn get_storage_folder() -> PathBuf {
+        if let Some(folder) = STORAGE_FOLDER.get() {
+            return folder.clone();
+        }
         let mut p = dirs::data_local_dir().unwrap_or(PathBuf::from("."));
         p.push(LOCAL_STORAGE_FOLDER);
         p
@@ -46,4 +252,119 @@ impl Storage {
         p.push(file_name);
         p
     }
+
+    /// Folder of an identity, containing its key files
+    pub fn identity_folder(identity: &str) -> PathBuf {
+        let mut p = Self::full_file_path(IDENTITIES_FOLDER);
+        p.push(identity);
+        p
+    }
+
+    fn identity_file_path(identity: &str, file_name: &str) -> PathBuf {
+        let mut p = Self::identity_folder(identity);
+        p.push(file_name);
+        p
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::storage_backend::FileStorage;
+
+    #[test]
+    fn test_write_file_and_keep_versions() {
+        let storage = FileStorage {};
+        let mut folder = std::env::temp_dir();
+        folder.push(format!("keystr-test-storage-{}", std::process::id()));
+        let _res = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let path = folder.join(".ncrypt");
+
+        // no file yet, no version
+        Storage::keep_version(&storage, &path, 2).unwrap();
+        assert!(!Storage::version_file(&path, 1).exists());
+
+        for contents in ["v1", "v2", "v3", "v4"] {
+            Storage::keep_version(&storage, &path, 2).unwrap();
+            Storage::write_private_file(&path, contents.as_bytes()).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "v4");
+        assert_eq!(
+            fs::read_to_string(Storage::version_file(&path, 1)).unwrap(),
+            "v3"
+        );
+        assert_eq!(
+            fs::read_to_string(Storage::version_file(&path, 2)).unwrap(),
+            "v2"
+        );
+        assert!(!Storage::version_file(&path, 3).exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // a missing file leaves a gap in its versions
+        fs::remove_file(&path).unwrap();
+        Storage::keep_version(&storage, &path, 2).unwrap();
+        assert!(!Storage::version_file(&path, 1).exists());
+        assert_eq!(
+            fs::read_to_string(Storage::version_file(&path, 2)).unwrap(),
+            "v3"
+        );
+
+        // left-over temporary file is replaced
+        fs::write(folder.join(".ncrypt.tmp"), "partial").unwrap();
+        Storage::write_file(&path, b"v5").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "v5");
+        assert!(!folder.join(".ncrypt.tmp").exists());
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_storage_folder() {
+        let exe_folder =
+            std::env::temp_dir().join(format!("keystr-test-storage-exe-{}", std::process::id()));
+        let _res = fs::remove_dir_all(&exe_folder);
+        fs::create_dir_all(&exe_folder).unwrap();
+        let resolve = |data_dir: Option<&str>, portable: bool, env: Option<&str>| {
+            Storage::resolve_storage_folder(
+                data_dir.map(PathBuf::from),
+                portable,
+                env.map(OsString::from),
+                Some(&exe_folder),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(resolve(None, false, None), None);
+        assert_eq!(resolve(None, false, Some("")), None);
+        assert_eq!(
+            resolve(None, false, Some("/media/usb/keystr")),
+            Some(PathBuf::from("/media/usb/keystr"))
+        );
+        assert_eq!(
+            resolve(None, true, Some("/media/usb/keystr")),
+            Some(exe_folder.join(PORTABLE_STORAGE_FOLDER))
+        );
+        assert_eq!(
+            resolve(Some("/tmp/profile2"), true, Some("/media/usb/keystr")),
+            Some(PathBuf::from("/tmp/profile2"))
+        );
+        // portable by marker file
+        fs::write(exe_folder.join(PORTABLE_MARKER_FILENAME), "").unwrap();
+        assert_eq!(
+            resolve(None, false, Some("/media/usb/keystr")),
+            Some(exe_folder.join(PORTABLE_STORAGE_FOLDER))
+        );
+        assert!(matches!(
+            Storage::resolve_storage_folder(None, true, None, None),
+            Err(Error::StoragePortableNoExecutable)
+        ));
+
+        fs::remove_dir_all(&exe_folder).unwrap();
+    }
 }
@@ -0,0 +1,395 @@
+use crate::base::error::Error;
+use crate::base::storage::Storage;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+/// Backend for storing data: file operations on the paths given by `Storage`.
+/// Writes are atomic; writes, renames and copies create missing parent folders.
+pub trait StorageBackend: Send + Sync {
+    /// Read the contents of a file
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error>;
+
+    /// Write a file, replacing it if it exists.
+    /// A private file (containing encrypted secrets) is readable only by the user.
+    fn write(&self, path: &Path, contents: &[u8], private: bool) -> Result<(), Error>;
+
+    /// True if the file exists
+    fn exists(&self, path: &Path) -> bool;
+
+    /// True if the file is readable only by the user (true where permissions are not supported)
+    fn is_private(&self, path: &Path) -> Result<bool, Error>;
+
+    /// Time of the last modification of a file, unix timestamp
+    fn modified(&self, path: &Path) -> Result<u64, Error>;
+
+    /// Remove a file, if it exists
+    fn remove_file(&self, path: &Path) -> Result<(), Error>;
+
+    /// Rename (move) a file, replacing the target if it exists
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), Error>;
+
+    /// Copy a file, keeping its permissions
+    fn copy(&self, from: &Path, to: &Path) -> Result<(), Error>;
+
+    /// Remove a folder with all its contents, if it exists
+    fn remove_folder(&self, folder: &Path) -> Result<(), Error>;
+
+    /// Rename (move) a folder with all its contents, if it exists; the target must not exist
+    fn rename_folder(&self, from: &Path, to: &Path) -> Result<(), Error>;
+
+    /// All files in a folder and its subfolders
+    fn list_files(&self, folder: &Path) -> Result<Vec<PathBuf>, Error>;
+
+    /// Read the contents of a text file
+    fn read_to_string(&self, path: &Path) -> Result<String, Error> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|_e| io::Error::from(io::ErrorKind::InvalidData).into())
+    }
+}
+
+/// Storage in the file system (in the data folder)
+pub(crate) struct FileStorage {}
+
+impl FileStorage {
+    pub fn new_shared() -> Arc<dyn StorageBackend> {
+        Arc::new(FileStorage {})
+    }
+
+    /// Create the folder of a file if missing (a configured data folder may be nested)
+    fn check_create_parent(path: &Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            if !parent.is_dir() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StorageBackend for FileStorage {
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        Ok(fs::read(path)?)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8], private: bool) -> Result<(), Error> {
+        Self::check_create_parent(path)?;
+        if private {
+            Storage::write_private_file(path, contents)
+        } else {
+            Storage::write_file(path, contents)
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    #[cfg(unix)]
+    fn is_private(&self, path: &Path) -> Result<bool, Error> {
+        use std::os::unix::fs::PermissionsExt;
+        Ok(fs::metadata(path)?.permissions().mode() & 0o077 == 0)
+    }
+
+    #[cfg(not(unix))]
+    fn is_private(&self, path: &Path) -> Result<bool, Error> {
+        fs::metadata(path)?;
+        Ok(true)
+    }
+
+    fn modified(&self, path: &Path) -> Result<u64, Error> {
+        Ok(fs::metadata(path)?
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), Error> {
+        if path.is_file() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        Self::check_create_parent(to)?;
+        Ok(fs::rename(from, to)?)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        Self::check_create_parent(to)?;
+        fs::copy(from, to)?;
+        Ok(())
+    }
+
+    fn remove_folder(&self, folder: &Path) -> Result<(), Error> {
+        if folder.is_dir() {
+            fs::remove_dir_all(folder)?;
+        }
+        Ok(())
+    }
+
+    fn rename_folder(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        if from.is_dir() {
+            Self::check_create_parent(to)?;
+            fs::rename(from, to)?;
+        }
+        Ok(())
+    }
+
+    fn list_files(&self, folder: &Path) -> Result<Vec<PathBuf>, Error> {
+        let mut files = Vec::new();
+        if folder.is_dir() {
+            for entry in fs::read_dir(folder)? {
+                let entry = entry?;
+                let file_type = entry.file_type()?;
+                if file_type.is_dir() {
+                    files.extend(self.list_files(&entry.path())?);
+                } else if file_type.is_file() {
+                    files.push(entry.path());
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+#[cfg(test)]
+/// Storage in memory, for tests
+pub(crate) mod memory {
+    use super::StorageBackend;
+    use crate::base::error::Error;
+
+    use zeroize::Zeroize;
+
+    use std::collections::BTreeMap;
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    /// A file kept by MemoryStorage
+    struct MemoryFile {
+        contents: Vec<u8>,
+        private: bool,
+        modified: u64,
+    }
+
+    impl Drop for MemoryFile {
+        fn drop(&mut self) {
+            self.contents.zeroize();
+        }
+    }
+
+    /// Storage in memory, nothing is persisted; for tests
+    #[derive(Default)]
+    pub(crate) struct MemoryStorage {
+        files: Mutex<BTreeMap<PathBuf, MemoryFile>>,
+        /// If set, the number of writes that still succeed, later ones fail (to test failures)
+        writes_left: Mutex<Option<usize>>,
+    }
+
+    impl MemoryStorage {
+        pub fn new_shared() -> Arc<dyn StorageBackend> {
+            Arc::new(MemoryStorage::default())
+        }
+
+        /// Make writes fail after the given number of successful ones; None for no failures
+        pub fn fail_writes_after(&self, writes: Option<usize>) {
+            *self.writes_left.lock().unwrap_or_else(|e| e.into_inner()) = writes;
+        }
+
+        fn not_found() -> Error {
+            io::Error::from(io::ErrorKind::NotFound).into()
+        }
+
+        fn files(&self) -> std::sync::MutexGuard<'_, BTreeMap<PathBuf, MemoryFile>> {
+            // a panic while holding the lock leaves the map consistent
+            self.files.lock().unwrap_or_else(|e| e.into_inner())
+        }
+    }
+
+    impl StorageBackend for MemoryStorage {
+        fn read(&self, path: &Path) -> Result<Vec<u8>, Error> {
+            match self.files().get(path) {
+                None => Err(Self::not_found()),
+                Some(file) => Ok(file.contents.clone()),
+            }
+        }
+
+        fn write(&self, path: &Path, contents: &[u8], private: bool) -> Result<(), Error> {
+            if let Some(writes_left) = self
+                .writes_left
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .as_mut()
+            {
+                if *writes_left == 0 {
+                    return Err(io::Error::from(io::ErrorKind::Other).into());
+                }
+                *writes_left -= 1;
+            }
+            let modified = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            self.files().insert(
+                path.to_path_buf(),
+                MemoryFile {
+                    contents: contents.to_vec(),
+                    private,
+                    modified,
+                },
+            );
+            Ok(())
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            self.files().contains_key(path)
+        }
+
+        fn is_private(&self, path: &Path) -> Result<bool, Error> {
+            match self.files().get(path) {
+                None => Err(Self::not_found()),
+                Some(file) => Ok(file.private),
+            }
+        }
+
+        fn modified(&self, path: &Path) -> Result<u64, Error> {
+            match self.files().get(path) {
+                None => Err(Self::not_found()),
+                Some(file) => Ok(file.modified),
+            }
+        }
+
+        fn remove_file(&self, path: &Path) -> Result<(), Error> {
+            self.files().remove(path);
+            Ok(())
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+            let mut files = self.files();
+            match files.remove(from) {
+                None => Err(Self::not_found()),
+                Some(file) => {
+                    files.insert(to.to_path_buf(), file);
+                    Ok(())
+                }
+            }
+        }
+
+        fn copy(&self, from: &Path, to: &Path) -> Result<(), Error> {
+            let mut files = self.files();
+            let copy = match files.get(from) {
+                None => return Err(Self::not_found()),
+                Some(file) => MemoryFile {
+                    contents: file.contents.clone(),
+                    private: file.private,
+                    modified: file.modified,
+                },
+            };
+            files.insert(to.to_path_buf(), copy);
+            Ok(())
+        }
+
+        fn remove_folder(&self, folder: &Path) -> Result<(), Error> {
+            self.files().retain(|path, _file| !path.starts_with(folder));
+            Ok(())
+        }
+
+        fn rename_folder(&self, from: &Path, to: &Path) -> Result<(), Error> {
+            let mut files = self.files();
+            let paths: Vec<PathBuf> = files
+                .keys()
+                .filter(|path| path.starts_with(from))
+                .cloned()
+                .collect();
+            for path in paths {
+                if let (Some(file), Ok(relative_path)) =
+                    (files.remove(&path), path.strip_prefix(from))
+                {
+                    files.insert(to.join(relative_path), file);
+                }
+            }
+            Ok(())
+        }
+
+        fn list_files(&self, folder: &Path) -> Result<Vec<PathBuf>, Error> {
+            Ok(self
+                .files()
+                .keys()
+                .filter(|path| path.starts_with(folder))
+                .cloned()
+                .collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::memory::MemoryStorage;
+    use super::*;
+
+    fn check_backend(storage: &dyn StorageBackend, folder: &Path) {
+        let path = folder.join("identities").join("main").join(".ncrypt");
+        assert!(!storage.exists(&path));
+        assert!(storage.read(&path).is_err());
+        storage.write(&path, b"secret", true).unwrap();
+        assert!(storage.exists(&path));
+        assert_eq!(storage.read(&path).unwrap(), b"secret".to_vec());
+        assert!(storage.is_private(&path).unwrap());
+        assert!(storage.modified(&path).unwrap() > 0);
+
+        let settings = folder.join("settings.json");
+        storage.write(&settings, b"{}", false).unwrap();
+        storage.write(&settings, b"{ }", false).unwrap();
+        assert_eq!(storage.read_to_string(&settings).unwrap(), "{ }");
+
+        let copy = folder.join("identities").join("main").join(".ncrypt.1");
+        storage.copy(&path, &copy).unwrap();
+        assert!(storage.is_private(&copy).unwrap());
+        let moved = folder.join("identities").join("main").join(".ncrypt.2");
+        storage.rename(&copy, &moved).unwrap();
+        assert!(!storage.exists(&copy));
+        assert_eq!(storage.read(&moved).unwrap(), b"secret".to_vec());
+        assert_eq!(
+            storage.list_files(folder).unwrap(),
+            vec![path.clone(), moved.clone(), settings.clone()]
+        );
+
+        storage.remove_file(&moved).unwrap();
+        // removing a missing file is fine
+        storage.remove_file(&moved).unwrap();
+        let renamed = folder.join("identities2");
+        storage
+            .rename_folder(&folder.join("identities"), &renamed)
+            .unwrap();
+        assert!(!storage.exists(&path));
+        assert!(storage.exists(&renamed.join("main").join(".ncrypt")));
+        // renaming a missing folder is fine
+        storage
+            .rename_folder(&folder.join("identities"), &renamed)
+            .unwrap();
+        storage.remove_folder(&renamed).unwrap();
+        assert_eq!(storage.list_files(folder).unwrap(), vec![settings]);
+    }
+
+    #[test]
+    fn test_memory_storage() {
+        check_backend(&MemoryStorage::default(), Path::new("/keystr"));
+    }
+
+    #[test]
+    fn test_file_storage() {
+        let mut folder = std::env::temp_dir();
+        folder.push(format!("keystr-test-file-storage-{}", std::process::id()));
+        let _res = fs::remove_dir_all(&folder);
+        check_backend(&FileStorage {}, &folder);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,183 @@
+use crate::base::error::Error;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+/// Accepted local date-time formats (without time zone, taken as local time)
+const LOCAL_FORMATS: [&str; 4] = [
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Duration units, with their length in seconds; months and years are approximate
+const DURATION_UNITS: [(&[&str], u64); 7] = [
+    (&["s", "sec", "secs", "second", "seconds"], 1),
+    (&["m", "min", "mins", "minute", "minutes"], 60),
+    (&["h", "hour", "hours"], 3600),
+    (&["d", "day", "days"], 86400),
+    (&["w", "week", "weeks"], 7 * 86400),
+    (&["mo", "month", "months"], 30 * 86400),
+    (&["y", "year", "years"], 365 * 86400),
+];
+
+/// Parsing and display of times and durations entered by the user
+pub(crate) struct TimeInput {}
+
+impl TimeInput {
+    /// Parse a point in time into a unix timestamp. Accepted:
+    /// a unix timestamp ('1676067553'), ISO-8601 with time zone ('2023-02-10T22:19:13Z',
+    /// '2023-02-10T23:19:13+01:00'), local date and time ('2023-02-10 23:19'), or a local date
+    /// ('2023-02-10', the start of the day)
+    pub fn parse_time(input: &str) -> Result<u64, Error> {
+        let input = input.trim();
+        if !input.is_empty() && input.chars().all(|c| c.is_ascii_digit()) {
+            return input
+                .parse::<u64>()
+                .map_err(|_e| Error::DelegationInvalidTime);
+        }
+        let timestamp = if let Ok(dt) = DateTime::parse_from_rfc3339(input) {
+            dt.timestamp()
+        } else {
+            let naive = LOCAL_FORMATS
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+                .or_else(|| {
+                    NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                        .ok()
+                        .and_then(|date| date.and_hms_opt(0, 0, 0))
+                })
+                .ok_or(Error::DelegationInvalidTime)?;
+            // a local time skipped by a daylight saving change does not exist
+            Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .ok_or(Error::DelegationInvalidTime)?
+                .timestamp()
+        };
+        u64::try_from(timestamp).map_err(|_e| Error::DelegationInvalidTime)
+    }
+
+    /// Parse a duration into seconds, e.g. '2h', '6 weeks', '1d 12h'; a plain number is days
+    pub fn parse_duration(input: &str) -> Result<u64, Error> {
+        let input = input.trim();
+        let mut total: u64 = 0;
+        let mut rest = input;
+        if let Ok(days) = input.parse::<u64>() {
+            total = days
+                .checked_mul(86400)
+                .ok_or(Error::DelegationInvalidDuration)?;
+            rest = "";
+        }
+        while !rest.is_empty() {
+            let number_len = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let number = rest[..number_len]
+                .parse::<u64>()
+                .map_err(|_e| Error::DelegationInvalidDuration)?;
+            rest = rest[number_len..].trim_start();
+            let unit_len = rest
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(rest.len());
+            let unit = rest[..unit_len].to_lowercase();
+            let seconds = DURATION_UNITS
+                .iter()
+                .find(|(names, _seconds)| names.contains(&unit.as_str()))
+                .map(|(_names, seconds)| *seconds)
+                .ok_or(Error::DelegationInvalidDuration)?;
+            total = number
+                .checked_mul(seconds)
+                .and_then(|s| total.checked_add(s))
+                .ok_or(Error::DelegationInvalidDuration)?;
+            rest = rest[unit_len..].trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        }
+        if total == 0 {
+            return Err(Error::DelegationInvalidDuration);
+        }
+        Ok(total)
+    }
+
+    /// Format a unix timestamp in local time, e.g. '2023-02-10 23:19 +01:00'
+    pub fn format_local(timestamp: u64) -> String {
+        match i64::try_from(timestamp)
+            .ok()
+            .and_then(|t| Local.timestamp_opt(t, 0).single())
+        {
+            Some(dt) => dt.format("%Y-%m-%d %H:%M %:z").to_string(),
+            None => timestamp.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(TimeInput::parse_time("1676067553").unwrap(), 1676067553);
+        assert_eq!(
+            TimeInput::parse_time("2023-02-10T22:19:13Z").unwrap(),
+            1676067553
+        );
+        assert_eq!(
+            TimeInput::parse_time(" 2023-02-10T23:19:13+01:00 ").unwrap(),
+            1676067553
+        );
+
+        // local time, compare to the local time of the timestamp
+        let local = Local.timestamp_opt(1676067540, 0).unwrap();
+        let input = local.format("%Y-%m-%d %H:%M").to_string();
+        assert_eq!(TimeInput::parse_time(&input).unwrap(), 1676067540);
+        let input = local.format("%Y-%m-%dT%H:%M:%S").to_string();
+        assert_eq!(TimeInput::parse_time(&input).unwrap(), 1676067540);
+        let day_start = TimeInput::parse_time(&local.format("%Y-%m-%d").to_string()).unwrap();
+        assert!(day_start <= 1676067540 && 1676067540 - day_start < 86400);
+
+        for input in [
+            "",
+            "yesterday",
+            "2023-02-30",
+            "2023-02-10 25:00",
+            "-5",
+            "1969-12-31T00:00:00Z",
+        ] {
+            assert!(TimeInput::parse_time(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(TimeInput::parse_duration("90").unwrap(), 90 * 86400);
+        assert_eq!(TimeInput::parse_duration("2h").unwrap(), 7200);
+        assert_eq!(TimeInput::parse_duration("6 weeks").unwrap(), 42 * 86400);
+        assert_eq!(TimeInput::parse_duration("1d 12h").unwrap(), 36 * 3600);
+        assert_eq!(
+            TimeInput::parse_duration("1 Day, 30 min").unwrap(),
+            86400 + 1800
+        );
+        assert_eq!(TimeInput::parse_duration("3mo").unwrap(), 90 * 86400);
+        for input in [
+            "",
+            "0",
+            "0h",
+            "h",
+            "2 fortnights",
+            "2h x",
+            "-2h",
+            "99999999999999999y",
+        ] {
+            assert!(TimeInput::parse_duration(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_format_local() {
+        let local = Local.timestamp_opt(1676067553, 0).unwrap();
+        assert_eq!(
+            TimeInput::format_local(1676067553),
+            local.format("%Y-%m-%d %H:%M %:z").to_string()
+        );
+    }
+}
@@ -2,10 +2,97 @@ mod base;
 mod model;
 mod ui;
 
+use crate::base::storage::{Storage, DATA_DIR_ENV_VAR};
 use crate::ui::ui_iced::KeystrApp;
 use iced::{Application, Settings};
 
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::process::exit;
+
+/// Command line options
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    /// Data folder to use instead of the default
+    data_dir: Option<PathBuf>,
+    /// Portable mode, data is kept next to the executable
+    portable: bool,
+}
+
+fn usage() -> String {
+    format!(
+        "Usage: keystr-rs [--data-dir <folder>] [--portable]\n\n  --data-dir <folder>  Keep data (keys, settings) in the given folder\n  --portable           Keep data in the 'keystr-data' folder next to the executable\n\nThe data folder can also be set in the {} environment variable.",
+        DATA_DIR_ENV_VAR
+    )
+}
+
+/// Parse command line arguments (without the program name); None if help is asked
+fn parse_args(mut args: impl Iterator<Item = OsString>) -> Result<Option<Options>, String> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--help") | Some("-h") => return Ok(None),
+            Some("--portable") => options.portable = true,
+            Some("--data-dir") => match args.next() {
+                None => return Err("Missing folder after --data-dir".to_string()),
+                Some(folder) => options.data_dir = Some(PathBuf::from(folder)),
+            },
+            Some(a) if a.starts_with("--data-dir=") => {
+                options.data_dir = Some(PathBuf::from(&a["--data-dir=".len()..]))
+            }
+            _ => return Err(format!("Unknown argument: {}", arg.to_string_lossy())),
+        }
+    }
+    Ok(Some(options))
+}
+
 #[tokio::main]
 async fn main() {
+    let options = match parse_args(std::env::args_os().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", usage());
+            return;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, usage());
+            exit(2);
+        }
+    };
+    if let Err(e) = Storage::configure(options.data_dir, options.portable) {
+        eprintln!("Could not set data folder: {}", e);
+        exit(2);
+    }
     let _res = KeystrApp::run(Settings::default());
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Options>, String> {
+        parse_args(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse(&[]), Ok(Some(Options::default())));
+        assert_eq!(
+            parse(&["--data-dir", "/media/usb/keystr"]),
+            Ok(Some(Options {
+                data_dir: Some(PathBuf::from("/media/usb/keystr")),
+                portable: false,
+            }))
+        );
+        assert_eq!(
+            parse(&["--portable", "--data-dir=profile2"]),
+            Ok(Some(Options {
+                data_dir: Some(PathBuf::from("profile2")),
+                portable: true,
+            }))
+        );
+        assert_eq!(parse(&["--help"]), Ok(None));
+        assert!(parse(&["--data-dir"]).is_err());
+        assert!(parse(&["--verbose"]).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use crate::base::backup_archive::BackupArchive;
+use crate::base::encrypt::KdfParams;
+use crate::base::error::Error;
+use crate::base::paper_backup::PaperBackup;
+use crate::base::secret::SecretString;
+use crate::base::storage::Storage;
+use crate::base::storage_backend::StorageBackend;
+use crate::model::status_messages::StatusMessages;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Model for the encrypted backup of the whole data folder (keys of all identities, settings),
+/// e.g. for moving to another machine, and for restoring it
+pub(crate) struct DataBackup {
+    /// Storage of the data folder
+    storage: Arc<dyn StorageBackend>,
+    /// Input for the backup file path to write
+    pub path_input: String,
+    /// Input for the backup password
+    pub password_input: SecretString,
+    /// Input for the backup password, repeated
+    pub repeat_password_input: SecretString,
+    /// Input for the backup file path to restore from
+    pub restore_path_input: String,
+    /// Input for the password of the backup to restore
+    pub restore_password_input: SecretString,
+    /// Backup read and validated, waiting for confirmation to restore
+    pending_restore: Option<BackupArchive>,
+}
+
+impl DataBackup {
+    pub fn new(storage: Arc<dyn StorageBackend>) -> Self {
+        Self {
+            storage,
+            path_input: String::new(),
+            password_input: SecretString::new(),
+            repeat_password_input: SecretString::new(),
+            restore_path_input: String::new(),
+            restore_password_input: SecretString::new(),
+            pending_restore: None,
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Write an encrypted backup of the data folder to a file. Returns the number of files.
+    /// It is recommend to zeroize() the password after use.
+    pub fn create_backup(
+        &self,
+        path: &Path,
+        password: &str,
+        kdf: &KdfParams,
+    ) -> Result<usize, Error> {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let archive = BackupArchive::from_folder(
+            self.storage.as_ref(),
+            &Storage::get_storage_folder(),
+            created_at,
+        )?;
+        let data = archive.encrypt(password, kdf)?;
+        Storage::write_private_file(path, &data)?;
+        Ok(archive.file_count())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Read and validate a backup file. It is kept for restoring it once confirmed,
+    /// the returned description is for the confirmation.
+    /// It is recommend to zeroize() the password after use.
+    pub fn prepare_restore(&mut self, path: &Path, password: &str) -> Result<String, Error> {
+        if !path.is_file() {
+            return Err(Error::BackupFileNotFound);
+        }
+        let archive = BackupArchive::decrypt(&std::fs::read(path)?, password)?;
+        let description = format!(
+            "Backup of {}, {} files, identities: {}",
+            PaperBackup::format_date(archive.created_at()),
+            archive.file_count(),
+            archive.identities().join(", ")
+        );
+        self.pending_restore = Some(archive);
+        Ok(description)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Restore the backup prepared by prepare_restore() into the data folder,
+    /// overwriting the current files
+    pub fn restore(&mut self) -> Result<(), Error> {
+        match self.pending_restore.take() {
+            None => Err(Error::BackupInvalid),
+            Some(archive) => {
+                archive.restore_to(self.storage.as_ref(), &Storage::get_storage_folder())
+            }
+        }
+    }
+
+    pub fn cancel_restore(&mut self) {
+        self.pending_restore = None;
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Action to write a backup of the data folder, encrypted with the given key derivation
+    pub fn create_action(&mut self, kdf: &KdfParams, status: &mut StatusMessages) {
+        let path = self.path_input.trim().to_string();
+        let res = if path.is_empty() {
+            Err(Error::BackupPathMissing)
+        } else if self.password_input.is_empty() {
+            Err(Error::KeyEncryptionPasswordMissing)
+        } else if self.password_input.as_str() != self.repeat_password_input.as_str() {
+            Err(Error::KeyEncryptionPasswordMismatch)
+        } else {
+            self.create_backup(&PathBuf::from(&path), self.password_input.as_str(), kdf)
+        };
+        match res {
+            Err(e) => status.set_error(&format!("Could not write backup ({})", e)),
+            Ok(count) => {
+                self.password_input.clear();
+                self.repeat_password_input.clear();
+                status.set(&format!("Backup of {} files written to '{}'", count, path));
+            }
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Action to read a backup for restore; returns its description if it is valid,
+    /// it is restored after confirmation
+    pub fn prepare_restore_action(&mut self, status: &mut StatusMessages) -> Option<String> {
+        let path = self.restore_path_input.trim().to_string();
+        let res = if path.is_empty() {
+            Err(Error::BackupPathMissing)
+        } else {
+            self.prepare_restore(
+                &PathBuf::from(&path),
+                &self.restore_password_input.to_zeroizing(),
+            )
+        };
+        // cleanup
+        self.restore_password_input.clear();
+        match res {
+            Err(e) => {
+                status.set_error(&format!("Could not read backup, check password! ({})", e));
+                None
+            }
+            Ok(description) => Some(description),
+        }
+    }
+}
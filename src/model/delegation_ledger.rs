@@ -0,0 +1,388 @@
+use crate::base::error::Error;
+use crate::base::storage::Storage;
+use crate::base::storage_backend::StorageBackend;
+use crate::model::keystore::KeySigner;
+use crate::model::status_messages::StatusMessages;
+
+use nostr::prelude::{Conditions, DelegationTag, FromBech32, ToBech32, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where a delegation was created
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) enum DelegationSource {
+    /// In the Delegate tab
+    Delegator,
+    /// For a Nostr Connect (NIP-46) request, from the client with the given npub
+    SignerRequest(String),
+    /// Re-issued at a key rotation, from the delegation of the retired key with the given npub
+    Rotation(String),
+}
+
+impl fmt::Display for DelegationSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DelegationSource::Delegator => write!(f, "Delegate tab"),
+            DelegationSource::SignerRequest(client) => write!(f, "Nostr Connect ({})", client),
+            DelegationSource::Rotation(old_npub) => write!(f, "Key rotation ({})", old_npub),
+        }
+    }
+}
+
+/// Validity of a delegation at a given time, from its time conditions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum DelegationStatus {
+    Active,
+    Expired,
+    NotYetValid,
+}
+
+impl DelegationStatus {
+    /// Validity at the given time (unix timestamp) by the time conditions; the limits are exclusive
+    pub fn of_conditions(conditions: &str, now: u64) -> Self {
+        match (Self::valid_from(conditions), Self::valid_until(conditions)) {
+            (_, Some(until)) if now >= until => DelegationStatus::Expired,
+            (Some(from), _) if now <= from => DelegationStatus::NotYetValid,
+            _ => DelegationStatus::Active,
+        }
+    }
+
+    /// Start of validity ('created_at>' condition) of a conditions string, if limited
+    pub fn valid_from(conditions: &str) -> Option<u64> {
+        Self::time_conditions(conditions, "created_at>").max()
+    }
+
+    /// End of validity ('created_at<' condition) of a conditions string, if limited
+    pub fn valid_until(conditions: &str) -> Option<u64> {
+        Self::time_conditions(conditions, "created_at<").min()
+    }
+
+    fn time_conditions<'a>(conditions: &'a str, prefix: &'a str) -> impl Iterator<Item = u64> + 'a {
+        conditions
+            .split('&')
+            .filter_map(move |c| c.trim().strip_prefix(prefix))
+            .filter_map(|value| value.trim().parse::<u64>().ok())
+    }
+}
+
+impl fmt::Display for DelegationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DelegationStatus::Active => write!(f, "active"),
+            DelegationStatus::Expired => write!(f, "expired"),
+            DelegationStatus::NotYetValid => write!(f, "not yet valid"),
+        }
+    }
+}
+
+/// A delegation created (signed) by one of our identities
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct DelegationRecord {
+    /// Delegator (signing identity) npub
+    pub delegator: String,
+    /// Delegatee npub, the key empowered to post on behalf of the delegator
+    pub delegatee: String,
+    /// Conditions string, e.g. 'kind=1&created_at>1676067553&created_at<1678659553'
+    pub conditions: String,
+    /// Signature of the delegation token (hex)
+    pub signature: String,
+    /// The delegation tag (JSON), as given to the delegatee
+    pub tag: String,
+    /// Creation time, unix timestamp
+    pub created_at: u64,
+    pub source: DelegationSource,
+}
+
+impl DelegationRecord {
+    pub fn new(
+        delegator: &XOnlyPublicKey,
+        delegatee: &XOnlyPublicKey,
+        conditions: &str,
+        tag: &DelegationTag,
+        source: DelegationSource,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            delegator: delegator.to_bech32()?,
+            delegatee: delegatee.to_bech32()?,
+            conditions: conditions.to_string(),
+            signature: tag.signature().to_string(),
+            tag: tag.to_string(),
+            created_at: DelegationLedger::current_time(),
+            source,
+        })
+    }
+
+    /// Start of validity ('created_at>' condition), if limited
+    pub fn valid_from(&self) -> Option<u64> {
+        DelegationStatus::valid_from(&self.conditions)
+    }
+
+    /// End of validity ('created_at<' condition), if limited
+    pub fn valid_until(&self) -> Option<u64> {
+        DelegationStatus::valid_until(&self.conditions)
+    }
+
+    /// Validity at the given time (unix timestamp)
+    pub fn status(&self, now: u64) -> DelegationStatus {
+        DelegationStatus::of_conditions(&self.conditions, now)
+    }
+
+    /// Sign the same delegation (same delegatee and conditions) with another key,
+    /// e.g. the new key of a rotated identity
+    pub fn reissue(&self, key_signer: &KeySigner) -> Result<DelegationRecord, Error> {
+        let delegatee_key = XOnlyPublicKey::from_bech32(&self.delegatee)?;
+        let conditions = Conditions::from_str(&self.conditions)?;
+        let tag =
+            key_signer.with_keys(|keys| DelegationTag::new(keys, delegatee_key, conditions))??;
+        DelegationRecord::new(
+            &key_signer.get_public_key(),
+            &delegatee_key,
+            &self.conditions,
+            &tag,
+            DelegationSource::Rotation(self.delegator.clone()),
+        )
+    }
+}
+
+/// Delegation as exported, with its status at the time of the export
+#[derive(Serialize)]
+struct ExportedDelegation<'a> {
+    #[serde(flatten)]
+    record: &'a DelegationRecord,
+    valid_from: Option<u64>,
+    valid_until: Option<u64>,
+    status: String,
+}
+
+/// Model for the ledger of created delegations: every delegation signed is recorded
+/// and persisted, so it is known which keys were empowered, how, and until when
+pub(crate) struct DelegationLedger {
+    /// Storage of the ledger file
+    storage: Arc<dyn StorageBackend>,
+    /// Recorded delegations, oldest first
+    records: Vec<DelegationRecord>,
+    /// Set if the ledger file exists but could not be loaded; saving would overwrite it
+    load_failed: bool,
+    /// Input for the file path to export the ledger to
+    pub export_path_input: String,
+}
+
+impl DelegationLedger {
+    pub fn new(storage: Arc<dyn StorageBackend>) -> Self {
+        Self {
+            storage,
+            records: Vec::new(),
+            load_failed: false,
+            export_path_input: String::new(),
+        }
+    }
+
+    pub fn current_time() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    }
+
+    pub fn records(&self) -> &Vec<DelegationRecord> {
+        &self.records
+    }
+
+    /// Delegations of the delegator (npub) still in effect at the given time: active,
+    /// or not yet valid
+    pub fn records_in_effect(&self, delegator: &str, now: u64) -> Vec<DelegationRecord> {
+        self.records
+            .iter()
+            .filter(|r| r.delegator == delegator)
+            .filter(|r| r.status(now) != DelegationStatus::Expired)
+            .cloned()
+            .collect()
+    }
+
+    /// Load the ledger from file, replacing the current records; empty if none is saved.
+    /// If loading fails, no records can be added until a successful load.
+    pub fn load(&mut self) -> Result<(), Error> {
+        self.records = Vec::new();
+        self.load_failed = true;
+        let path = Storage::delegations_file();
+        if self.storage.exists(&path) {
+            self.records = serde_json::from_str(&self.storage.read_to_string(&path)?)?;
+        }
+        self.load_failed = false;
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        self.storage.write(
+            &Storage::delegations_file(),
+            serde_json::to_string(&self.records)?.as_bytes(),
+            false,
+        )
+    }
+
+    /// Record a newly created delegation, and save the ledger
+    pub fn add(&mut self, record: DelegationRecord) -> Result<(), Error> {
+        if self.load_failed {
+            return Err(Error::DelegationLedgerNotLoaded);
+        }
+        self.records.push(record);
+        self.save()
+    }
+
+    /// Write the ledger to a file, as JSON, with the current status of each delegation.
+    /// Returns the number of delegations.
+    pub fn export(&self, path: &Path) -> Result<usize, Error> {
+        let now = Self::current_time();
+        let exported: Vec<ExportedDelegation> = self
+            .records
+            .iter()
+            .map(|record| ExportedDelegation {
+                record,
+                valid_from: record.valid_from(),
+                valid_until: record.valid_until(),
+                status: record.status(now).to_string(),
+            })
+            .collect();
+        Storage::write_file(path, serde_json::to_string_pretty(&exported)?.as_bytes())?;
+        Ok(exported.len())
+    }
+
+    /// Action to record a newly created delegation
+    pub fn add_action(&mut self, record: DelegationRecord, status: &mut StatusMessages) {
+        if let Err(e) = self.add(record) {
+            status.set_error(&format!("Could not save delegation ledger ({})", e));
+        }
+    }
+
+    /// Action to export the ledger to the file given in the input
+    pub fn export_action(&mut self, status: &mut StatusMessages) {
+        let path = self.export_path_input.trim().to_string();
+        let res = if path.is_empty() {
+            Err(Error::DelegationExportPathMissing)
+        } else {
+            self.export(&PathBuf::from(&path))
+        };
+        match res {
+            Err(e) => status.set_error(&format!("Could not export delegations ({})", e)),
+            Ok(count) => status.set(&format!("{} delegations exported to '{}'", count, path)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::storage_backend::memory::MemoryStorage;
+
+    const NPUB: &str = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+    const NPUB2: &str = "npub1h652adkpv4lr8k66cadg8yg0wl5wcc29z4lyw66m3rrwskcl4v6qr82xez";
+
+    fn record(conditions: &str) -> DelegationRecord {
+        DelegationRecord {
+            delegator: NPUB.to_string(),
+            delegatee: NPUB2.to_string(),
+            conditions: conditions.to_string(),
+            signature: "00".repeat(64),
+            tag: String::new(),
+            created_at: 1676067553,
+            source: DelegationSource::Delegator,
+        }
+    }
+
+    #[test]
+    fn test_status() {
+        let r = record("kind=1&created_at>1676067553&created_at<1678659553");
+        assert_eq!(r.valid_from(), Some(1676067553));
+        assert_eq!(r.valid_until(), Some(1678659553));
+        assert_eq!(r.status(1676067553), DelegationStatus::NotYetValid);
+        assert_eq!(r.status(1677000000), DelegationStatus::Active);
+        assert_eq!(r.status(1678659553), DelegationStatus::Expired);
+
+        // no time limits
+        let r = record("kind=1");
+        assert_eq!(r.valid_from(), None);
+        assert_eq!(r.valid_until(), None);
+        assert_eq!(r.status(1677000000), DelegationStatus::Active);
+
+        // order does not matter, the strictest limit applies
+        let r = record("created_at<1678659553&created_at>1676067553&created_at<1677000000");
+        assert_eq!(r.valid_until(), Some(1677000000));
+        assert_eq!(r.status(1677000000), DelegationStatus::Expired);
+    }
+
+    #[test]
+    fn test_add_load_export() {
+        let storage = MemoryStorage::new_shared();
+        let mut ledger = DelegationLedger::new(storage.clone());
+        ledger.load().unwrap();
+        assert!(ledger.records().is_empty());
+
+        ledger.add(record("kind=1&created_at<1678659553")).unwrap();
+        let mut r = record("created_at>1676067553");
+        r.source = DelegationSource::SignerRequest(NPUB2.to_string());
+        ledger.add(r).unwrap();
+
+        let mut loaded = DelegationLedger::new(storage);
+        loaded.load().unwrap();
+        assert_eq!(loaded.records().len(), 2);
+        assert_eq!(
+            loaded.records()[0].conditions,
+            "kind=1&created_at<1678659553"
+        );
+        assert_eq!(
+            loaded.records()[1].source,
+            DelegationSource::SignerRequest(NPUB2.to_string())
+        );
+
+        let mut status = StatusMessages::new();
+        loaded.export_action(&mut status);
+        assert!(status.get_last().starts_with("Error: "));
+
+        let path = std::env::temp_dir().join(format!(
+            "keystr-test-delegations-{}.json",
+            std::process::id()
+        ));
+        assert_eq!(loaded.export(&path).unwrap(), 2);
+        let exported: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(exported[0]["delegatee"], NPUB2);
+        assert_eq!(exported[0]["valid_until"], 1678659553);
+        assert_eq!(exported[0]["status"], "expired");
+        assert_eq!(exported[1]["valid_until"], serde_json::Value::Null);
+        assert_eq!(exported[1]["status"], "active");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_add_after_failed_load() {
+        let storage = MemoryStorage::new_shared();
+        let path = Storage::delegations_file();
+        storage.write(&path, b"[{\"delegator\":", false).unwrap();
+
+        let mut ledger = DelegationLedger::new(storage.clone());
+        assert!(ledger.load().is_err());
+        assert!(ledger.records().is_empty());
+        // the unreadable file is kept, not overwritten
+        assert!(matches!(
+            ledger.add(record("kind=1")),
+            Err(Error::DelegationLedgerNotLoaded)
+        ));
+        let mut status = StatusMessages::new();
+        ledger.add_action(record("kind=1"), &mut status);
+        assert!(status.get_last().starts_with("Error: "));
+        assert_eq!(storage.read_to_string(&path).unwrap(), "[{\"delegator\":");
+
+        // once the file is moved aside, the ledger is usable again
+        storage
+            .rename(&path, &path.with_extension("json.bad"))
+            .unwrap();
+        ledger.load().unwrap();
+        ledger.add(record("kind=1")).unwrap();
+        assert_eq!(ledger.records().len(), 1);
+    }
+}
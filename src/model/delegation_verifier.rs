@@ -0,0 +1,378 @@
+use crate::base::error::Error;
+use crate::base::paper_backup::PaperBackup;
+use crate::base::time_input::TimeInput;
+use crate::model::delegation_ledger::{DelegationLedger, DelegationStatus};
+use crate::model::delegator::Delegator;
+use crate::model::status_messages::StatusMessages;
+
+use nostr::prelude::{
+    verify_delegation_signature, DelegationTag, FromBech32, ToBech32, XOnlyPublicKey,
+};
+use nostr::Event;
+
+use std::str::FromStr;
+
+/// Result of the verification of a delegation
+pub(crate) struct DelegationVerification {
+    /// Delegator npub
+    pub delegator: String,
+    /// Delegatee npub
+    pub delegatee: String,
+    /// Conditions string
+    pub conditions: String,
+    /// Conditions in plain language, one line each
+    pub conditions_description: Vec<String>,
+    /// The delegation signature is valid (by the delegator, for this delegatee and conditions)
+    pub signature_valid: bool,
+    /// The signature of the given event is valid; None if no event was given
+    pub event_signature_valid: Option<bool>,
+    /// Whether the given kind/time is allowed, in plain language; None if none was given
+    pub check: Option<String>,
+}
+
+/// Model for verifying a delegation received from someone else
+pub(crate) struct DelegationVerifier {
+    /// Input for the delegation tag, JSON, e.g. '["delegation","<hex>","kind=1","<sig>"]'
+    pub tag_input: String,
+    /// Input for the delegatee npub (or hex), not needed if an event is given
+    pub delegatee_input: String,
+    /// Input for a signed event (JSON), optional, by the delegatee
+    pub event_input: String,
+    /// Input for an event kind to check, optional (default from the event)
+    pub kind_input: String,
+    /// Input for a time (date-time or unix timestamp) to check, optional (default from the event)
+    pub time_input: String,
+    /// Result of the last verification
+    result: Option<DelegationVerification>,
+}
+
+impl DelegationVerifier {
+    pub fn new() -> Self {
+        Self {
+            tag_input: String::new(),
+            delegatee_input: String::new(),
+            event_input: String::new(),
+            kind_input: String::new(),
+            time_input: String::new(),
+            result: None,
+        }
+    }
+
+    pub fn get_result(&self) -> &Option<DelegationVerification> {
+        &self.result
+    }
+
+    /// Verify the delegation from the inputs
+    pub fn verify(&self) -> Result<DelegationVerification, Error> {
+        let event = match self.event_input.trim() {
+            "" => None,
+            json => Some(Event::from_json(json).map_err(|_e| Error::DelegationInvalidEvent)?),
+        };
+        let tag_str = match (self.tag_input.trim(), &event) {
+            ("", Some(event)) => Self::delegation_tag_of_event(event)?,
+            ("", None) => return Err(Error::DelegationTagMissing),
+            (tag, _) => tag.to_string(),
+        };
+        let tag = DelegationTag::from_str(&tag_str)?;
+        // the delegatee is the author of the event
+        let delegatee = match (&event, self.delegatee_input.trim()) {
+            (Some(event), _) => event.pubkey,
+            (None, "") => return Err(Error::DelegationDelegateeMissing),
+            (None, key) => match XOnlyPublicKey::from_bech32(key) {
+                Ok(key) => key,
+                Err(_) => XOnlyPublicKey::from_str(key)?,
+            },
+        };
+        let conditions = tag.conditions();
+        let conditions_str = conditions.to_string();
+        let signature_valid = verify_delegation_signature(
+            tag.delegator_pubkey(),
+            tag.signature(),
+            delegatee,
+            conditions,
+        )
+        .is_ok();
+
+        let kind = match (self.kind_input.trim(), &event) {
+            ("", Some(event)) => Some(event.kind.as_u64()),
+            ("", None) => None,
+            (kind, _) => Some(Delegator::parse_kind(kind)?),
+        };
+        let time = match (self.time_input.trim(), &event) {
+            ("", Some(event)) => Some(event.created_at.as_u64()),
+            ("", None) => None,
+            (time, _) => Some(TimeInput::parse_time(time)?),
+        };
+        let check = if kind.is_none() && time.is_none() {
+            None
+        } else {
+            Some(Self::describe_check(
+                &conditions_str,
+                signature_valid,
+                kind,
+                time,
+            ))
+        };
+
+        Ok(DelegationVerification {
+            delegator: tag.delegator_pubkey().to_bech32()?,
+            delegatee: delegatee.to_bech32()?,
+            conditions_description: Self::describe_conditions(&conditions_str),
+            conditions: conditions_str,
+            signature_valid,
+            event_signature_valid: event.map(|e| e.verify().is_ok()),
+            check,
+        })
+    }
+
+    /// The delegation tag of an event, as JSON
+    fn delegation_tag_of_event(event: &Event) -> Result<String, Error> {
+        let tag = event
+            .tags
+            .iter()
+            .map(|t| t.as_vec())
+            .find(|t| t.first().map(|name| name.as_str()) == Some("delegation"))
+            .ok_or(Error::DelegationTagMissing)?;
+        Ok(serde_json::to_string(&tag)?)
+    }
+
+    fn kinds(conditions: &str) -> Vec<u64> {
+        conditions
+            .split('&')
+            .filter_map(|c| c.strip_prefix("kind="))
+            .filter_map(|kind| kind.parse::<u64>().ok())
+            .collect()
+    }
+
+    fn describe_time(time: u64) -> String {
+        format!("{} ({})", PaperBackup::format_date(time), time)
+    }
+
+    /// The conditions in plain language
+    fn describe_conditions(conditions: &str) -> Vec<String> {
+        let kinds = Self::kinds(conditions);
+        let mut lines = vec![if kinds.is_empty() {
+            "Event kinds: any".to_string()
+        } else {
+            format!(
+                "Event kinds: {}{}",
+                kinds
+                    .iter()
+                    .map(|kind| Delegator::kind_description(*kind))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                if kinds.iter().any(|kind| *kind != kinds[0]) {
+                    " (all have to match, so no event is allowed)"
+                } else {
+                    ""
+                }
+            )
+        }];
+        lines.push(match DelegationStatus::valid_from(conditions) {
+            None => "Valid from: no limit".to_string(),
+            Some(from) => format!("Valid after: {}", Self::describe_time(from)),
+        });
+        lines.push(match DelegationStatus::valid_until(conditions) {
+            None => "Valid until: no limit".to_string(),
+            Some(until) => format!("Valid before: {}", Self::describe_time(until)),
+        });
+        lines.push(format!(
+            "Now: {}",
+            DelegationStatus::of_conditions(conditions, DelegationLedger::current_time())
+        ));
+        lines
+    }
+
+    /// Whether an event of the given kind and/or time is allowed by the delegation, in plain language.
+    /// All clauses have to match (as NIP-26 and the library evaluate them), so with several
+    /// different kinds no event is allowed.
+    fn describe_check(
+        conditions: &str,
+        signature_valid: bool,
+        kind: Option<u64>,
+        time: Option<u64>,
+    ) -> String {
+        let mut problems = Vec::new();
+        if !signature_valid {
+            problems.push("the delegation signature is invalid".to_string());
+        }
+        if let Some(kind) = kind {
+            if Self::kinds(conditions).iter().any(|k| *k != kind) {
+                problems.push("the kind is not delegated".to_string());
+            }
+        }
+        if let Some(time) = time {
+            match DelegationStatus::of_conditions(conditions, time) {
+                DelegationStatus::Active => {}
+                DelegationStatus::NotYetValid => {
+                    problems.push("the time is before the start of validity".to_string())
+                }
+                DelegationStatus::Expired => {
+                    problems.push("the time is after the end of validity".to_string())
+                }
+            }
+        }
+        let mut checked = Vec::new();
+        if let Some(kind) = kind {
+            checked.push(format!("kind {}", Delegator::kind_description(kind)));
+        }
+        if let Some(time) = time {
+            checked.push(format!("time {}", Self::describe_time(time)));
+        }
+        if problems.is_empty() {
+            format!("Event with {}: allowed", checked.join(", "))
+        } else {
+            format!(
+                "Event with {}: NOT allowed, {}",
+                checked.join(", "),
+                problems.join(", ")
+            )
+        }
+    }
+
+    /// Action to verify the delegation from the inputs
+    pub fn verify_action(&mut self, status: &mut StatusMessages) {
+        match self.verify() {
+            Err(e) => {
+                self.result = None;
+                status.set_error(&format!("Could not verify delegation ({})", e));
+            }
+            Ok(result) => {
+                if result.signature_valid {
+                    status.set("Delegation signature is valid");
+                } else {
+                    status.set_error("Delegation signature is INVALID");
+                }
+                self.result = Some(result);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nostr::prelude::{Conditions, EventBuilder, EventProperties, Keys, Kind, SecretKey, Tag};
+
+    const TAG: &str = "[\"delegation\",\"1a459a8a6aa6441d480ba665fb8fb21a4cfe8bcacb7d87300f8046a558a3fce4\",\"kind=1&created_at>1676067553&created_at<1678659553\",\"369aed09c1ad52fceb77ecd6c16f2433eac4a3803fc41c58876a5b60f4f36b9493d5115e5ec5a0ce6c3668ffe5b58d47f2cbc97233833bb7e908f66dbbbd9d36\"]";
+    const DELEGATOR_NPUB: &str = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+    const DELEGATEE_NPUB: &str = "npub1h652adkpv4lr8k66cadg8yg0wl5wcc29z4lyw66m3rrwskcl4v6qr82xez";
+
+    #[test]
+    fn test_verify_tag() {
+        let mut v = DelegationVerifier::new();
+        assert!(v.verify().is_err());
+        v.tag_input = TAG.to_string();
+        // delegatee missing
+        assert!(v.verify().is_err());
+        v.delegatee_input = DELEGATEE_NPUB.to_string();
+
+        let res = v.verify().unwrap();
+        assert!(res.signature_valid);
+        assert_eq!(res.delegator, DELEGATOR_NPUB);
+        assert_eq!(res.delegatee, DELEGATEE_NPUB);
+        assert_eq!(
+            res.conditions,
+            "kind=1&created_at>1676067553&created_at<1678659553"
+        );
+        assert_eq!(res.conditions_description[0], "Event kinds: Text note (1)");
+        assert_eq!(
+            res.conditions_description[1],
+            "Valid after: 2023-02-10 22:19 UTC (1676067553)"
+        );
+        assert_eq!(res.conditions_description[3], "Now: expired");
+        assert!(res.event_signature_valid.is_none());
+        assert!(res.check.is_none());
+
+        v.kind_input = "1".to_string();
+        v.time_input = "1677000000".to_string();
+        assert!(v.verify().unwrap().check.unwrap().ends_with(": allowed"));
+        v.kind_input = "7".to_string();
+        v.time_input = "1678659553".to_string();
+        assert_eq!(
+            v.verify().unwrap().check.unwrap(),
+            "Event with kind Reaction (7), time 2023-03-12 22:19 UTC (1678659553): NOT allowed, the kind is not delegated, the time is after the end of validity"
+        );
+        v.time_input = "yesterday".to_string();
+        assert!(v.verify().is_err());
+
+        // for another delegatee the signature does not match
+        v.time_input = String::new();
+        v.delegatee_input = DELEGATOR_NPUB.to_string();
+        assert!(!v.verify().unwrap().signature_valid);
+    }
+
+    #[test]
+    fn test_verify_event() {
+        let delegator = Keys::new(
+            SecretKey::from_bech32(
+                "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+            )
+            .unwrap(),
+        );
+        let delegatee = Keys::generate();
+        let reaction_event = |conditions: &str| {
+            let dtag = DelegationTag::new(
+                &delegator,
+                delegatee.public_key(),
+                Conditions::from_str(conditions).unwrap(),
+            )
+            .unwrap();
+            let tag = Tag::parse(vec![
+                "delegation".to_string(),
+                dtag.delegator_pubkey().to_string(),
+                dtag.conditions().to_string(),
+                dtag.signature().to_string(),
+            ])
+            .unwrap();
+            let event = EventBuilder::new(Kind::Reaction, "+", &[tag])
+                .to_event(&delegatee)
+                .unwrap();
+            (dtag, event)
+        };
+
+        let (_dtag, event) = reaction_event("kind=7");
+        let mut v = DelegationVerifier::new();
+        v.event_input = event.as_json();
+        let res = v.verify().unwrap();
+        assert!(res.signature_valid);
+        assert_eq!(res.event_signature_valid, Some(true));
+        assert_eq!(res.delegator, DELEGATOR_NPUB);
+        assert_eq!(res.delegatee, delegatee.public_key().to_bech32().unwrap());
+        assert_eq!(res.conditions_description[0], "Event kinds: Reaction (7)");
+        assert!(res.check.unwrap().ends_with(": allowed"));
+
+        // the kind to check can be overridden
+        v.kind_input = "6".to_string();
+        assert!(v.verify().unwrap().check.unwrap().contains("NOT allowed"));
+
+        // several kinds: all clauses have to match, as in the library, so nothing is allowed
+        let (dtag, event) = reaction_event("kind=1&kind=7");
+        assert!(dtag
+            .validate(
+                delegatee.public_key(),
+                &EventProperties::new(7, event.created_at.as_u64())
+            )
+            .is_err());
+        v.event_input = event.as_json();
+        v.kind_input = String::new();
+        let res = v.verify().unwrap();
+        assert_eq!(
+            res.conditions_description[0],
+            "Event kinds: Text note (1), Reaction (7) (all have to match, so no event is allowed)"
+        );
+        assert!(res
+            .check
+            .unwrap()
+            .contains("NOT allowed, the kind is not delegated"));
+
+        // an event without delegation tag
+        v.event_input = EventBuilder::new_text_note("hi", &[])
+            .to_event(&delegatee)
+            .unwrap()
+            .as_json();
+        assert!(v.verify().is_err());
+        v.event_input = "{}".to_string();
+        assert!(v.verify().is_err());
+    }
+}
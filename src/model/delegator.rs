@@ -1,4 +1,7 @@
 use crate::base::error::Error;
+use crate::base::time_input::TimeInput;
+use crate::model::delegation_ledger::{DelegationRecord, DelegationSource};
+use crate::model::keystore::KeySigner;
 
 use nostr::prelude::{
     Conditions, DelegationTag, DelegationToken, FromBech32, Keys, ToBech32, XOnlyPublicKey,
@@ -7,18 +10,42 @@ use nostr::prelude::{
 use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Common event kinds, with their names, offered for selection
+pub(crate) const EVENT_KINDS: [(u64, &str); 10] = [
+    (0, "Metadata"),
+    (1, "Text note"),
+    (3, "Contacts"),
+    (4, "Encrypted DM"),
+    (5, "Deletion"),
+    (6, "Repost"),
+    (7, "Reaction"),
+    (42, "Channel message"),
+    (9735, "Zap"),
+    (30023, "Long-form article"),
+];
+
+/// Largest valid event kind
+const MAX_KIND: u64 = 65535;
+
 /// Model for Delegator
 pub(crate) struct Delegator {
     // Input for delegatee
     pub delegatee_npub_input: String,
-    // Kind condition
-    pub kind_condition_input: String,
-    // Validity start time, can be empty
+    // Selected event kind (none for any kind). Only one: NIP-26 condition clauses all have to
+    // match, so an event can never match several kind clauses.
+    pub kind: Option<u64>,
+    // Input for a custom (numeric) event kind
+    pub custom_kind_input: String,
+    // Input for validity start time (date-time or unix timestamp), can be empty
+    pub time_start_input: String,
+    // Input for validity end time (date-time or unix timestamp), can be empty
+    pub time_end_input: String,
+    // Validity start time (unix timestamp), can be empty
     pub time_cond_start: String,
-    // Validity end time, can be empty
+    // Validity end time (unix timestamp), can be empty
     pub time_cond_end: String,
-    // Validity in days
-    pub time_cond_days: String,
+    // Validity duration from now, e.g. '90d' or '6 weeks'
+    pub time_cond_duration: String,
     // Compiled conditions string
     pub conditions: String,
     // Compiled delegation string
@@ -33,10 +60,13 @@ impl Delegator {
     pub fn new() -> Self {
         let mut d = Delegator {
             delegatee_npub_input: String::new(),
-            kind_condition_input: String::new(),
+            kind: None,
+            custom_kind_input: String::new(),
+            time_start_input: String::new(),
+            time_end_input: String::new(),
             time_cond_start: String::new(),
             time_cond_end: String::new(),
-            time_cond_days: "90".to_string(),
+            time_cond_duration: "90d".to_string(),
             conditions: String::new(),
             delegation_string: String::new(),
             signature: String::new(),
@@ -46,10 +76,11 @@ impl Delegator {
         d
     }
 
+    /// Compile the conditions from the inputs, and the delegation string (if the delegatee is valid)
     pub fn validate_and_update(&mut self) -> Result<(), Error> {
         let mut cond = Vec::new();
-        if self.kind_condition_input.len() > 0 {
-            cond.push(self.kind_condition_input.clone());
+        if let Some(kind) = self.kind {
+            cond.push(format!("kind={}", kind));
         }
         if self.time_cond_start.len() > 0 {
             cond.push(format!("created_at>{}", self.time_cond_start));
@@ -58,6 +89,9 @@ impl Delegator {
             cond.push(format!("created_at<{}", self.time_cond_end));
         }
         self.conditions = cond.join("&");
+        self.delegation_string = String::new();
+        Self::check_conditions(&self.conditions)?;
+        Self::check_time_range(&self.time_cond_start, &self.time_cond_end)?;
 
         let delegatee_key = XOnlyPublicKey::from_bech32(self.delegatee_npub_input.clone())?;
 
@@ -66,6 +100,84 @@ impl Delegator {
         Ok(())
     }
 
+    /// Check a conditions string against the NIP-26 grammar: clauses 'kind=<n>',
+    /// 'created_at<<n>' or 'created_at><n>' joined by '&' (empty for no conditions)
+    pub fn check_conditions(conditions: &str) -> Result<(), Error> {
+        if conditions.is_empty() {
+            return Ok(());
+        }
+        for clause in conditions.split('&') {
+            if let Some(kind) = clause.strip_prefix("kind=") {
+                Self::parse_kind(kind).map_err(|_e| Error::DelegationInvalidConditions)?;
+            } else {
+                let time = clause
+                    .strip_prefix("created_at<")
+                    .or_else(|| clause.strip_prefix("created_at>"))
+                    .ok_or(Error::DelegationInvalidConditions)?;
+                if !time.chars().all(|c| c.is_ascii_digit()) || time.parse::<u64>().is_err() {
+                    return Err(Error::DelegationInvalidConditions);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the end of validity is after the start, if both are set
+    fn check_time_range(time_cond_start: &str, time_cond_end: &str) -> Result<(), Error> {
+        if let (Ok(start), Ok(end)) = (time_cond_start.parse::<u64>(), time_cond_end.parse::<u64>())
+        {
+            if end <= start {
+                return Err(Error::DelegationEndBeforeStart);
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse an event kind, a number up to 65535
+    pub fn parse_kind(s: &str) -> Result<u64, Error> {
+        if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+            return Err(Error::DelegationInvalidKind);
+        }
+        match s.parse::<u64>() {
+            Ok(kind) if kind <= MAX_KIND => Ok(kind),
+            _ => Err(Error::DelegationInvalidKind),
+        }
+    }
+
+    /// Name of a common event kind, if known
+    pub fn kind_name(kind: u64) -> Option<&'static str> {
+        EVENT_KINDS
+            .iter()
+            .find(|(k, _name)| *k == kind)
+            .map(|(_k, name)| *name)
+    }
+
+    /// Description of an event kind, e.g. 'Reaction (7)', or just the number if not known
+    pub fn kind_description(kind: u64) -> String {
+        match Self::kind_name(kind) {
+            Some(name) => format!("{} ({})", name, kind),
+            None => kind.to_string(),
+        }
+    }
+
+    /// Select an event kind (replacing the selected one), or unselect it (allowing any kind)
+    pub fn set_kind(&mut self, kind: u64, selected: bool) -> Result<(), Error> {
+        if selected {
+            self.kind = Some(kind);
+        } else if self.kind == Some(kind) {
+            self.kind = None;
+        }
+        self.validate_and_update()
+    }
+
+    /// Select the custom kind from the input (a number)
+    pub fn set_custom_kind(&mut self) -> Result<(), Error> {
+        let kind = Self::parse_kind(self.custom_kind_input.trim())?;
+        self.kind = Some(kind);
+        self.custom_kind_input = String::new();
+        self.validate_and_update()
+    }
+
     fn current_time() -> u64 {
         SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -73,29 +185,57 @@ impl Delegator {
             .as_secs()
     }
 
-    pub fn time_set_start(&mut self, start: &str) {
-        if let Ok(n) = start.parse::<u64>() {
-            self.time_cond_start = n.to_string();
-            self.time_cond_days = "".to_string();
-            let _r = self.validate_and_update();
+    /// Parse a validity time limit input into a unix timestamp string, empty for no limit
+    fn parse_time_limit(input: &str) -> Result<String, Error> {
+        if input.trim().is_empty() {
+            Ok(String::new())
+        } else {
+            Ok(TimeInput::parse_time(input)?.to_string())
         }
     }
 
-    pub fn time_set_end(&mut self, end: &str) {
-        if let Ok(n) = end.parse::<u64>() {
-            self.time_cond_end = n.to_string();
-            self.time_cond_days = "".to_string();
-            let _r = self.validate_and_update();
-        }
+    /// Set the validity start: a date-time (ISO-8601 or local) or a unix timestamp, empty for none.
+    /// A start not before the end is rejected, the previous start is kept.
+    pub fn time_set_start(&mut self, start: &str) -> Result<(), Error> {
+        let time_cond_start = Self::parse_time_limit(start)?;
+        Self::check_time_range(&time_cond_start, &self.time_cond_end)?;
+        self.time_cond_start = time_cond_start;
+        self.time_start_input = start.trim().to_string();
+        self.time_cond_duration = "".to_string();
+        self.validate_and_update()
     }
 
-    pub fn time_set_days(&mut self, days: &str) {
-        if let Ok(n) = days.parse::<u64>() {
-            self.time_cond_days = days.to_string();
-            let now = Self::current_time();
-            self.time_cond_start = now.to_string();
-            self.time_cond_end = (now + n * 24 * 3600).to_string();
-            let _r = self.validate_and_update();
+    /// Set the validity end: a date-time (ISO-8601 or local) or a unix timestamp, empty for none.
+    /// An end not after the start is rejected, the previous end is kept.
+    pub fn time_set_end(&mut self, end: &str) -> Result<(), Error> {
+        let time_cond_end = Self::parse_time_limit(end)?;
+        Self::check_time_range(&self.time_cond_start, &time_cond_end)?;
+        self.time_cond_end = time_cond_end;
+        self.time_end_input = end.trim().to_string();
+        self.time_cond_duration = "".to_string();
+        self.validate_and_update()
+    }
+
+    /// Set the validity from now, for a duration like '2h', '6 weeks' or '90' (days)
+    pub fn time_set_duration(&mut self, duration: &str) -> Result<(), Error> {
+        let seconds = TimeInput::parse_duration(duration)?;
+        let now = Self::current_time();
+        let end = now
+            .checked_add(seconds)
+            .ok_or(Error::DelegationInvalidDuration)?;
+        self.time_cond_duration = duration.trim().to_string();
+        self.time_cond_start = now.to_string();
+        self.time_cond_end = end.to_string();
+        self.time_start_input = self.time_cond_start.clone();
+        self.time_end_input = self.time_cond_end.clone();
+        self.validate_and_update()
+    }
+
+    /// A validity time limit in local time, empty if not set
+    pub fn time_limit_local(time_cond: &str) -> String {
+        match time_cond.parse::<u64>() {
+            Ok(time) => TimeInput::format_local(time),
+            Err(_) => String::new(),
         }
     }
 
@@ -107,25 +247,32 @@ impl Delegator {
 
     /// Create delegation tag (incl. signature). Delegatee pubkey and conditions are taken from self.
     /// Result signature and also updated delegation tag are places in self.
-    pub fn create_delegation(&mut self, keys: &Keys) -> Result<(), Error> {
+    /// Returns the delegation, for the ledger.
+    pub fn create_delegation(&mut self, key_signer: &KeySigner) -> Result<DelegationRecord, Error> {
         self.validate_and_update()?;
         let delegatee_key = XOnlyPublicKey::from_bech32(self.delegatee_npub_input.clone())?;
 
-        let tag = DelegationTag::new(
-            &keys,
-            delegatee_key,
-            Conditions::from_str(&self.conditions.clone())?,
-        )?;
+        let conditions = Conditions::from_str(&self.conditions.clone())?;
+        let tag =
+            key_signer.with_keys(|keys| DelegationTag::new(keys, delegatee_key, conditions))??;
         self.delegation_tag = tag.to_string();
         self.signature = tag.signature().to_string();
-        Ok(())
+        DelegationRecord::new(
+            &key_signer.get_public_key(),
+            &delegatee_key,
+            &self.conditions,
+            &tag,
+            DelegationSource::Delegator,
+        )
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::base::secret::SecretKeyHolder;
     use nostr::prelude::{DelegationTag, EventProperties, SecretKey};
+    use std::sync::Arc;
 
     #[test]
     fn test_create_delegation() {
@@ -133,16 +280,24 @@ mod test {
             "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
         )
         .unwrap();
-        let keys = Keys::new(sk);
+        let key_signer = KeySigner::new(Arc::new(SecretKeyHolder::new(&sk)));
 
         let delegatee_npub_str = "npub1h652adkpv4lr8k66cadg8yg0wl5wcc29z4lyw66m3rrwskcl4v6qr82xez";
         let mut d = Delegator::new();
         d.delegatee_npub_input = delegatee_npub_str.to_string();
-        d.kind_condition_input = "kind=1".to_string();
+        d.set_kind(1, true).unwrap();
         d.time_cond_start = 1676067553.to_string();
         d.time_cond_end = 1678659553.to_string();
 
-        let _res = d.create_delegation(&keys).unwrap();
+        let record = d.create_delegation(&key_signer).unwrap();
+        assert_eq!(
+            record.delegator,
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+        assert_eq!(record.delegatee, delegatee_npub_str);
+        assert_eq!(record.conditions, d.conditions);
+        assert_eq!(record.tag, d.delegation_tag);
+        assert_eq!(record.valid_until(), Some(1678659553));
 
         // // verify signature (it's variable)
         // let verify_result = verify_delegation_signature(
@@ -201,13 +356,129 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_kinds() {
+        let mut d = Delegator::new();
+        // no delegatee yet, the kind is selected nevertheless
+        assert!(d.set_kind(7, true).is_err());
+        assert_eq!(d.conditions, "kind=7");
+        d.delegatee_npub_input =
+            "npub1h652adkpv4lr8k66cadg8yg0wl5wcc29z4lyw66m3rrwskcl4v6qr82xez".to_string();
+        // selecting another kind replaces it
+        d.set_kind(1, true).unwrap();
+        assert_eq!(d.conditions, "kind=1");
+        d.custom_kind_input = " 30078 ".to_string();
+        d.set_custom_kind().unwrap();
+        assert_eq!(d.custom_kind_input, "");
+        assert_eq!(d.conditions, "kind=30078");
+        assert_eq!(d.delegation_string, "nostr:delegation:bea8aeb6c1657e33db5ac75a83910f77e8ec6145157e476b5b88c6e85b1fab34:kind=30078");
+
+        // unselecting another kind keeps it, unselecting it allows any kind
+        d.set_kind(7, false).unwrap();
+        d.time_set_end("1678659553").unwrap();
+        assert_eq!(d.conditions, "kind=30078&created_at<1678659553");
+        d.set_kind(30078, false).unwrap();
+        assert_eq!(d.conditions, "created_at<1678659553");
+        d.set_kind(30078, true).unwrap();
+
+        // invalid custom kinds are not selected
+        for input in ["", "x", "-1", "65536", "1,7", "1 7"] {
+            d.custom_kind_input = input.to_string();
+            assert!(d.set_custom_kind().is_err());
+            assert_eq!(d.kind, Some(30078));
+        }
+
+        assert_eq!(Delegator::kind_description(7), "Reaction (7)");
+        assert_eq!(Delegator::kind_description(30078), "30078");
+    }
+
+    #[test]
+    fn test_kind_validated_by_lib() {
+        let sk = SecretKey::from_bech32(
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+        )
+        .unwrap();
+        let key_signer = KeySigner::new(Arc::new(SecretKeyHolder::new(&sk)));
+        let delegatee_npub_str = "npub1h652adkpv4lr8k66cadg8yg0wl5wcc29z4lyw66m3rrwskcl4v6qr82xez";
+        let delegatee_key = XOnlyPublicKey::from_bech32(delegatee_npub_str).unwrap();
+
+        // an event of the selected kind is accepted by the library, of other kinds not
+        for (kind, _name) in EVENT_KINDS {
+            let mut d = Delegator::new();
+            d.delegatee_npub_input = delegatee_npub_str.to_string();
+            d.set_kind(kind, true).unwrap();
+            d.create_delegation(&key_signer).unwrap();
+            let dtag = DelegationTag::from_str(&d.delegation_tag).unwrap();
+            assert!(dtag
+                .validate(delegatee_key, &EventProperties::new(kind, 1676500000))
+                .is_ok());
+            assert!(dtag
+                .validate(delegatee_key, &EventProperties::new(kind + 1, 1676500000))
+                .is_err());
+        }
+
+        // the library requires all clauses to match: with several kinds, no event matches
+        let dtag = key_signer
+            .with_keys(|keys| {
+                DelegationTag::new(
+                    keys,
+                    delegatee_key,
+                    Conditions::from_str("kind=1&kind=7").unwrap(),
+                )
+            })
+            .unwrap()
+            .unwrap();
+        for kind in [1, 7] {
+            assert!(dtag
+                .validate(delegatee_key, &EventProperties::new(kind, 1676500000))
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn test_check_conditions() {
+        for c in [
+            "",
+            "kind=1",
+            "kind=1&kind=7&created_at>1676067553&created_at<1678659553",
+            "created_at<1686078180&created_at>1680807780",
+        ] {
+            assert!(Delegator::check_conditions(c).is_ok(), "{}", c);
+        }
+        for c in [
+            "kind=",
+            "kind=note",
+            "kind=70000",
+            "kind=1&",
+            "kind = 1",
+            "kind>1",
+            "created_at=1676067553",
+            "created_at<-5",
+            "created_at>",
+            "kind=1&&created_at>1676067553",
+        ] {
+            assert!(Delegator::check_conditions(c).is_err(), "{}", c);
+        }
+    }
+
     #[test]
     fn test_time_set_start() {
         let mut d = Delegator::new();
         d.delegatee_npub_input =
             "npub1h652adkpv4lr8k66cadg8yg0wl5wcc29z4lyw66m3rrwskcl4v6qr82xez".to_string();
-        d.time_set_start("1676067553");
+        d.time_set_start("1676067553").unwrap();
         assert_eq!(d.delegation_string, "nostr:delegation:bea8aeb6c1657e33db5ac75a83910f77e8ec6145157e476b5b88c6e85b1fab34:created_at>1676067553");
+        d.time_set_start("2023-02-10T22:19:13Z").unwrap();
+        assert_eq!(d.time_cond_start, "1676067553");
+        assert_eq!(d.time_start_input, "2023-02-10T22:19:13Z");
+
+        // invalid input keeps the current start
+        assert!(d.time_set_start("next monday").is_err());
+        assert_eq!(d.time_cond_start, "1676067553");
+
+        // empty for no limit
+        d.time_set_start("").unwrap();
+        assert_eq!(d.conditions, "");
     }
 
     #[test]
@@ -215,19 +486,61 @@ mod test {
         let mut d = Delegator::new();
         d.delegatee_npub_input =
             "npub1h652adkpv4lr8k66cadg8yg0wl5wcc29z4lyw66m3rrwskcl4v6qr82xez".to_string();
-        d.time_set_end("1678659553");
+        d.time_set_end("1678659553").unwrap();
         assert_eq!(d.delegation_string, "nostr:delegation:bea8aeb6c1657e33db5ac75a83910f77e8ec6145157e476b5b88c6e85b1fab34:created_at<1678659553");
+        assert_eq!(
+            Delegator::time_limit_local(&d.time_cond_end),
+            TimeInput::format_local(1678659553)
+        );
     }
 
     #[test]
-    fn test_time_set_days() {
+    fn test_end_before_start() {
         let mut d = Delegator::new();
         d.delegatee_npub_input =
             "npub1h652adkpv4lr8k66cadg8yg0wl5wcc29z4lyw66m3rrwskcl4v6qr82xez".to_string();
-        d.time_set_days("11");
+        d.time_set_end("2023-03-12T22:19:13Z").unwrap();
+        d.time_set_start("2023-02-10T22:19:13Z").unwrap();
+        assert_eq!(d.conditions, "created_at>1676067553&created_at<1678659553");
+        let delegation_string = d.delegation_string.clone();
+
+        // rejected: the previous start (and the conditions built from it) are kept
+        for start in ["2023-03-12T22:19:13Z", "2023-04-01T00:00:00Z"] {
+            assert!(matches!(
+                d.time_set_start(start),
+                Err(Error::DelegationEndBeforeStart)
+            ));
+            assert_eq!(d.time_cond_start, "1676067553");
+            assert_eq!(d.time_start_input, "2023-02-10T22:19:13Z");
+            assert_eq!(d.conditions, "created_at>1676067553&created_at<1678659553");
+            assert_eq!(d.delegation_string, delegation_string);
+        }
+        // same for the end
+        assert!(matches!(
+            d.time_set_end("2023-01-01T00:00:00Z"),
+            Err(Error::DelegationEndBeforeStart)
+        ));
+        assert_eq!(d.time_cond_end, "1678659553");
+        assert_eq!(d.conditions, "created_at>1676067553&created_at<1678659553");
+    }
+
+    #[test]
+    fn test_time_set_duration() {
+        let mut d = Delegator::new();
+        d.delegatee_npub_input =
+            "npub1h652adkpv4lr8k66cadg8yg0wl5wcc29z4lyw66m3rrwskcl4v6qr82xez".to_string();
+        d.time_set_duration("11").unwrap();
         assert_eq!(
             d.time_cond_end.parse::<i64>().unwrap() - d.time_cond_start.parse::<i64>().unwrap(),
             11 * 24 * 60 * 60
         );
+        d.time_set_duration("6 weeks").unwrap();
+        assert_eq!(
+            d.time_cond_end.parse::<i64>().unwrap() - d.time_cond_start.parse::<i64>().unwrap(),
+            6 * 7 * 24 * 60 * 60
+        );
+        assert_eq!(d.time_end_input, d.time_cond_end);
+        assert!(d.time_set_duration("soon").is_err());
+        assert_eq!(d.time_cond_duration, "6 weeks");
     }
 }
@@ -1,323 +1,2166 @@
-use crate::base::encrypt::Encrypt;
+use crate::base::encrypt::{Encrypt, KdfParams, KeySecurity};
 use crate::base::error::Error;
+use crate::base::frost::{Frost, FrostKeyShare, MAX_PARTICIPANTS};
+use crate::base::key_file::{KeyFile, KeyFileFormat, KeyFileKey};
+use crate::base::nip06::{Nip06, MAX_ACCOUNT};
+use crate::base::paper_backup::{PaperBackup, PaperBackupSecret};
+use crate::base::secret::{SecretKeyHolder, SecretString};
+use crate::base::shamir::{Shamir, PUBKEY_PREFIX_LEN};
 use crate::base::storage::Storage;
+use crate::base::storage_backend::StorageBackend;
+use crate::model::rotation::Rotation;
 use crate::model::security_settings::{SecurityLevel, SecuritySettings};
 use crate::model::status_messages::StatusMessages;
 
-use nostr::prelude::{FromPkStr, FromSkStr, Keys, SecretKey, ToBech32, XOnlyPublicKey, SECP256K1};
+use nostr::prelude::{FromPkStr, Keys, SecretKey, ToBech32, XOnlyPublicKey, SECP256K1};
 use nostr::secp256k1::schnorr::Signature;
+use nostr::secp256k1::Message;
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
 
-use std::fs;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-/// Model for KeyStore part
-#[readonly::make]
-pub(crate) struct Keystore {
-    #[readonly]
+/// Name of the identity present by default, also used when migrating old single-key storage
+const DEFAULT_IDENTITY_NAME: &str = "main";
+/// Number of previous versions kept of the saved key of an identity
+const KEY_FILE_VERSIONS: u8 = 3;
+/// Max length of an identity name (it is also used as folder name)
+const IDENTITY_NAME_MAX_LEN: usize = 32;
+/// Number of accounts shown in the mnemonic account preview
+const MNEMONIC_PREVIEW_ACCOUNTS: u32 = 5;
+/// Max length of a sub-key label
+const SUBKEY_LABEL_MAX_LEN: usize = 32;
+/// Default number of Shamir shares, and needed shares
+const SHAMIR_DEFAULT_COUNT: u8 = 3;
+const SHAMIR_DEFAULT_THRESHOLD: u8 = 2;
+/// Default number of FROST participants, and needed signers
+const FROST_DEFAULT_COUNT: u8 = 3;
+const FROST_DEFAULT_THRESHOLD: u8 = 2;
+
+/// A named identity in the keyring: a key pair, a FROST group key with local key shares,
+/// or only a public key
+pub(crate) struct Identity {
+    name: String,
     has_unsaved_change: bool,
-    keys: Option<Keys>,
+    public_key: Option<XOnlyPublicKey>,
+    /// Secret key, if set (and unlocked); shared with the signer
+    secret_key: Option<Arc<SecretKeyHolder>>,
     encrypted_secret_key: Option<Vec<u8>>,
+    /// How the secret key has been handled so far (saved along the encrypted key)
+    key_security: KeySecurity,
+    /// Mnemonic the secret key was derived from, kept in memory only until saved
+    mnemonic: Option<StoredMnemonic>,
+    /// Encrypted mnemonic, as loaded from or saved to storage
+    encrypted_mnemonic: Option<Vec<u8>>,
+    /// Key derivation (and its cost) of the saved secret key, if saved or loaded
+    stored_kdf: Option<KdfParams>,
+    /// Labelled sub-keys derived from the mnemonic
+    subkeys: Vec<SubKey>,
+    /// FROST key shares held locally, if the key is a FROST group key (and unlocked);
+    /// shared with the signer
+    frost_shares: Option<Arc<Vec<FrostKeyShare>>>,
+    /// Encrypted FROST key shares, as loaded from or saved to storage
+    encrypted_frost_shares: Vec<Vec<u8>>,
+    /// Set if the key has been rotated, the identity is kept as retired
+    retired: Option<Retirement>,
+}
+
+/// A BIP39 mnemonic with the NIP-06 account the secret key was derived from.
+/// Persisted (serialized, then encrypted) alongside the encrypted secret key.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct StoredMnemonic {
+    pub mnemonic: String,
+    pub account: u32,
+    pub derivation_path: String,
+    /// The passphrase itself is not stored, only whether one was used
+    pub has_passphrase: bool,
+}
+
+impl Drop for StoredMnemonic {
+    fn drop(&mut self) {
+        self.mnemonic.zeroize();
+    }
+}
+
+/// A labelled sub-key (e.g. for an application), derived from the mnemonic of the identity,
+/// on its own NIP-06 account index. Only the public key is kept (and persisted), the secret key
+/// is derived again when exported.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SubKey {
+    pub label: String,
+    pub account: u32,
+    pub npub: String,
+}
+
+impl fmt::Display for SubKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (account {})", self.label, self.account)
+    }
+}
+
+/// Retirement of an identity whose key has been rotated
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct Retirement {
+    /// npub of the new key
+    pub successor: String,
+    /// Time of the rotation (unix timestamp)
+    pub retired_at: u64,
+    /// The migration event signed by the old key (JSON), to be published
+    pub migration_event: String,
+}
+
+/// Persisted keyring index: names of identities, the active one, and the retired ones
+#[derive(Default, Serialize, Deserialize)]
+struct KeyringIndex {
+    identities: Vec<String>,
+    active: String,
+    #[serde(default)]
+    retired: BTreeMap<String, Retirement>,
+}
+
+/// Model for KeyStore part, a keyring of named identities, one of them active.
+/// Key operations (generate, import, save, etc.) act on the active identity.
+pub(crate) struct Keystore {
+    /// Where keys are saved to and loaded from
+    storage: Arc<dyn StorageBackend>,
+    /// The identities, there is always at least one
+    identities: Vec<Identity>,
+    /// Index of the active identity
+    active: usize,
     pub hide_secret_key: bool,
+    /// Input for the name of a new identity
+    pub identity_name_input: String,
     /// Input for public key import
     pub public_key_input: String,
     /// Input for secret key import
-    pub secret_key_input: String,
+    pub secret_key_input: SecretString,
     /// Input for BIP39 mnemonic
-    pub mnemonic_input: String,
+    pub mnemonic_input: SecretString,
+    /// Input for optional BIP39 passphrase ("25th word")
+    pub mnemonic_passphrase_input: SecretString,
+    /// Input for NIP-06 account index
+    pub mnemonic_account_input: String,
+    /// Preview of account indexes and npubs derived from the mnemonic input
+    mnemonic_preview: Vec<(u32, String)>,
+    /// Input for NIP-49 encrypted secret key import
+    pub ncryptsec_input: String,
+    /// Input for password for NIP-49 export/import
+    pub ncryptsec_password_input: SecretString,
+    /// Exported NIP-49 encrypted secret key
+    ncryptsec_output: String,
+    /// Input for key file path to import
+    pub import_file_path_input: String,
+    /// Input for password of an encrypted key file
+    pub import_file_password_input: SecretString,
+    /// Input for paper backup file path
+    pub paper_backup_path_input: String,
+    /// Input for number of Shamir shares to create
+    pub shamir_count_input: String,
+    /// Input for number of Shamir shares needed to restore
+    pub shamir_threshold_input: String,
+    /// Shamir shares created from the secret key, for backup
+    shamir_shares: Vec<Zeroizing<String>>,
+    /// Input for folder to save the Shamir shares to
+    pub shamir_folder_input: String,
+    /// Input for Shamir shares to restore from, separated by spaces or commas
+    pub shamir_restore_input: SecretString,
+    /// Secret key restored from Shamir shares, to be accepted after checking its npub
+    shamir_restored: Option<SecretKeyHolder>,
+    /// Input for the label of a new sub-key
+    pub subkey_label_input: String,
+    /// Exported sub-key: its label and nsec
+    subkey_nsec_output: Option<(String, Zeroizing<String>)>,
+    /// Input for number of FROST participants (key shares) to create
+    pub frost_count_input: String,
+    /// Input for number of FROST signers needed
+    pub frost_threshold_input: String,
+    /// Input for encryption password, for revealing the stored mnemonic
+    pub reveal_mnemonic_password_input: SecretString,
+    /// Stored mnemonic, decrypted for showing it
+    revealed_mnemonic: Option<StoredMnemonic>,
+    /// Input for current password, for password change
+    pub change_old_password_input: SecretString,
+    /// Input for new password, for password change
+    pub change_new_password_input: SecretString,
+    /// Input for repeat new password, for password change
+    pub change_repeat_password_input: SecretString,
+    /// Input for new scrypt cost (log2 rounds) for password change, empty to keep current
+    pub change_log2_rounds_input: String,
+    /// Input for password, for upgrading the encryption cost of the saved secret key
+    pub upgrade_password_input: SecretString,
     /// Input for encryption password, for decrypt
-    pub decrypt_password_input: String,
+    pub decrypt_password_input: SecretString,
     /// Input for encryption password, for save
-    pub save_password_input: String,
+    pub save_password_input: SecretString,
     /// Input for repeat encryption password, for save
-    pub save_repeat_password_input: String,
+    pub save_repeat_password_input: SecretString,
+}
+
+impl Identity {
+    fn new(name: &str) -> Self {
+        Identity {
+            name: name.to_string(),
+            has_unsaved_change: false,
+            public_key: None,
+            secret_key: None,
+            encrypted_secret_key: None,
+            key_security: KeySecurity::Unknown,
+            mnemonic: None,
+            encrypted_mnemonic: None,
+            stored_kdf: None,
+            subkeys: Vec::new(),
+            frost_shares: None,
+            encrypted_frost_shares: Vec::new(),
+            retired: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// True if the secret key (or the FROST key shares) can be locked: it is unlocked,
+    /// and saved encrypted without changes
+    fn can_lock(&self) -> bool {
+        !self.has_unsaved_change
+            && ((self.encrypted_secret_key.is_some() && self.secret_key.is_some())
+                || (!self.encrypted_frost_shares.is_empty() && self.frost_shares.is_some()))
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Forget the decrypted secret key (and mnemonic) or FROST key shares, keep the public key
+    /// and the encrypted ones. Returns false if it cannot be locked (see can_lock()).
+    fn lock(&mut self) -> bool {
+        if !self.can_lock() {
+            return false;
+        }
+        self.secret_key = None;
+        self.mnemonic = None;
+        self.frost_shares = None;
+        true
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Set the secret key, and the public key derived from it
+    fn set_secret_key(&mut self, secret_key: SecretKeyHolder) {
+        self.public_key = Some(secret_key.public_key());
+        self.secret_key = Some(Arc::new(secret_key));
+    }
+
+    fn clear(&mut self) {
+        self.public_key = None;
+        self.secret_key = None;
+        self.encrypted_secret_key = None;
+        self.has_unsaved_change = false;
+        self.key_security = KeySecurity::Unknown;
+        self.mnemonic = None;
+        self.encrypted_mnemonic = None;
+        self.stored_kdf = None;
+        self.subkeys = Vec::new();
+        self.frost_shares = None;
+        self.encrypted_frost_shares = Vec::new();
+        self.retired = None;
+    }
 }
 
 impl Keystore {
+    /// A keystore on an in-memory storage, for tests
+    #[cfg(test)]
     pub fn new() -> Self {
+        Self::new_with_storage(crate::base::storage_backend::memory::MemoryStorage::new_shared())
+    }
+
+    pub fn new_with_storage(storage: Arc<dyn StorageBackend>) -> Self {
         Keystore {
-            has_unsaved_change: false,
-            keys: None,
-            encrypted_secret_key: None,
+            storage,
+            identities: vec![Identity::new(DEFAULT_IDENTITY_NAME)],
+            active: 0,
             hide_secret_key: true,
+            identity_name_input: String::new(),
             public_key_input: String::new(),
-            secret_key_input: String::new(),
-            mnemonic_input: String::new(),
-            decrypt_password_input: String::new(),
-            save_password_input: String::new(),
-            save_repeat_password_input: String::new(),
+            secret_key_input: SecretString::new(),
+            mnemonic_input: SecretString::new(),
+            mnemonic_passphrase_input: SecretString::new(),
+            mnemonic_account_input: "0".to_string(),
+            mnemonic_preview: Vec::new(),
+            ncryptsec_input: String::new(),
+            ncryptsec_password_input: SecretString::new(),
+            ncryptsec_output: String::new(),
+            import_file_path_input: String::new(),
+            import_file_password_input: SecretString::new(),
+            paper_backup_path_input: String::new(),
+            shamir_count_input: SHAMIR_DEFAULT_COUNT.to_string(),
+            shamir_threshold_input: SHAMIR_DEFAULT_THRESHOLD.to_string(),
+            shamir_shares: Vec::new(),
+            shamir_folder_input: String::new(),
+            shamir_restore_input: SecretString::new(),
+            shamir_restored: None,
+            subkey_label_input: String::new(),
+            subkey_nsec_output: None,
+            frost_count_input: FROST_DEFAULT_COUNT.to_string(),
+            frost_threshold_input: FROST_DEFAULT_THRESHOLD.to_string(),
+            reveal_mnemonic_password_input: SecretString::new(),
+            revealed_mnemonic: None,
+            change_old_password_input: SecretString::new(),
+            change_new_password_input: SecretString::new(),
+            change_repeat_password_input: SecretString::new(),
+            change_log2_rounds_input: String::new(),
+            upgrade_password_input: SecretString::new(),
+            decrypt_password_input: SecretString::new(),
+            save_password_input: SecretString::new(),
+            save_repeat_password_input: SecretString::new(),
+        }
+    }
+
+    fn active_identity(&self) -> &Identity {
+        &self.identities[self.active]
+    }
+
+    fn active_identity_mut(&mut self) -> &mut Identity {
+        &mut self.identities[self.active]
+    }
+
+    pub fn active_identity_name(&self) -> &str {
+        self.active_identity().name()
+    }
+
+    pub fn identity_names(&self) -> Vec<String> {
+        self.identities.iter().map(|i| i.name.clone()).collect()
+    }
+
+    /// Identity names are also used as folder names, restrict them
+    fn validate_identity_name(name: &str) -> Result<(), Error> {
+        if name.is_empty()
+            || name.len() > IDENTITY_NAME_MAX_LEN
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(Error::IdentityInvalidName);
+        }
+        Ok(())
+    }
+
+    /// Add a new, empty identity, and make it active
+    pub fn add_identity(&mut self, name: &str) -> Result<(), Error> {
+        Self::validate_identity_name(name)?;
+        if self.identities.iter().any(|i| i.name == name) {
+            return Err(Error::IdentityAlreadyExists);
+        }
+        self.identities.push(Identity::new(name));
+        self.active = self.identities.len() - 1;
+        self.ncryptsec_output = String::new();
+        self.revealed_mnemonic = None;
+        self.shamir_hide();
+        self.subkey_nsec_output = None;
+        Ok(())
+    }
+
+    /// Make the identity with the given name active
+    pub fn select_identity(&mut self, name: &str) -> Result<(), Error> {
+        match self.identities.iter().position(|i| i.name == name) {
+            None => Err(Error::IdentityNotFound),
+            Some(idx) => {
+                self.active = idx;
+                self.ncryptsec_output = String::new();
+                self.revealed_mnemonic = None;
+                self.shamir_hide();
+                self.subkey_nsec_output = None;
+                Ok(())
+            }
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Rotate the key of the active identity (e.g. if it is suspected compromised): a new
+    /// identity with a newly generated key is added and made active, the old key signs
+    /// a migration event pointing to the new public key, and the old identity is kept,
+    /// marked as retired. Returns the migration event (JSON), to be published.
+    /// If the keyring is persisted, the new key is saved first (encrypted with the password
+    /// in the save inputs), and the retirement is persisted only after that succeeded;
+    /// otherwise the new key is an unsaved change.
+    pub fn rotate_identity(
+        &mut self,
+        new_name: &str,
+        security_settings: &SecuritySettings,
+    ) -> Result<String, Error> {
+        if self.active_identity().retired.is_some() {
+            return Err(Error::IdentityRetired);
+        }
+        let persisted =
+            security_settings.allows_persist() && self.storage.exists(&Storage::keyring_file());
+        if persisted {
+            self.check_save_password(security_settings)?;
+        }
+        let old_signer = self.get_signer()?;
+        let new_secret_key = SecretKeyHolder::generate();
+        let successor = new_secret_key.public_key().to_bech32()?;
+        let migration_event = serde_json::to_string(&Rotation::migration_event(
+            &old_signer,
+            &new_secret_key.public_key(),
+        )?)?;
+        let old = self.active;
+        self.add_identity(new_name)?;
+        self.set_generated_secret_key(new_secret_key);
+        if persisted {
+            if let Err(e) = self.save_keys(&security_settings.kdf_params()) {
+                // drop the new identity, the old one stays in use
+                self.identities.pop();
+                self.active = old;
+                let _res = self
+                    .storage
+                    .remove_folder(&Storage::identity_folder(new_name));
+                let _res = self.save_keyring_index();
+                return Err(e);
+            }
+            self.save_password_input.clear();
+            self.save_repeat_password_input.clear();
+        }
+        self.identities[old].retired = Some(Retirement {
+            successor,
+            retired_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            migration_event: migration_event.clone(),
+        });
+        if persisted {
+            if let Err(e) = self.save_keyring_index() {
+                // the new key is saved, the old one can be retired again
+                self.identities[old].retired = None;
+                return Err(e);
+            }
+        }
+        Ok(migration_event)
+    }
+
+    /// Retirement of the active identity, if its key has been rotated
+    pub fn get_retirement(&self) -> Option<&Retirement> {
+        self.active_identity().retired.as_ref()
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Remove the active identity, from memory and also from storage, if it was saved.
+    /// The first remaining identity becomes active.
+    pub fn remove_active_identity(&mut self) -> Result<(), Error> {
+        if self.identities.len() <= 1 {
+            return Err(Error::IdentityCannotRemoveLast);
+        }
+        let removed = self.identities.remove(self.active);
+        self.active = 0;
+        self.ncryptsec_output = String::new();
+        self.revealed_mnemonic = None;
+        self.shamir_hide();
+        self.subkey_nsec_output = None;
+        self.storage
+            .remove_folder(&Storage::identity_folder(&removed.name))?;
+        if self.storage.exists(&Storage::keyring_file()) {
+            self.save_keyring_index()?;
         }
+        Ok(())
     }
 
-    /// Action to clear existing keys
+    /// Action to clear existing keys (of the active identity)
     pub fn clear(&mut self) {
-        self.keys = None;
-        self.encrypted_secret_key = None;
-        self.has_unsaved_change = false;
+        self.active_identity_mut().clear();
+        self.ncryptsec_output = String::new();
+        self.revealed_mnemonic = None;
+        self.shamir_shares = Vec::new();
+        self.subkey_nsec_output = None;
     }
 
     /// Generate new random keys
     pub fn generate(&mut self) {
-        self.keys = Some(Keys::generate());
-        self.encrypted_secret_key = None;
-        self.has_unsaved_change = true;
+        self.set_generated_secret_key(SecretKeyHolder::generate());
+    }
+
+    /// Use a newly generated secret key (e.g. from vanity search), as unsaved change
+    pub fn set_generated_secret_key(&mut self, secret_key: SecretKeyHolder) {
+        let identity = self.active_identity_mut();
+        identity.set_secret_key(secret_key);
+        identity.encrypted_secret_key = None;
+        identity.has_unsaved_change = true;
+        identity.key_security = KeySecurity::Medium;
     }
 
     /// Import public key only, in 'npub' bech32 or hex format. Signing will not be possible.
     pub fn import_public_key(&mut self, public_key_str: &str) -> Result<(), Error> {
         self.clear();
-        self.keys = Some(Keys::from_pk_str(public_key_str)?);
-        self.has_unsaved_change = true;
+        let identity = self.active_identity_mut();
+        identity.public_key = Some(Keys::from_pk_str(public_key_str)?.public_key());
+        identity.has_unsaved_change = true;
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import secret key, in 'nsec' bech32 or hex format (pubkey is derived from it)
+    pub fn import_secret_key(
+        &mut self,
+        secret_key_str: &str,
+        is_changed: bool,
+    ) -> Result<(), Error> {
+        self.clear();
+        let identity = self.active_identity_mut();
+        identity.set_secret_key(SecretKeyHolder::from_sk_str(secret_key_str)?);
+        identity.has_unsaved_change = is_changed;
+        // key was given in plain text
+        identity.key_security = KeySecurity::Weak;
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    pub fn import_encrypted_secret_key(
+        &mut self,
+        encrypted_key_str: &str,
+        is_changed: bool,
+    ) -> Result<(), Error> {
+        self.clear();
+        let identity = self.active_identity_mut();
+        identity.encrypted_secret_key =
+            Some(hex::decode(encrypted_key_str).map_err(|_e| Error::KeyInvalidEncrypted)?);
+        identity.has_unsaved_change = is_changed;
+        Ok(())
+    }
+
+    /// Try to decrypt the already loaded encrypted key using the decryption password
+    /// It is recommend to zeroize() the password after use.
+    pub fn decrypt_secret_key(&mut self, password: &str) -> Result<(), Error> {
+        let sk_bytes = match &self.active_identity().encrypted_secret_key {
+            None => return Err(Error::KeyNotSet),
+            Some(d) => d,
+        };
+        let (sk, key_security) = Encrypt::decrypt_key_with_security(&sk_bytes, &password)?;
+        // keep the encrypted key (for locking again), the loaded encrypted mnemonic,
+        // the stored cost, the sub-keys and the retirement, they belong to the same key
+        let encrypted_secret_key = sk_bytes.clone();
+        let encrypted_mnemonic = self.active_identity_mut().encrypted_mnemonic.take();
+        let stored_kdf = self.active_identity().stored_kdf;
+        let subkeys = std::mem::take(&mut self.active_identity_mut().subkeys);
+        let retired = self.active_identity_mut().retired.take();
+        self.clear();
+        let identity = self.active_identity_mut();
+        identity.set_secret_key(SecretKeyHolder::new(&sk));
+        identity.key_security = key_security;
+        identity.encrypted_secret_key = Some(encrypted_secret_key);
+        identity.encrypted_mnemonic = encrypted_mnemonic;
+        identity.stored_kdf = stored_kdf;
+        identity.subkeys = subkeys;
+        identity.retired = retired;
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import secret key from a NIP-49 encrypted 'ncryptsec' string, decrypting it with password
+    /// It is recommend to zeroize() the password after use.
+    pub fn import_ncryptsec(
+        &mut self,
+        ncryptsec: &str,
+        password: &str,
+        is_changed: bool,
+    ) -> Result<(), Error> {
+        let (sk, key_security) = Encrypt::decrypt_key_ncryptsec(ncryptsec, password)?;
+        self.clear();
+        let identity = self.active_identity_mut();
+        identity.set_secret_key(SecretKeyHolder::new(&sk));
+        identity.has_unsaved_change = is_changed;
+        identity.key_security = key_security;
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import a key from a key file (ncryptsec, JSON export, text with nsec, Keystr encrypted key),
+    /// format is detected. Encrypted keys are decrypted with the password. Returns the format.
+    /// It is recommend to zeroize() the password after use.
+    pub fn import_key_file(&mut self, path: &Path, password: &str) -> Result<KeyFileFormat, Error> {
+        let (format, key) = KeyFile::read(path)?;
+        match key {
+            KeyFileKey::SecretKey(secret_key) => self.import_secret_key(&secret_key, true)?,
+            KeyFileKey::Ncryptsec(ncryptsec) => {
+                self.import_ncryptsec(&ncryptsec, password, true)?
+            }
+            KeyFileKey::KeystrEncrypted(encrypted) => {
+                let (sk, key_security) = Encrypt::decrypt_key_with_security(&encrypted, password)?;
+                self.clear();
+                let identity = self.active_identity_mut();
+                identity.set_secret_key(SecretKeyHolder::new(&sk));
+                identity.has_unsaved_change = true;
+                identity.key_security = key_security;
+            }
+            KeyFileKey::PublicKey(public_key) => self.import_public_key(&public_key)?,
+        }
+        Ok(format)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Export secret key as a NIP-49 encrypted 'ncryptsec' string, with scrypt cost 2^log2_rounds
+    /// It is recommend to zeroize() the password after use.
+    pub fn export_ncryptsec(&self, password: &str, log2_rounds: u8) -> Result<String, Error> {
+        self.get_secret()?.with_secret_key(|sk| {
+            Encrypt::encrypt_key_ncryptsec(
+                sk,
+                password,
+                log2_rounds,
+                self.active_identity().key_security,
+            )
+        })?
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import secret key from BIP39 mnemonic, with optional passphrase, from the given NIP-06 account
+    pub fn import_mnemonic(
+        &mut self,
+        mnemonic: &str,
+        passphrase: &str,
+        account: u32,
+        is_changed: bool,
+    ) -> Result<(), Error> {
+        let mnemonic = bip39::Mnemonic::parse(mnemonic)?;
+        let secret_key = Nip06::derive_secret_key(&mnemonic, passphrase, account)?;
+        self.clear();
+        let identity = self.active_identity_mut();
+        identity.set_secret_key(SecretKeyHolder::new(&secret_key));
+        identity.has_unsaved_change = is_changed;
+        identity.key_security = KeySecurity::Unknown;
+        identity.mnemonic = Some(StoredMnemonic {
+            mnemonic: mnemonic.to_string(),
+            account,
+            derivation_path: Nip06::derivation_path(account),
+            has_passphrase: !passphrase.is_empty(),
+        });
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Derive the npubs of the first few accounts of a mnemonic, to help choosing the account
+    pub fn preview_mnemonic_accounts(
+        mnemonic: &str,
+        passphrase: &str,
+        count: u32,
+    ) -> Result<Vec<(u32, String)>, Error> {
+        let mnemonic = bip39::Mnemonic::parse(mnemonic)?;
+        let keys = Nip06::derive_secret_keys(&mnemonic, passphrase, 0..count)?;
+        let mut preview = Vec::new();
+        for (account, sk) in keys.iter().enumerate() {
+            let npub = sk.x_only_public_key(SECP256K1).0.to_bech32()?;
+            preview.push((account as u32, npub));
+        }
+        Ok(preview)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// The mnemonic of the active identity in plain form, if available: before saving,
+    /// or once the stored one is revealed
+    fn available_mnemonic(&self) -> Option<&StoredMnemonic> {
+        self.active_identity()
+            .mnemonic
+            .as_ref()
+            .or(self.revealed_mnemonic.as_ref())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Derive the secret key of an account from the mnemonic of the active identity.
+    /// If the mnemonic has a passphrase, it is taken from the passphrase input.
+    /// The derivation is checked by re-deriving the key of the identity itself.
+    fn derive_from_mnemonic(&self, account: u32) -> Result<SecretKey, Error> {
+        let stored = self.available_mnemonic().ok_or(Error::SubkeyNoMnemonic)?;
+        let mnemonic = bip39::Mnemonic::parse(&stored.mnemonic)?;
+        let passphrase = if stored.has_passphrase {
+            self.mnemonic_passphrase_input.as_str()
+        } else {
+            ""
+        };
+        let end = stored
+            .account
+            .max(account)
+            .checked_add(1)
+            .ok_or(Error::MnemonicInvalidAccount)?;
+        let mut keys =
+            Nip06::derive_secret_keys(&mnemonic, passphrase, stored.account.min(account)..end)?;
+        let own_key = &keys[(stored.account - stored.account.min(account)) as usize];
+        if Some(own_key.x_only_public_key(SECP256K1).0) != self.active_identity().public_key {
+            return Err(Error::SubkeyPassphraseMismatch);
+        }
+        Ok(keys.swap_remove((account - stored.account.min(account)) as usize))
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Derive a new labelled sub-key from the mnemonic, on the next unused account index
+    pub fn add_subkey(&mut self, label: &str) -> Result<(), Error> {
+        let label = label.trim();
+        let identity = self.active_identity();
+        if label.is_empty()
+            || label.chars().count() > SUBKEY_LABEL_MAX_LEN
+            || identity.subkeys.iter().any(|s| s.label == label)
+        {
+            return Err(Error::SubkeyInvalidLabel);
+        }
+        let own_account = self
+            .available_mnemonic()
+            .ok_or(Error::SubkeyNoMnemonic)?
+            .account;
+        let next_account = |account: u32| {
+            account
+                .checked_add(1)
+                .filter(|account| *account <= MAX_ACCOUNT)
+                .ok_or(Error::MnemonicInvalidAccount)
+        };
+        let mut account = next_account(own_account)?;
+        while identity.subkeys.iter().any(|s| s.account == account) {
+            account = next_account(account)?;
+        }
+        let npub = self
+            .derive_from_mnemonic(account)?
+            .x_only_public_key(SECP256K1)
+            .0
+            .to_bech32()?;
+        self.active_identity_mut().subkeys.push(SubKey {
+            label: label.to_string(),
+            account,
+            npub,
+        });
+        Ok(())
+    }
+
+    /// Remove a sub-key from the list (it can be derived again, on the same account)
+    pub fn remove_subkey(&mut self, index: usize) -> Result<(), Error> {
+        let subkeys = &mut self.active_identity_mut().subkeys;
+        if index >= subkeys.len() {
+            return Err(Error::SubkeyNotFound);
+        }
+        subkeys.remove(index);
+        self.subkey_nsec_output = None;
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Derive the secret key of a sub-key, in 'nsec' format
+    pub fn export_subkey_nsec(&self, index: usize) -> Result<Zeroizing<String>, Error> {
+        let subkey = self
+            .active_identity()
+            .subkeys
+            .get(index)
+            .ok_or(Error::SubkeyNotFound)?;
+        let secret_key = SecretKeyHolder::new(&self.derive_from_mnemonic(subkey.account)?);
+        if secret_key.public_key().to_bech32()? != subkey.npub {
+            return Err(Error::SubkeyPassphraseMismatch);
+        }
+        secret_key.to_nsec()
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Split the secret key into Shamir shares, any `threshold` of them restore it (see Shamir).
+    /// Once created, the key is considered to be handled insecurely.
+    pub fn split_secret_key(&mut self, threshold: u8, count: u8) -> Result<(), Error> {
+        let secret = self.get_secret()?;
+        let pubkey_prefix = Self::pubkey_prefix(&secret.public_key());
+        let shares = secret.with_secret_key(|sk| {
+            let mut secret_bytes = sk.secret_bytes();
+            let shares = Shamir::split(&secret_bytes, &pubkey_prefix, threshold, count);
+            secret_bytes.zeroize();
+            shares
+        })??;
+        self.shamir_shares = shares;
+        self.active_identity_mut().key_security = KeySecurity::Weak;
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Restore a secret key from Shamir shares (separated by whitespace or commas).
+    /// The restored key is verified against the public key prefix contained in the shares.
+    pub fn restore_from_shares(shares: &str) -> Result<SecretKeyHolder, Error> {
+        let shares: Vec<&str> = shares
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|s| !s.is_empty())
+            .collect();
+        let (secret_bytes, pubkey_prefix) = Shamir::combine(&shares)?;
+        let secret_key = SecretKeyHolder::new(&SecretKey::from_slice(&*secret_bytes)?);
+        if Self::pubkey_prefix(&secret_key.public_key()) != pubkey_prefix {
+            return Err(Error::ShamirVerificationFailed);
+        }
+        Ok(secret_key)
+    }
+
+    fn pubkey_prefix(public_key: &XOnlyPublicKey) -> [u8; PUBKEY_PREFIX_LEN] {
+        let mut prefix = [0; PUBKEY_PREFIX_LEN];
+        prefix.copy_from_slice(&public_key.serialize()[0..PUBKEY_PREFIX_LEN]);
+        prefix
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Use the secret key restored from Shamir shares, if any
+    pub fn accept_restored_secret_key(&mut self) -> Result<(), Error> {
+        let secret_key = self.shamir_restored.take().ok_or(Error::KeyNotSet)?;
+        self.clear();
+        let identity = self.active_identity_mut();
+        identity.set_secret_key(secret_key);
+        identity.has_unsaved_change = true;
+        // shares were handled in plain text
+        identity.key_security = KeySecurity::Weak;
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Save the created Shamir shares to a folder, one file per share. Returns the number saved.
+    pub fn save_shamir_shares(&self, folder: &Path) -> Result<usize, Error> {
+        let count = self.shamir_shares.len();
+        for (i, share) in self.shamir_shares.iter().enumerate() {
+            let mut path = folder.to_path_buf();
+            path.push(format!(
+                "{}-share-{}-of-{}.txt",
+                self.active_identity_name(),
+                i + 1,
+                count
+            ));
+            Storage::write_private_file(&path, share.as_bytes())?;
+        }
+        Ok(count)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Write a printable paper backup (SVG) to a file: npub, and the exported ncryptsec or
+    /// (if with_mnemonic) the revealed mnemonic, with QR codes and recovery instructions.
+    pub fn export_paper_backup(&mut self, path: &Path, with_mnemonic: bool) -> Result<(), Error> {
+        let public_key = self.get_public_key()?;
+        let secret = if with_mnemonic {
+            let mnemonic = self
+                .revealed_mnemonic
+                .as_ref()
+                .ok_or(Error::PaperBackupNoSecret)?;
+            PaperBackupSecret::Mnemonic {
+                words: Zeroizing::new(mnemonic.mnemonic.clone()),
+                account: mnemonic.account,
+                derivation_path: mnemonic.derivation_path.clone(),
+                has_passphrase: mnemonic.has_passphrase,
+            }
+        } else {
+            if self.ncryptsec_output.is_empty() {
+                return Err(Error::PaperBackupNoSecret);
+            }
+            PaperBackupSecret::Ncryptsec(self.ncryptsec_output.clone())
+        };
+        let backup = PaperBackup {
+            identity: self.active_identity_name().to_string(),
+            npub: public_key.to_bech32()?,
+            secret,
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        };
+        Storage::write_private_file(path, backup.to_svg()?.as_bytes())?;
+        if with_mnemonic {
+            // mnemonic was written in plain text
+            self.active_identity_mut().key_security = KeySecurity::Weak;
+        }
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Generate a new FROST group key with a trusted dealer, as `count` key shares, any
+    /// `threshold` of them can sign. The public key is the group key; all shares are held
+    /// locally, as unsaved change. Once saved, share files can be moved to other devices.
+    pub fn generate_frost(&mut self, threshold: u8, count: u8) -> Result<(), Error> {
+        let shares = Frost::generate_with_dealer(threshold, count)?;
+        let public_key = XOnlyPublicKey::from_slice(shares[0].group_public_key())?;
+        self.clear();
+        let identity = self.active_identity_mut();
+        identity.public_key = Some(public_key);
+        identity.frost_shares = Some(Arc::new(shares));
+        identity.has_unsaved_change = true;
+        identity.key_security = KeySecurity::Medium;
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Encrypt each FROST key share held locally separately (they are stored one per file),
+    /// returns the participant indexes and the encrypted shares.
+    /// It is recommend to zeroize() the password after use.
+    fn encrypt_frost_shares(
+        &self,
+        password: &str,
+        kdf: &KdfParams,
+    ) -> Result<Vec<(u8, Vec<u8>)>, Error> {
+        let identity = self.active_identity();
+        let shares = identity.frost_shares.as_ref().ok_or(Error::KeyNotSet)?;
+        shares
+            .iter()
+            .map(|share| {
+                Encrypt::encrypt_data(&share.to_bytes(), password, kdf, identity.key_security)
+                    .map(|data| (share.index(), data))
+            })
+            .collect()
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Decrypt the loaded encrypted FROST key shares using the password.
+    /// The shares have to belong to the group key of the identity.
+    /// It is recommend to zeroize() the password after use.
+    pub fn decrypt_frost_shares(&mut self, password: &str) -> Result<(), Error> {
+        let public_key = self.get_public_key()?.serialize();
+        let identity = self.active_identity();
+        if identity.encrypted_frost_shares.is_empty() {
+            return Err(Error::KeyNotSet);
+        }
+        let mut shares = Vec::new();
+        let mut key_security = KeySecurity::Unknown;
+        for encrypted in &identity.encrypted_frost_shares {
+            let (mut data, security) = Encrypt::decrypt_data(encrypted, password)?;
+            let share = FrostKeyShare::from_bytes(&data);
+            data.zeroize();
+            let share = share?;
+            if *share.group_public_key() != public_key {
+                return Err(Error::FrostInvalidShare);
+            }
+            key_security = security;
+            shares.push(share);
+        }
+        let identity = self.active_identity_mut();
+        identity.frost_shares = Some(Arc::new(shares));
+        identity.key_security = key_security;
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Save secret key to file, encrypted using the given key derivation.
+    pub fn save_encrypted_secret_key(&mut self, kdf: &KdfParams) -> Result<(), Error> {
+        let secret = self.get_secret()?;
+
+        if self.save_password_input.as_str() != self.save_repeat_password_input.as_str() {
+            return Err(Error::KeyEncryptionPasswordMismatch);
+        }
+        let password = self.save_password_input.as_str();
+
+        let key_security = self.active_identity().key_security;
+        let data = secret
+            .with_secret_key(|sk| Encrypt::encrypt_key_kdf(sk, password, kdf, key_security))??;
+        let hex_string = hex::encode(&data);
+        let path = Storage::encrypted_secret_key_file(self.active_identity_name());
+        self.storage.write(&path, hex_string.as_bytes(), true)?;
+        // keep it, the key can be locked from now on
+        self.active_identity_mut().encrypted_secret_key = Some(data);
+
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Save the mnemonic of the secret key to file, encrypted with the same password as the key.
+    /// If the key has no mnemonic, a previously saved mnemonic file is removed.
+    fn save_encrypted_mnemonic(&mut self, kdf: &KdfParams) -> Result<(), Error> {
+        let path = Storage::encrypted_mnemonic_file(self.active_identity_name());
+        let identity = self.active_identity();
+        let data = match &identity.mnemonic {
+            None => {
+                if identity.encrypted_mnemonic.is_none() {
+                    // stale, belongs to a previous key
+                    self.storage.remove_file(&path)?;
+                }
+                return Ok(());
+            }
+            Some(mnemonic) => {
+                let mut serialized = serde_json::to_string(mnemonic)?;
+                let data = Encrypt::encrypt_data(
+                    serialized.as_bytes(),
+                    self.save_password_input.as_str(),
+                    kdf,
+                    identity.key_security,
+                );
+                serialized.zeroize();
+                data?
+            }
+        };
+        self.storage
+            .write(&path, hex::encode(&data).as_bytes(), true)?;
+        let identity = self.active_identity_mut();
+        identity.encrypted_mnemonic = Some(data);
+        // plain mnemonic is not needed any more, it can be revealed using the password
+        identity.mnemonic = None;
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Save the FROST key shares held locally, one file per participant, each encrypted with
+    /// the save password using the given key derivation. Other share files are removed, and so
+    /// is a secret key saved earlier for the identity (it would take precedence when loading).
+    fn save_frost_shares(&mut self, kdf: &KdfParams) -> Result<(), Error> {
+        if self.save_password_input.as_str() != self.save_repeat_password_input.as_str() {
+            return Err(Error::KeyEncryptionPasswordMismatch);
+        }
+        let encrypted_shares = self.encrypt_frost_shares(self.save_password_input.as_str(), kdf)?;
+        let name = self.active_identity_name().to_string();
+        for (index, data) in &encrypted_shares {
+            self.storage.write(
+                &Storage::frost_share_file(&name, *index),
+                hex::encode(data).as_bytes(),
+                true,
+            )?;
+        }
+        let indexes: Vec<u8> = encrypted_shares.iter().map(|(index, _)| *index).collect();
+        self.remove_frost_share_files(&name, &indexes)?;
+        for path in [
+            Storage::encrypted_secret_key_file(&name),
+            Storage::encrypted_mnemonic_file(&name),
+            Storage::subkeys_file(&name),
+        ] {
+            self.storage.remove_file(&path)?;
+        }
+        self.active_identity_mut().encrypted_frost_shares =
+            encrypted_shares.into_iter().map(|(_, data)| data).collect();
         Ok(())
     }
 
-    /// Warning: Security-sensitive method!
-    /// Import secret key, in 'nsec' bech32 or hex format (pubkey is derived from it)
-    pub fn import_secret_key(
-        &mut self,
-        secret_key_str: &str,
-        is_changed: bool,
-    ) -> Result<(), Error> {
-        self.clear();
-        self.keys = Some(Keys::from_sk_str(secret_key_str)?);
-        self.has_unsaved_change = is_changed;
-        Ok(())
+    /// Remove the FROST key share files of an identity, except the ones with the given indexes
+    fn remove_frost_share_files(&self, identity: &str, keep: &[u8]) -> Result<(), Error> {
+        for index in (1..=MAX_PARTICIPANTS).filter(|index| !keep.contains(index)) {
+            self.storage
+                .remove_file(&Storage::frost_share_file(identity, index))?;
+        }
+        Ok(())
+    }
+
+    /// True if the identity has saved FROST key share files
+    fn has_frost_share_files(&self, identity: &str) -> bool {
+        (1..=MAX_PARTICIPANTS).any(|index| {
+            self.storage
+                .exists(&Storage::frost_share_file(identity, index))
+        })
+    }
+
+    /// Files of the saved key of an identity, these are versioned together
+    fn key_files(identity: &str) -> [PathBuf; 4] {
+        [
+            Storage::public_key_file(identity),
+            Storage::encrypted_secret_key_file(identity),
+            Storage::encrypted_mnemonic_file(identity),
+            Storage::subkeys_file(identity),
+        ]
+    }
+
+    /// Keep the saved key files of the active identity as their latest previous version,
+    /// before they are overwritten. Nothing is kept if there is no saved secret key.
+    fn keep_key_versions(&self) -> Result<(), Error> {
+        let name = self.active_identity_name();
+        if !self
+            .storage
+            .exists(&Storage::encrypted_secret_key_file(name))
+        {
+            return Ok(());
+        }
+        for path in Self::key_files(name) {
+            Storage::keep_version(self.storage.as_ref(), &path, KEY_FILE_VERSIONS)?;
+        }
+        Ok(())
+    }
+
+    /// Remove the previous versions of the saved key files of the active identity
+    fn remove_key_versions(&self) -> Result<(), Error> {
+        for path in Self::key_files(self.active_identity_name()) {
+            for version in 1..=KEY_FILE_VERSIONS {
+                self.storage
+                    .remove_file(&Storage::version_file(&path, version))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Previous versions of the saved secret key of the active identity:
+    /// version number (1 is the latest), and the time it was replaced (unix timestamp)
+    pub fn get_key_versions(&self) -> Vec<(u8, u64)> {
+        let path = Storage::encrypted_secret_key_file(self.active_identity_name());
+        (1..=KEY_FILE_VERSIONS)
+            .filter_map(|version| {
+                let modified = self
+                    .storage
+                    .modified(&Storage::version_file(&path, version))
+                    .ok()?;
+                Some((version, modified))
+            })
+            .collect()
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Restore a previous version of the saved key of the active identity (with its mnemonic,
+    /// sub-keys and public key). The current one is kept as a version, so it can be undone.
+    /// The restored key is loaded, it has to be unlocked with its own password.
+    pub fn restore_key_version(&mut self, version: u8) -> Result<(), Error> {
+        if self.has_unsaved_change() {
+            return Err(Error::KeyUnsavedChange);
+        }
+        let name = self.active_identity_name().to_string();
+        if !self.storage.exists(&Storage::version_file(
+            &Storage::encrypted_secret_key_file(&name),
+            version,
+        )) {
+            return Err(Error::KeyVersionNotFound);
+        }
+        // read them first, keeping the current files shifts the versions
+        let mut restored = Vec::new();
+        for path in Self::key_files(&name) {
+            let version_path = Storage::version_file(&path, version);
+            let contents = if self.storage.exists(&version_path) {
+                Some(Zeroizing::new(self.storage.read(&version_path)?))
+            } else {
+                None
+            };
+            restored.push((path, contents));
+        }
+        self.keep_key_versions()?;
+        for (path, contents) in restored {
+            match contents {
+                Some(contents) => self.storage.write(&path, &contents, true)?,
+                None => self.storage.remove_file(&path)?,
+            }
+        }
+        let retired = self.active_identity_mut().retired.take();
+        self.clear();
+        let res = self.load_identity_keys();
+        self.active_identity_mut().retired = retired;
+        res
+    }
+
+    /// Read a file containing hex-encoded encrypted data
+    fn read_hex_file(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        hex::decode(self.storage.read_to_string(path)?.trim())
+            .map_err(|_e| Error::KeyInvalidEncrypted)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Change the password of the stored secret key (and mnemonic) of the active identity.
+    /// The old password is verified by decrypting, then the key is re-encrypted with new salt
+    /// and nonce, optionally with a stronger key derivation (e.g. higher scrypt cost).
+    /// Previous versions of the saved key are removed, they are encrypted with old passwords.
+    /// Returns the key derivation used.
+    /// It is recommend to zeroize() the passwords after use.
+    pub fn change_password(
+        &mut self,
+        old_password: &str,
+        new_password: &str,
+        kdf: Option<KdfParams>,
+    ) -> Result<KdfParams, Error> {
+        if self.has_unsaved_change() {
+            return Err(Error::KeyUnsavedChange);
+        }
+        let secret_path = Storage::encrypted_secret_key_file(self.active_identity_name());
+        if !self.storage.exists(&secret_path) {
+            return Err(Error::KeyNotSet);
+        }
+        let encrypted = self.read_hex_file(&secret_path)?;
+        let current_kdf = Encrypt::get_kdf_params(&encrypted)?;
+        let kdf = kdf.unwrap_or(current_kdf);
+        if kdf.is_weaker_than(&current_kdf) {
+            return Err(Error::KeyEncryptionCostInvalid);
+        }
+
+        // verify old password
+        let (sk, key_security) = Encrypt::decrypt_key_with_security(&encrypted, old_password)?;
+        let new_encrypted = Encrypt::encrypt_key_kdf(&sk, new_password, &kdf, key_security)?;
+
+        // re-encrypt the mnemonic as well, before writing anything
+        let mnemonic_path = Storage::encrypted_mnemonic_file(self.active_identity_name());
+        let new_encrypted_mnemonic = if self.storage.exists(&mnemonic_path) {
+            let (mut data, key_security) =
+                Encrypt::decrypt_data(&self.read_hex_file(&mnemonic_path)?, old_password)?;
+            let res = Encrypt::encrypt_data(&data, new_password, &kdf, key_security);
+            data.zeroize();
+            Some(res?)
+        } else {
+            None
+        };
+
+        self.storage
+            .write(&secret_path, hex::encode(&new_encrypted).as_bytes(), true)?;
+        if let Some(data) = &new_encrypted_mnemonic {
+            self.storage
+                .write(&mnemonic_path, hex::encode(data).as_bytes(), true)?;
+        }
+        // an old (possibly leaked) password should not decrypt any saved version
+        self.remove_key_versions()?;
+
+        let identity = self.active_identity_mut();
+        identity.encrypted_secret_key = Some(new_encrypted);
+        identity.stored_kdf = Some(kdf);
+        if new_encrypted_mnemonic.is_some() {
+            identity.encrypted_mnemonic = new_encrypted_mnemonic;
+        }
+        Ok(kdf)
+    }
+
+    /// Save public key to file.
+    pub fn save_public_key(&self) -> Result<(), Error> {
+        let pubkey = self.get_public_key()?;
+        let npub_string = pubkey.to_bech32()?;
+        self.storage.write(
+            &Storage::public_key_file(self.active_identity_name()),
+            npub_string.as_bytes(),
+            false,
+        )?;
+        Ok(())
+    }
+
+    /// Save the list of identities (not the keys themselves)
+    fn save_keyring_index(&self) -> Result<(), Error> {
+        let index = KeyringIndex {
+            identities: self.identity_names(),
+            active: self.active_identity_name().to_string(),
+            retired: self
+                .identities
+                .iter()
+                .filter_map(|i| i.retired.clone().map(|r| (i.name.clone(), r)))
+                .collect(),
+        };
+        self.storage.write(
+            &Storage::keyring_file(),
+            serde_json::to_string(&index)?.as_bytes(),
+            false,
+        )?;
+        Ok(())
+    }
+
+    /// Check that the password in the save inputs is given, if it is mandatory
+    fn check_save_password(&self, security_settings: &SecuritySettings) -> Result<(), Error> {
+        if security_settings.security_level == SecurityLevel::PersistMandatoryPassword
+            && self.save_password_input.is_empty()
+        {
+            return Err(Error::KeyEncryptionPasswordMissing);
+        }
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Save public/secret key of the active identity to file(s), and the keyring index.
+    /// The secret key is encrypted using the given key derivation.
+    /// Returns if secret key has been saved
+    pub fn save_keys(&mut self, kdf: &KdfParams) -> Result<bool, Error> {
+        if !self.has_unsaved_change() {
+            return Err(Error::KeyNoChangeToSave);
+        }
+        if (self.is_secret_key_set() || self.is_frost_key())
+            && self.save_password_input.as_str() != self.save_repeat_password_input.as_str()
+        {
+            return Err(Error::KeyEncryptionPasswordMismatch);
+        }
+        // the saved key is about to be overwritten, keep it
+        self.keep_key_versions()?;
+        // save public key
+        self.save_public_key()?;
+        // save secret key if set
+        let secret_saved = if self.is_secret_key_set() {
+            self.save_encrypted_secret_key(kdf)?;
+            self.save_encrypted_mnemonic(kdf)?;
+            self.save_subkeys()?;
+            // stale, belong to a previous FROST group key
+            self.remove_frost_share_files(self.active_identity_name(), &[])?;
+            self.active_identity_mut().stored_kdf = Some(*kdf);
+            true
+        } else if self.is_frost_key() {
+            self.save_frost_shares(kdf)?;
+            self.active_identity_mut().stored_kdf = Some(*kdf);
+            true
+        } else {
+            false
+        };
+        self.save_keyring_index()?;
+        self.active_identity_mut().has_unsaved_change = false;
+        Ok(secret_saved)
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Load secret key from file
+    pub fn load_secret_key(&mut self) -> Result<(), Error> {
+        let sk_hex = Zeroizing::new(self.storage.read_to_string(
+            &Storage::encrypted_secret_key_file(self.active_identity_name()),
+        )?);
+        self.import_encrypted_secret_key(&sk_hex, false)?;
+        let identity = self.active_identity_mut();
+        identity.stored_kdf = match &identity.encrypted_secret_key {
+            None => None,
+            Some(encrypted) => Encrypt::get_kdf_params(encrypted).ok(),
+        };
+        // Also try to decrypt with empty password, set it if successful, ignore if not
+        let _ret = self.decrypt_secret_key("");
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Load the encrypted mnemonic from file (it is decrypted only when revealed)
+    fn load_encrypted_mnemonic(&mut self) -> Result<(), Error> {
+        let path = Storage::encrypted_mnemonic_file(self.active_identity_name());
+        if !self.storage.exists(&path) {
+            return Ok(());
+        }
+        let data = self.read_hex_file(&path)?;
+        self.active_identity_mut().encrypted_mnemonic = Some(data);
+        Ok(())
+    }
+
+    /// Load the list of sub-keys from file, if any
+    fn load_subkeys(&mut self) -> Result<(), Error> {
+        let path = Storage::subkeys_file(self.active_identity_name());
+        if !self.storage.exists(&path) {
+            return Ok(());
+        }
+        let subkeys = serde_json::from_str::<Vec<SubKey>>(&self.storage.read_to_string(&path)?)?;
+        self.active_identity_mut().subkeys = subkeys;
+        Ok(())
+    }
+
+    /// Save the list of sub-keys (public keys and labels only), remove the file if there is none
+    fn save_subkeys(&self) -> Result<(), Error> {
+        let path = Storage::subkeys_file(self.active_identity_name());
+        let subkeys = &self.active_identity().subkeys;
+        if subkeys.is_empty() {
+            return self.storage.remove_file(&path);
+        }
+        self.storage
+            .write(&path, serde_json::to_string(subkeys)?.as_bytes(), false)?;
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Load the (group) public key and the encrypted FROST key shares from file;
+    /// the shares are decrypted when unlocking
+    fn load_frost_shares(&mut self) -> Result<(), Error> {
+        self.load_public_key()?;
+        let name = self.active_identity_name().to_string();
+        let mut encrypted_shares = Vec::new();
+        for index in 1..=MAX_PARTICIPANTS {
+            let path = Storage::frost_share_file(&name, index);
+            if self.storage.exists(&path) {
+                encrypted_shares.push(self.read_hex_file(&path)?);
+            }
+        }
+        let identity = self.active_identity_mut();
+        identity.stored_kdf = encrypted_shares
+            .first()
+            .and_then(|encrypted| Encrypt::get_kdf_params(encrypted).ok());
+        identity.encrypted_frost_shares = encrypted_shares;
+        // Also try to decrypt with empty password, set it if successful, ignore if not
+        let _ret = self.decrypt_frost_shares("");
+        Ok(())
+    }
+
+    /// Load public key from file
+    pub fn load_public_key(&mut self) -> Result<(), Error> {
+        let pk_string = self
+            .storage
+            .read_to_string(&Storage::public_key_file(self.active_identity_name()))?;
+        self.import_public_key(&pk_string)?;
+        self.active_identity_mut().has_unsaved_change = false;
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Load public/secret key of the active identity from file, if there is any
+    fn load_identity_keys(&mut self) -> Result<(), Error> {
+        let secret_path = Storage::encrypted_secret_key_file(self.active_identity_name());
+        if self.storage.exists(&secret_path) {
+            // secret key file exists, load secret key, and its mnemonic and sub-keys if any
+            self.load_secret_key()?;
+            self.load_encrypted_mnemonic()?;
+            self.load_subkeys()
+        } else if self.has_frost_share_files(self.active_identity_name()) {
+            // FROST group key, load its key shares
+            self.load_frost_shares()
+        } else if self
+            .storage
+            .exists(&Storage::public_key_file(self.active_identity_name()))
+        {
+            // load public key
+            self.load_public_key()
+        } else {
+            // nothing saved for this identity yet
+            Ok(())
+        }
+    }
+
+    /// Move keys from the single-key storage (used before the keyring) into the keyring,
+    /// as the default identity. Does nothing if the keyring already exists.
+    fn migrate_legacy_storage(&self) -> Result<(), Error> {
+        if self.storage.exists(&Storage::keyring_file()) {
+            return Ok(());
+        }
+        let legacy_files = [
+            (
+                Storage::legacy_public_key_file(),
+                Storage::public_key_file(DEFAULT_IDENTITY_NAME),
+            ),
+            (
+                Storage::legacy_encrypted_secret_key_file(),
+                Storage::encrypted_secret_key_file(DEFAULT_IDENTITY_NAME),
+            ),
+        ];
+        if !legacy_files
+            .iter()
+            .any(|(from, _to)| self.storage.exists(from))
+        {
+            return Ok(());
+        }
+        for (from, to) in legacy_files.iter() {
+            if self.storage.exists(from) {
+                self.storage.rename(from, to)?;
+            }
+        }
+        let index = KeyringIndex {
+            identities: vec![DEFAULT_IDENTITY_NAME.to_string()],
+            active: DEFAULT_IDENTITY_NAME.to_string(),
+            retired: BTreeMap::new(),
+        };
+        self.storage.write(
+            &Storage::keyring_file(),
+            serde_json::to_string(&index)?.as_bytes(),
+            false,
+        )?;
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Load all identities of the keyring from file, replacing the current ones
+    pub fn load_keys(&mut self) -> Result<(), Error> {
+        self.migrate_legacy_storage()?;
+        let index_str = self.storage.read_to_string(&Storage::keyring_file())?;
+        let index = serde_json::from_str::<KeyringIndex>(&index_str)?;
+
+        self.identities = Vec::new();
+        for name in &index.identities {
+            if Self::validate_identity_name(name).is_ok()
+                && !self.identities.iter().any(|i| &i.name == name)
+            {
+                self.identities.push(Identity::new(name));
+            }
+        }
+        if self.identities.is_empty() {
+            self.identities.push(Identity::new(DEFAULT_IDENTITY_NAME));
+        }
+        // Load each identity; a failing one does not prevent loading the others
+        let mut res = Ok(());
+        for idx in 0..self.identities.len() {
+            self.active = idx;
+            if let Err(e) = self.load_identity_keys() {
+                res = Err(e);
+            }
+            let identity = self.active_identity_mut();
+            identity.retired = index.retired.get(&identity.name).cloned();
+        }
+        self.active = self
+            .identities
+            .iter()
+            .position(|i| i.name == index.active)
+            .unwrap_or(0);
+        res
+    }
+
+    /// Warning: Security-sensitive method!
+    ///.Action to save secret key to file
+    pub fn save_action(
+        &mut self,
+        security_settings: &SecuritySettings,
+        status: &mut StatusMessages,
+    ) {
+        let res = if !security_settings.allows_persist() {
+            Err(Error::KeySaveNotAllowed)
+        } else {
+            self.check_save_password(security_settings)
+                .and_then(|_| self.save_keys(&security_settings.kdf_params()))
+        };
+        match res {
+            Err(e) => status.set_error_err(&e),
+            Ok(ss) => {
+                if ss {
+                    // Clear password input
+                    self.save_password_input.clear();
+                    self.save_repeat_password_input.clear();
+                    status.set("Secret key persisted to storage");
+                } else {
+                    status.set("Public key persisted to storage");
+                }
+            }
+        }
+    }
+
+    ///.Action to load secret key from file
+    pub fn load_action(
+        &mut self,
+        security_settings: &SecuritySettings,
+        status: &mut StatusMessages,
+    ) {
+        // TODO confirmation
+        let res = if !security_settings.allows_persist() {
+            Err(Error::KeyLoadNotAllowed)
+        } else {
+            self.load_keys()
+        };
+        if let Err(e) = res {
+            status.set_error_err(&e);
+        } else {
+            status.set("Keys loaded from storage (may need decryption with password)");
+            // one message for all identities, a message per identity would hide the others
+            let mut not_private = Vec::new();
+            let mut weak = Vec::new();
+            for identity in &self.identities {
+                let secret_path = Storage::encrypted_secret_key_file(&identity.name);
+                if let Ok(false) = self.storage.is_private(&secret_path) {
+                    not_private.push(format!("'{}'", identity.name));
+                }
+                if let Some(kdf) = &identity.stored_kdf {
+                    if security_settings.is_kdf_weak(kdf) {
+                        weak.push(format!("'{}' ({})", identity.name, kdf));
+                    }
+                }
+            }
+            if !not_private.is_empty() {
+                status.set_error(&format!(
+                    "Saved secret key files of {} are readable by other users, check their permissions",
+                    not_private.join(", ")
+                ));
+            }
+            if !weak.is_empty() {
+                status.set_error(&format!(
+                    "Saved secret keys of {} use a weaker encryption than the setting ({}), consider upgrading them",
+                    weak.join(", "),
+                    security_settings.kdf_params()
+                ));
+            }
+        }
+    }
+
+    /// Key derivation of the saved secret key of the active identity,
+    /// if it is weaker than the one in the settings
+    pub fn get_weak_stored_kdf(&self, security_settings: &SecuritySettings) -> Option<KdfParams> {
+        match self.active_identity().stored_kdf {
+            Some(kdf) if security_settings.is_kdf_weak(&kdf) => Some(kdf),
+            _ => None,
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Action to re-encrypt the saved secret key with the key derivation from the settings,
+    /// keeping the password
+    pub fn upgrade_encryption_action(
+        &mut self,
+        security_settings: &SecuritySettings,
+        status: &mut StatusMessages,
+    ) {
+        let password = self.upgrade_password_input.to_zeroizing();
+        let res = self.change_password(&password, &password, Some(security_settings.kdf_params()));
+        match res {
+            Err(e) => status.set_error(&format!(
+                "Could not upgrade encryption, check password! ({})",
+                e
+            )),
+            Ok(kdf) => {
+                self.upgrade_password_input.clear();
+                status.set(&format!("Saved secret key re-encrypted ({})", kdf));
+            }
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Action to change the password of the stored secret key
+    pub fn change_password_action(
+        &mut self,
+        security_settings: &SecuritySettings,
+        status: &mut StatusMessages,
+    ) {
+        let res = if !security_settings.allows_persist() {
+            Err(Error::KeySaveNotAllowed)
+        } else if self.change_new_password_input.as_str()
+            != self.change_repeat_password_input.as_str()
+        {
+            Err(Error::KeyEncryptionPasswordMismatch)
+        } else if security_settings.security_level == SecurityLevel::PersistMandatoryPassword
+            && self.change_new_password_input.is_empty()
+        {
+            Err(Error::KeyEncryptionPasswordMissing)
+        } else {
+            let log2_rounds_input = self.change_log2_rounds_input.trim();
+            let kdf = if log2_rounds_input.is_empty() {
+                Ok(None)
+            } else {
+                log2_rounds_input
+                    .parse::<u8>()
+                    .map(|log2_rounds| Some(KdfParams::Scrypt { log2_rounds }))
+                    .map_err(|_e| Error::KeyEncryptionCostInvalid)
+            };
+            kdf.and_then(|kdf| {
+                self.change_password(
+                    &self.change_old_password_input.to_zeroizing(),
+                    &self.change_new_password_input.to_zeroizing(),
+                    kdf,
+                )
+            })
+        };
+        // cleanup
+        self.change_old_password_input.clear();
+        self.change_new_password_input.clear();
+        self.change_repeat_password_input.clear();
+        match res {
+            Err(e) => status.set_error(&format!("Could not change password ({})", e)),
+            Ok(kdf) => {
+                self.change_log2_rounds_input = String::new();
+                status.set(&format!(
+                    "Password changed, secret key re-encrypted ({})",
+                    kdf
+                ));
+            }
+        }
+    }
+
+    pub fn unlock_secret_key_action(
+        &mut self,
+        security_settings: &SecuritySettings,
+        status: &mut StatusMessages,
+    ) {
+        // check if password is set if needed
+        let res = if security_settings.security_level == SecurityLevel::PersistMandatoryPassword
+            && self.decrypt_password_input.is_empty()
+        {
+            Err(Error::KeyEncryptionPasswordMissing)
+        } else if self.is_frost_key() {
+            self.decrypt_frost_shares(&self.decrypt_password_input.to_zeroizing())
+        } else {
+            self.decrypt_secret_key(&self.decrypt_password_input.to_zeroizing())
+        };
+        match res {
+            Err(e) => status.set(&format!(
+                "Could not decrypt secret key, check password! ({})",
+                e
+            )),
+            Ok(_) => {
+                // cleanup
+                self.decrypt_password_input.clear();
+                status.set("Secret key decrypted")
+            }
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import secret key, in 'nsec' bech32 or hex format (pubkey is derived from it)
+    pub fn import_secret_key_action(&mut self, status: &mut StatusMessages) {
+        match self.import_secret_key(&self.secret_key_input.to_zeroizing(), true) {
+            Err(e) => status.set_error(&format!("Error importing, {}", e.to_string())),
+            Ok(_) => status.set("Secret key imported"),
+        };
+        // cleanup
+        self.secret_key_input.clear();
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Import secret key from BIP39 mnemonic
+    pub fn import_mnemonic_action(&mut self, status: &mut StatusMessages) {
+        let res = match Nip06::parse_account(&self.mnemonic_account_input) {
+            Err(e) => Err(e),
+            Ok(account) => self.import_mnemonic(
+                &self.mnemonic_input.to_zeroizing(),
+                &self.mnemonic_passphrase_input.to_zeroizing(),
+                account,
+                true,
+            ),
+        };
+        match res {
+            Err(e) => {
+                status.set_error(&format!("Error importing from mnemonic, {}", e.to_string()))
+            }
+            Ok(_) => status.set(&format!(
+                "Secret key imported from mnemonic (account {})",
+                self.mnemonic_account_input.trim()
+            )),
+        };
+        // cleanup
+        self.mnemonic_input.clear();
+        self.mnemonic_passphrase_input.clear();
+        self.mnemonic_account_input = "0".to_string();
+        self.mnemonic_preview = Vec::new();
+    }
+
+    /// Action to show the npubs of the first few accounts of the mnemonic input
+    pub fn preview_mnemonic_action(&mut self, status: &mut StatusMessages) {
+        match Self::preview_mnemonic_accounts(
+            self.mnemonic_input.as_str(),
+            self.mnemonic_passphrase_input.as_str(),
+            MNEMONIC_PREVIEW_ACCOUNTS,
+        ) {
+            Err(e) => {
+                self.mnemonic_preview = Vec::new();
+                status.set_error(&format!("Error deriving from mnemonic, {}", e.to_string()))
+            }
+            Ok(preview) => self.mnemonic_preview = preview,
+        }
+    }
+
+    /// Account indexes and npubs derived in the mnemonic preview
+    pub fn get_mnemonic_preview(&self) -> &Vec<(u32, String)> {
+        &self.mnemonic_preview
     }
 
-    /// Warning: Security-sensitive method!
-    pub fn import_encrypted_secret_key(
-        &mut self,
-        encrypted_key_str: &str,
-        is_changed: bool,
-    ) -> Result<(), Error> {
-        self.clear();
-        self.encrypted_secret_key =
-            Some(hex::decode(encrypted_key_str).map_err(|_e| Error::KeyInvalidEncrypted)?);
-        self.has_unsaved_change = is_changed;
-        Ok(())
+    /// True if the active identity has an encrypted mnemonic that can be revealed
+    pub fn has_stored_mnemonic(&self) -> bool {
+        self.active_identity().encrypted_mnemonic.is_some()
     }
 
-    /// Try to decrypt the already loaded encrypted key using the decryption password
+    /// Warning: Security-sensitive method!
+    /// Decrypt the stored mnemonic with the password, for showing it.
+    /// Once shown, the key is considered to be handled insecurely.
     /// It is recommend to zeroize() the password after use.
-    pub fn decrypt_secret_key(&mut self, password: &str) -> Result<(), Error> {
-        let sk_bytes = match &self.encrypted_secret_key {
-            None => return Err(Error::KeyNotSet),
+    pub fn reveal_mnemonic(&mut self, password: &str) -> Result<(), Error> {
+        let encrypted = match &self.active_identity().encrypted_mnemonic {
+            None => return Err(Error::MnemonicNotStored),
             Some(d) => d,
         };
-        let sk = Encrypt::decrypt_key(&sk_bytes, &password)?;
-        self.import_secret_key(&sk.to_bech32()?, false)
-    }
-
-    /// Warning: Security-sensitive method!
-    /// Import secret key from BIP39 mnemonic
-    pub fn import_mnemonic(&mut self, mnemonic: &str, is_changed: bool) -> Result<(), Error> {
-        let mnemonic = bip39::Mnemonic::parse(mnemonic)?;
-        let password = "".to_string();
-        let seed = mnemonic.to_seed(password);
-        let account = 0;
-        let derivation_path = format!("m/44'/1237'/{account}'/0/0");
-        let child_xprv = bip32::XPrv::derive_from_path(&seed, &derivation_path.parse()?)?;
-        let private_key = child_xprv.private_key();
-        let secret_key = SecretKey::from_slice(&private_key.to_bytes())?;
-        self.clear();
-        self.keys = Some(Keys::new(secret_key));
-        self.has_unsaved_change = is_changed;
+        let (mut data, _key_security) = Encrypt::decrypt_data(encrypted, password)?;
+        let mnemonic = serde_json::from_slice::<StoredMnemonic>(&data);
+        data.zeroize();
+        self.revealed_mnemonic = Some(mnemonic?);
+        self.active_identity_mut().key_security = KeySecurity::Weak;
         Ok(())
     }
 
     /// Warning: Security-sensitive method!
-    /// Save secret key to file.
-    pub fn save_encrypted_secret_key(&self) -> Result<(), Error> {
-        let sk = self.get_secret_key()?;
-
-        if self.save_password_input != self.save_repeat_password_input {
-            return Err(Error::KeyEncryptionPasswordMismatch);
-        }
-        let password = &self.save_password_input;
-
-        Storage::check_create_folder()?;
-        let data = Encrypt::encrypt_key(&sk, &password, Encrypt::default_log2_rounds())?;
-        let hex_string = hex::encode(data);
-        let path = Storage::encrypted_secret_key_file();
-        // create empty file
-        fs::write(path.as_path(), "")?;
-        // set permissions, TODO make it on non-unix as well
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            fs::set_permissions(path.as_path(), fs::Permissions::from_mode(0o600))?;
-        }
-        // write contents
-        fs::write(path.as_path(), hex_string.to_string())?;
-
-        Ok(())
+    /// The revealed mnemonic, if revealed
+    pub fn get_revealed_mnemonic(&self) -> &Option<StoredMnemonic> {
+        &self.revealed_mnemonic
     }
 
-    /// Save public key to file.
-    pub fn save_public_key(&self) -> Result<(), Error> {
-        let pubkey = self.get_public_key()?;
-        Storage::check_create_folder()?;
-        let npub_string = pubkey.to_bech32()?;
-        fs::write(Storage::public_key_file(), npub_string)?;
-        Ok(())
+    /// Hide the revealed mnemonic
+    pub fn hide_mnemonic(&mut self) {
+        self.revealed_mnemonic = None;
     }
 
     /// Warning: Security-sensitive method!
-    /// Save public/secret key to file(s).
-    /// Returns if secret key has been saved
-    pub fn save_keys(&self) -> Result<bool, Error> {
-        if !self.has_unsaved_change {
-            return Err(Error::KeyNoChangeToSave);
-        }
-        // save public key
-        self.save_public_key()?;
-        // save secret key if set
-        if self.is_secret_key_set() {
-            self.save_encrypted_secret_key()?;
-            Ok(true)
+    /// Action to reveal the stored mnemonic, password has to be re-entered
+    pub fn reveal_mnemonic_action(
+        &mut self,
+        security_settings: &SecuritySettings,
+        status: &mut StatusMessages,
+    ) {
+        let res = if security_settings.security_level == SecurityLevel::PersistMandatoryPassword
+            && self.reveal_mnemonic_password_input.is_empty()
+        {
+            Err(Error::KeyEncryptionPasswordMissing)
         } else {
-            Ok(false)
+            self.reveal_mnemonic(&self.reveal_mnemonic_password_input.to_zeroizing())
+        };
+        match res {
+            Err(e) => status.set_error(&format!(
+                "Could not reveal mnemonic, check password! ({})",
+                e
+            )),
+            Ok(_) => status.set("Mnemonic revealed, hide it when done"),
         }
+        // cleanup
+        self.reveal_mnemonic_password_input.clear();
     }
 
     /// Warning: Security-sensitive method!
-    /// Load secret key from file
-    pub fn load_secret_key(&mut self) -> Result<(), Error> {
-        let sk_hex = fs::read_to_string(Storage::encrypted_secret_key_file())?;
-        self.import_encrypted_secret_key(&sk_hex, false)?;
-        // Also try to decrypt with empty password, set it if successful, ignore if not
-        let _ret = self.decrypt_secret_key("");
-        Ok(())
+    /// Import secret key from NIP-49 encrypted 'ncryptsec' string
+    pub fn import_ncryptsec_action(&mut self, status: &mut StatusMessages) {
+        match self.import_ncryptsec(
+            &self.ncryptsec_input.clone(),
+            &self.ncryptsec_password_input.to_zeroizing(),
+            true,
+        ) {
+            Err(e) => status.set_error(&format!(
+                "Error importing ncryptsec, check password! ({})",
+                e.to_string()
+            )),
+            Ok(_) => status.set("Secret key imported from ncryptsec"),
+        };
+        // cleanup
+        self.ncryptsec_input = String::new();
+        self.ncryptsec_password_input.clear();
     }
 
-    /// Load public key from file
-    pub fn load_public_key(&mut self) -> Result<(), Error> {
-        let pk_string = fs::read_to_string(Storage::public_key_file())?;
-        self.import_public_key(&pk_string)?;
-        Ok(())
+    /// Warning: Security-sensitive method!
+    /// Action to restore a previous version of the saved key
+    pub fn restore_key_version_action(&mut self, version: u8, status: &mut StatusMessages) {
+        match self.restore_key_version(version) {
+            Err(e) => status.set_error(&format!("Could not restore key version ({})", e)),
+            Ok(_) => status.set(&format!(
+                "Key version {} restored (the replaced key, if any, is kept as version 1), unlock it with its password",
+                version
+            )),
+        }
     }
 
     /// Warning: Security-sensitive method!
-    /// Load public/secret key from file
-    pub fn load_keys(&mut self) -> Result<(), Error> {
-        let secret_path = Storage::encrypted_secret_key_file();
-        if secret_path.as_path().is_file() {
-            // secret key file exists, load secret key
-            self.load_secret_key()
+    /// Import a key from a key file, with format detection
+    pub fn import_key_file_action(&mut self, status: &mut StatusMessages) {
+        let path = self.import_file_path_input.trim().to_string();
+        let res = if path.is_empty() {
+            Err(Error::ImportFilePathMissing)
         } else {
-            // load public key
-            self.load_public_key()
-        }
+            self.import_key_file(
+                &PathBuf::from(&path),
+                &self.import_file_password_input.to_zeroizing(),
+            )
+        };
+        match res {
+            Err(e) => status.set_error(&format!("Error importing key file, {}", e)),
+            Ok(format) => {
+                let key = if self.is_secret_key_set() {
+                    "Secret key"
+                } else {
+                    "Public key"
+                };
+                status.set(&format!("{} imported from key file ({})", key, format));
+                self.import_file_path_input = String::new();
+            }
+        };
+        // cleanup
+        self.import_file_password_input.clear();
     }
 
     /// Warning: Security-sensitive method!
-    ///.Action to save secret key to file
-    pub fn save_action(
+    /// Export secret key as NIP-49 encrypted 'ncryptsec' string, with the configured scrypt cost
+    pub fn export_ncryptsec_action(
         &mut self,
         security_settings: &SecuritySettings,
         status: &mut StatusMessages,
     ) {
-        let res = if !security_settings.allows_persist() {
-            Err(Error::KeySaveNotAllowed)
+        let res = if self.ncryptsec_password_input.is_empty() {
+            Err(Error::KeyEncryptionPasswordMissing)
         } else {
-            if security_settings.security_level == SecurityLevel::PersistMandatoryPassword
-                && self.save_password_input.is_empty()
-            {
-                Err(Error::KeyEncryptionPasswordMissing)
-            } else {
-                self.save_keys()
-            }
+            self.export_ncryptsec(
+                self.ncryptsec_password_input.as_str(),
+                security_settings.kdf_log2_rounds,
+            )
         };
         match res {
             Err(e) => status.set_error_err(&e),
-            Ok(ss) => {
-                if ss {
-                    // Clear password input
-                    self.save_password_input = "".to_string();
-                    self.save_repeat_password_input = "".to_string();
-                    status.set("Secret key persisted to storage");
-                } else {
-                    status.set("Public key persisted to storage");
-                }
+            Ok(ncryptsec) => {
+                self.ncryptsec_output = ncryptsec;
+                status.set("Secret key exported as ncryptsec");
             }
         }
+        // cleanup
+        self.ncryptsec_password_input.clear();
     }
 
-    ///.Action to load secret key from file
-    pub fn load_action(
+    /// Warning: Security-sensitive method!
+    /// Action to add a sub-key with the label from the input.
+    /// If the keys are already saved, the sub-key list is saved too.
+    pub fn add_subkey_action(
         &mut self,
         security_settings: &SecuritySettings,
         status: &mut StatusMessages,
     ) {
-        // TODO confirmation
-        let res = if !security_settings.allows_persist() {
-            Err(Error::KeyLoadNotAllowed)
-        } else {
-            self.load_keys()
-        };
-        if let Err(e) = res {
-            status.set_error_err(&e);
-        } else {
-            status.set("Keys loaded from storage (may need decryption with password)");
+        let label = self.subkey_label_input.trim().to_string();
+        match self.add_subkey(&label) {
+            Err(e) => status.set_error_err(&e),
+            Ok(_) => {
+                self.subkey_label_input = String::new();
+                self.on_subkeys_changed(security_settings, status);
+                status.set(&format!("Sub-key '{}' added", label));
+            }
         }
     }
 
-    pub fn unlock_secret_key_action(
+    pub fn remove_subkey_action(
         &mut self,
+        index: usize,
         security_settings: &SecuritySettings,
         status: &mut StatusMessages,
     ) {
-        // check if password is set if needed
-        let res = if security_settings.security_level == SecurityLevel::PersistMandatoryPassword
-            && self.decrypt_password_input.is_empty()
+        match self.remove_subkey(index) {
+            Err(e) => status.set_error_err(&e),
+            Ok(_) => {
+                self.on_subkeys_changed(security_settings, status);
+                status.set("Sub-key removed");
+            }
+        }
+    }
+
+    /// Save the sub-key list if the keys are saved; otherwise it is saved with the keys
+    fn on_subkeys_changed(
+        &mut self,
+        security_settings: &SecuritySettings,
+        status: &mut StatusMessages,
+    ) {
+        if security_settings.allows_persist()
+            && self.active_identity().encrypted_secret_key.is_some()
         {
-            Err(Error::KeyEncryptionPasswordMissing)
+            if let Err(e) = self.save_subkeys() {
+                status.set_error(&format!("Could not save sub-keys ({})", e));
+            }
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Action to export the secret key of a sub-key, shown until hidden
+    pub fn export_subkey_action(&mut self, index: usize, status: &mut StatusMessages) {
+        match self.export_subkey_nsec(index) {
+            Err(e) => status.set_error_err(&e),
+            Ok(nsec) => {
+                let label = self.active_identity().subkeys[index].label.clone();
+                status.set(&format!("Secret key of sub-key '{}' exported", label));
+                self.subkey_nsec_output = Some((label, nsec));
+            }
+        }
+    }
+
+    /// Hide the exported sub-key nsec
+    pub fn hide_subkey_nsec(&mut self) {
+        self.subkey_nsec_output = None;
+    }
+
+    /// Sub-keys of the active identity
+    pub fn get_subkeys(&self) -> &Vec<SubKey> {
+        &self.active_identity().subkeys
+    }
+
+    /// Warning: Security-sensitive method!
+    /// The exported sub-key label and nsec, if any
+    pub fn get_subkey_nsec(&self) -> Option<&(String, Zeroizing<String>)> {
+        self.subkey_nsec_output.as_ref()
+    }
+
+    /// True if sub-keys can be added or exported (the mnemonic is available)
+    pub fn can_derive_subkeys(&self) -> bool {
+        self.available_mnemonic().is_some()
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Action to write a paper backup to the file from the input
+    pub fn paper_backup_action(&mut self, with_mnemonic: bool, status: &mut StatusMessages) {
+        let path = self.paper_backup_path_input.trim().to_string();
+        let res = if path.is_empty() {
+            Err(Error::PaperBackupPathMissing)
         } else {
-            self.decrypt_secret_key(&self.decrypt_password_input.clone())
+            self.export_paper_backup(&PathBuf::from(&path), with_mnemonic)
         };
         match res {
-            Err(e) => status.set(&format!(
-                "Could not decrypt secret key, check password! ({})",
-                e
+            Err(e) => status.set_error(&format!("Could not write paper backup ({})", e)),
+            Ok(_) => status.set(&format!(
+                "Paper backup written to '{}', print it and delete the file",
+                path
             )),
-            Ok(_) => {
-                // cleanup
-                self.decrypt_password_input = "".to_string();
-                status.set("Secret key decrypted")
-            }
         }
     }
 
     /// Warning: Security-sensitive method!
-    /// Import secret key, in 'nsec' bech32 or hex format (pubkey is derived from it)
-    pub fn import_secret_key_action(&mut self, status: &mut StatusMessages) {
-        match self.import_secret_key(&self.secret_key_input.clone(), true) {
-            Err(e) => status.set_error(&format!("Error importing, {}", e.to_string())),
-            Ok(_) => status.set("Secret key imported"),
+    /// Action to split the secret key into Shamir shares
+    pub fn shamir_split_action(&mut self, status: &mut StatusMessages) {
+        let res = match (
+            self.shamir_threshold_input.trim().parse::<u8>(),
+            self.shamir_count_input.trim().parse::<u8>(),
+        ) {
+            (Ok(threshold), Ok(count)) => self.split_secret_key(threshold, count),
+            _ => Err(Error::ShamirInvalidParameters),
         };
-        // cleanup
-        self.secret_key_input = String::new();
+        match res {
+            Err(e) => status.set_error_err(&e),
+            Ok(_) => status.set(&format!(
+                "Created {} shares, any {} of them restore the key. Store them separately, then hide them!",
+                self.shamir_shares.len(),
+                self.shamir_threshold_input.trim()
+            )),
+        }
     }
 
     /// Warning: Security-sensitive method!
-    /// Import secret key from BIP39 mnemonic
-    pub fn import_mnemonic_action(&mut self, status: &mut StatusMessages) {
-        match self.import_mnemonic(&self.mnemonic_input.clone(), true) {
+    /// Action to save the created Shamir shares to the folder from the input
+    pub fn shamir_save_action(&mut self, status: &mut StatusMessages) {
+        if self.shamir_shares.is_empty() {
+            status.set_error("Create the shares first");
+            return;
+        }
+        let folder = PathBuf::from(self.shamir_folder_input.trim());
+        match self.save_shamir_shares(&folder) {
+            Err(e) => status.set_error(&format!("Could not save shares ({})", e)),
+            Ok(count) => status.set(&format!(
+                "Saved {} shares to '{}', move them to separate places",
+                count,
+                folder.display()
+            )),
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Action to restore a secret key from the Shamir shares input; it has to be accepted after
+    pub fn shamir_restore_action(&mut self, status: &mut StatusMessages) {
+        match Self::restore_from_shares(self.shamir_restore_input.as_str()) {
             Err(e) => {
-                status.set_error(&format!("Error importing from mnemonic, {}", e.to_string()))
+                self.shamir_restored = None;
+                status.set_error(&format!("Could not restore from shares, {}", e))
             }
-            Ok(_) => status.set("Secret key imported from mnemonic"),
+            Ok(secret_key) => {
+                self.shamir_restored = Some(secret_key);
+                self.shamir_restore_input.clear();
+                status.set("Key restored from shares, check its npub, then accept it");
+            }
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Action to use the secret key restored from Shamir shares
+    pub fn shamir_accept_action(&mut self, status: &mut StatusMessages) {
+        match self.accept_restored_secret_key() {
+            Err(e) => status.set_error_err(&e),
+            Ok(_) => status.set("Secret key restored from shares"),
+        }
+    }
+
+    /// Hide the created Shamir shares, and forget a restored but not accepted key
+    pub fn shamir_hide(&mut self) {
+        self.shamir_shares = Vec::new();
+        self.shamir_restored = None;
+    }
+
+    /// Warning: Security-sensitive method!
+    /// The created Shamir shares, empty if none
+    pub fn get_shamir_shares(&self) -> &Vec<Zeroizing<String>> {
+        &self.shamir_shares
+    }
+
+    /// The npub of the secret key restored from Shamir shares, if there is one to be accepted
+    pub fn get_shamir_restored_npub(&self) -> Option<String> {
+        self.shamir_restored
+            .as_ref()
+            .map(|secret_key| secret_key.public_key().to_bech32().unwrap_or_default())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Action to generate a FROST group key, with the participant count and threshold inputs
+    pub fn generate_frost_action(&mut self, status: &mut StatusMessages) {
+        let res = match (
+            self.frost_threshold_input.trim().parse::<u8>(),
+            self.frost_count_input.trim().parse::<u8>(),
+        ) {
+            (Ok(threshold), Ok(count)) => self.generate_frost(threshold, count),
+            _ => Err(Error::FrostInvalidParameters),
         };
-        // cleanup
-        self.mnemonic_input = String::new();
+        match res {
+            Err(e) => status.set_error_err(&e),
+            Ok(_) => status.set(&format!(
+                "FROST {}-of-{} group key generated, save it to keep the key shares",
+                self.frost_threshold_input.trim(),
+                self.frost_count_input.trim()
+            )),
+        }
+    }
+
+    /// True if the key of the active identity is a FROST group key
+    pub fn is_frost_key(&self) -> bool {
+        let identity = self.active_identity();
+        identity.frost_shares.is_some() || !identity.encrypted_frost_shares.is_empty()
+    }
+
+    /// FROST group key of the active identity, if unlocked: the threshold, the participant count,
+    /// and the indexes of the key shares held locally
+    pub fn get_frost_info(&self) -> Option<(u8, u8, Vec<u8>)> {
+        self.active_identity()
+            .frost_shares
+            .as_ref()
+            .and_then(|shares| {
+                shares.first().map(|first| {
+                    (
+                        first.threshold(),
+                        first.count(),
+                        shares.iter().map(|share| share.index()).collect(),
+                    )
+                })
+            })
+    }
+
+    /// Exported NIP-49 'ncryptsec' string, empty if not exported
+    pub fn get_ncryptsec(&self) -> &str {
+        &self.ncryptsec_output
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Show or hide the secret key. Once shown, the key is considered to be handled insecurely.
+    pub fn toggle_hide_secret_key(&mut self) {
+        self.hide_secret_key = !self.hide_secret_key;
+        if !self.hide_secret_key && self.is_secret_key_set() {
+            self.active_identity_mut().key_security = KeySecurity::Weak;
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Lock all identities whose secret key is unlocked and saved: forget the decrypted keys,
+    /// so the password is needed again. Returns the number of identities locked.
+    pub fn lock_all(&mut self) -> usize {
+        let count = self
+            .identities
+            .iter_mut()
+            .map(|i| i.lock())
+            .filter(|locked| *locked)
+            .count();
+        if count > 0 {
+            self.revealed_mnemonic = None;
+            self.shamir_hide();
+            self.subkey_nsec_output = None;
+            self.hide_secret_key = true;
+        }
+        count
+    }
+
+    /// True if any identity has an unlocked secret key that can be locked
+    pub fn can_lock(&self) -> bool {
+        self.identities.iter().any(|i| i.can_lock())
+    }
+
+    /// True if the active identity has an encrypted secret key (or encrypted FROST key shares)
+    /// that is not unlocked
+    pub fn is_locked(&self) -> bool {
+        let identity = self.active_identity();
+        (self.is_encrypted_secret_key_set() && !self.is_secret_key_set())
+            || (!identity.encrypted_frost_shares.is_empty() && identity.frost_shares.is_none())
+    }
+
+    /// Action to lock the secret keys (see lock_all())
+    pub fn lock_action(&mut self, status: &mut StatusMessages) {
+        let has_unsaved = self
+            .identities
+            .iter()
+            .any(|i| i.has_unsaved_change && i.public_key.is_some());
+        match self.lock_all() {
+            0 => status.set_error("No saved secret key to lock"),
+            count => {
+                if has_unsaved {
+                    status.set(&format!(
+                        "Locked {} secret key(s); keys with unsaved changes are not locked",
+                        count
+                    ));
+                } else {
+                    status.set(&format!("Locked {} secret key(s)", count));
+                }
+            }
+        }
     }
 
     pub fn get_signer(&self) -> Result<KeySigner, Error> {
-        Ok(KeySigner {
-            keys: self.get_keys()?.clone(),
-        })
+        match &self.active_identity().frost_shares {
+            Some(shares) => Ok(KeySigner::new_frost(shares.clone(), self.get_public_key()?)),
+            None => Ok(KeySigner::new(self.get_secret()?.clone())),
+        }
+    }
+
+    /// True if the active identity can sign: its secret key or FROST key shares are unlocked
+    pub fn can_sign(&self) -> bool {
+        self.is_secret_key_set() || self.active_identity().frost_shares.is_some()
     }
 
     pub fn keys_is_set(&self) -> bool {
-        self.keys.is_some()
+        self.active_identity().public_key.is_some()
+    }
+
+    /// True if any identity of the keyring has keys set
+    #[cfg(test)]
+    pub fn any_keys_set(&self) -> bool {
+        self.identities.iter().any(|i| i.public_key.is_some())
+    }
+
+    /// True if the active identity has changes not yet persisted
+    pub fn has_unsaved_change(&self) -> bool {
+        self.active_identity().has_unsaved_change
+    }
+
+    /// True if any identity of the keyring has changes not yet persisted
+    pub fn any_unsaved_change(&self) -> bool {
+        self.identities.iter().any(|i| i.has_unsaved_change)
     }
 
     #[cfg(test)]
@@ -326,28 +2169,23 @@ impl Keystore {
     }
 
     pub fn is_secret_key_set(&self) -> bool {
-        self.get_secret_key().is_ok()
+        self.active_identity().secret_key.is_some()
     }
 
     pub fn is_encrypted_secret_key_set(&self) -> bool {
-        self.encrypted_secret_key.is_some()
-    }
-
-    /// Warning: Security-sensitive method!
-    pub(crate) fn get_keys(&self) -> Result<&Keys, Error> {
-        match &self.keys {
-            None => Err(Error::KeyNotSet),
-            Some(kk) => Ok(kk),
-        }
+        self.active_identity().encrypted_secret_key.is_some()
     }
 
     pub fn get_public_key(&self) -> Result<XOnlyPublicKey, Error> {
-        Ok(self.get_keys()?.public_key())
+        self.active_identity().public_key.ok_or(Error::KeyNotSet)
     }
 
     /// Warning: Security-sensitive method!
-    fn get_secret_key(&self) -> Result<SecretKey, Error> {
-        Ok(self.get_keys()?.secret_key()?)
+    fn get_secret(&self) -> Result<&Arc<SecretKeyHolder>, Error> {
+        match &self.active_identity().secret_key {
+            None => Err(Error::KeyNotSet),
+            Some(secret) => Ok(secret),
+        }
     }
 
     pub fn get_npub(&self) -> String {
@@ -362,15 +2200,15 @@ impl Keystore {
 
     /// Warning: Security-sensitive method!
     /// Return secret key as nsec string, if set, and if Hide option is not active.
-    pub fn get_nsec(&self) -> String {
-        match self.get_secret_key() {
-            Err(_) => "(not set)".to_string(),
-            Ok(key) => {
+    pub fn get_nsec(&self) -> Zeroizing<String> {
+        match self.get_secret() {
+            Err(_) => Zeroizing::new("(not set)".to_string()),
+            Ok(secret) => {
                 if self.hide_secret_key {
-                    "".to_string()
+                    Zeroizing::new(String::new())
                 } else {
-                    match key.to_bech32() {
-                        Err(_) => "(conversion error)".to_string(),
+                    match secret.to_nsec() {
+                        Err(_) => Zeroizing::new("(conversion error)".to_string()),
                         Ok(s) => s,
                     }
                 }
@@ -379,27 +2217,94 @@ impl Keystore {
     }
 }
 
-/// Can sign a hash with its own secret key
+/// What a KeySigner signs with
+#[derive(Clone)]
+enum SignerBackend {
+    /// Own secret key
+    Key(Arc<SecretKeyHolder>),
+    /// FROST key shares (at least threshold many) of the group public key
+    Frost(Arc<Vec<FrostKeyShare>>, XOnlyPublicKey),
+}
+
+/// Can sign a hash with its own secret key, or with FROST key shares held locally.
+/// Clones share the same secret (it is not copied), it is zeroized when the last one is dropped.
 #[derive(Clone)]
 pub(crate) struct KeySigner {
-    pub keys: Keys,
+    backend: SignerBackend,
 }
 
-impl KeySigner {
+impl KeySigner {
+    pub fn new(secret: Arc<SecretKeyHolder>) -> Self {
+        Self {
+            backend: SignerBackend::Key(secret),
+        }
+    }
+
+    /// Signer for a FROST group key, signing by a local ceremony of the key shares
+    pub fn new_frost(shares: Arc<Vec<FrostKeyShare>>, public_key: XOnlyPublicKey) -> Self {
+        Self {
+            backend: SignerBackend::Frost(shares, public_key),
+        }
+    }
+
     pub fn get_public_key(&self) -> XOnlyPublicKey {
-        self.keys.public_key()
+        match &self.backend {
+            SignerBackend::Key(secret) => secret.public_key(),
+            SignerBackend::Frost(_shares, public_key) => *public_key,
+        }
     }
 
-    /// Perform signing of a hash using loaded secret key
+    /// Perform signing of a hash using loaded secret key, or FROST key shares.
+    /// The result is a normal BIP-340 Schnorr signature in both cases.
     pub fn sign(&self, hash: Vec<u8>) -> Result<Signature, Error> {
-        let kp = self.keys.key_pair()?;
-        Ok(SECP256K1.sign_schnorr(&nostr::secp256k1::Message::from_slice(&hash)?, &kp))
+        match &self.backend {
+            SignerBackend::Key(secret) => secret.sign_schnorr(&hash),
+            SignerBackend::Frost(shares, _public_key) => {
+                let message = Message::from_slice(&hash)?;
+                let threshold = shares
+                    .first()
+                    .map(|share| share.threshold() as usize)
+                    .unwrap_or_default();
+                let signers: Vec<&FrostKeyShare> = shares.iter().take(threshold).collect();
+                let signature = Frost::sign_local(&signers, message.as_ref())?;
+                Ok(Signature::from_slice(&signature)?)
+            }
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Call f with the keys, for nostr operations that need them (e.g. delegation).
+    /// Not possible for a FROST group key.
+    pub fn with_keys<R>(&self, f: impl FnOnce(&Keys) -> R) -> Result<R, Error> {
+        match &self.backend {
+            SignerBackend::Key(secret) => secret.with_keys(f),
+            SignerBackend::Frost(..) => Err(Error::FrostNoSingleKey),
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::base::storage_backend::memory::MemoryStorage;
+    use crate::model::security_settings::{KdfKind, SECURITY_LEVELS};
+    use bech32::FromBase32;
+    use std::fs;
+
+    const NSEC: &str = "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae";
+    const NPUB: &str = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+
+    fn security_settings(level: SecurityLevel) -> SecuritySettings {
+        let mut settings = SecuritySettings::default();
+        settings.security_level = level;
+        // fast, for tests
+        settings.kdf_log2_rounds = Encrypt::min_log2_rounds();
+        settings
+    }
+
+    fn error_status(e: Error) -> String {
+        format!("Error: {}!", e)
+    }
 
     #[test]
     fn test_new() {
@@ -407,8 +2312,8 @@ mod test {
         assert_eq!(k.is_public_key_set(), false);
         assert_eq!(k.is_secret_key_set(), false);
         assert_eq!(k.get_npub(), "(not set)");
-        assert_eq!(k.get_nsec(), "(not set)");
-        assert!(k.get_keys().is_err());
+        assert_eq!(k.get_nsec().as_str(), "(not set)");
+        assert!(k.get_signer().is_err());
     }
 
     #[test]
@@ -420,24 +2325,26 @@ mod test {
         assert!(k.get_npub().len() > 60);
         k.hide_secret_key = false;
         assert!(k.get_nsec().len() > 60);
-        assert!(k.get_keys().is_ok());
+        assert!(k.get_signer().is_ok());
         assert_eq!(
-            k.get_keys().unwrap().public_key().to_bech32().unwrap(),
+            k.get_signer()
+                .unwrap()
+                .get_public_key()
+                .to_bech32()
+                .unwrap(),
             k.get_npub()
         );
         assert_eq!(
-            k.get_keys()
+            k.get_secret()
                 .unwrap()
-                .secret_key()
-                .unwrap()
-                .to_bech32()
+                .with_secret_key(|sk| sk.to_bech32().unwrap())
                 .unwrap(),
-            k.get_nsec()
+            k.get_nsec().as_str()
         );
 
         // test hide option
         k.hide_secret_key = true;
-        assert_eq!(k.get_nsec(), "".to_string());
+        assert_eq!(k.get_nsec().as_str(), "");
     }
 
     #[test]
@@ -457,7 +2364,7 @@ mod test {
         );
         k.hide_secret_key = false;
         assert_eq!(
-            k.get_nsec(),
+            k.get_nsec().as_str(),
             "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"
         );
     }
@@ -473,7 +2380,7 @@ mod test {
             .unwrap();
         k.hide_secret_key = false;
         assert_eq!(
-            k.get_nsec(),
+            k.get_nsec().as_str(),
             "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"
         );
     }
@@ -526,7 +2433,12 @@ mod test {
     fn test_import_mnemonic() {
         let mut k = Keystore::new();
         let _res = k
-            .import_mnemonic("oil oil oil oil oil oil oil oil oil oil oil oil", true)
+            .import_mnemonic(
+                "oil oil oil oil oil oil oil oil oil oil oil oil",
+                "",
+                0,
+                true,
+            )
             .unwrap();
         assert!(k.is_public_key_set());
         assert!(k.is_secret_key_set());
@@ -536,8 +2448,929 @@ mod test {
         );
         k.hide_secret_key = false;
         assert_eq!(
-            k.get_nsec(),
+            k.get_nsec().as_str(),
             "nsec16awa8nftexjs4nk8zfl5wrrtc6a7hhycj7p8ztlf4dfy5xa9dcnslmfkz5"
         );
     }
+
+    #[test]
+    fn test_import_mnemonic_passphrase_account() {
+        let mnemonic = "oil oil oil oil oil oil oil oil oil oil oil oil";
+        let preview = Keystore::preview_mnemonic_accounts(mnemonic, "", 3).unwrap();
+        assert_eq!(preview.len(), 3);
+        assert_eq!(
+            preview[0],
+            (
+                0,
+                "npub1tczgvlwvcdxp5f4mp8rqehramx6dqemq6v8egf3qdfzazn8cs7dqlhmwux".to_string()
+            )
+        );
+
+        let mut k = Keystore::new();
+        k.import_mnemonic(mnemonic, "", 2, true).unwrap();
+        assert_eq!(k.get_npub(), preview[2].1);
+
+        // passphrase changes the key
+        k.import_mnemonic(mnemonic, "25th word", 0, true).unwrap();
+        assert_ne!(k.get_npub(), preview[0].1);
+        let preview_pp = Keystore::preview_mnemonic_accounts(mnemonic, "25th word", 1).unwrap();
+        assert_eq!(k.get_npub(), preview_pp[0].1);
+    }
+
+    #[test]
+    fn test_import_mnemonic_keeps_mnemonic() {
+        let mut k = Keystore::new();
+        k.import_mnemonic(
+            "oil oil oil oil oil oil oil oil oil oil oil oil",
+            "25th word",
+            3,
+            true,
+        )
+        .unwrap();
+        let mnemonic = k.active_identity().mnemonic.as_ref().unwrap();
+        assert_eq!(
+            mnemonic.mnemonic,
+            "oil oil oil oil oil oil oil oil oil oil oil oil"
+        );
+        assert_eq!(mnemonic.account, 3);
+        assert_eq!(mnemonic.derivation_path, "m/44'/1237'/3'/0/0");
+        assert!(mnemonic.has_passphrase);
+
+        // other import drops it
+        k.generate();
+        k.import_secret_key(
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+            true,
+        )
+        .unwrap();
+        assert!(k.active_identity().mnemonic.is_none());
+    }
+
+    #[test]
+    fn test_reveal_mnemonic() {
+        let mut k = Keystore::new();
+        assert!(k.reveal_mnemonic("password").is_err());
+
+        let stored = StoredMnemonic {
+            mnemonic: "oil oil oil oil oil oil oil oil oil oil oil oil".to_string(),
+            account: 0,
+            derivation_path: Nip06::derivation_path(0),
+            has_passphrase: false,
+        };
+        let serialized = serde_json::to_string(&stored).unwrap();
+        k.active_identity_mut().encrypted_mnemonic = Some(
+            Encrypt::encrypt_data(
+                serialized.as_bytes(),
+                "password",
+                &KdfParams::Scrypt { log2_rounds: 13 },
+                KeySecurity::Medium,
+            )
+            .unwrap(),
+        );
+        assert!(k.has_stored_mnemonic());
+        assert!(k.reveal_mnemonic("wrong").is_err());
+        assert!(k.get_revealed_mnemonic().is_none());
+
+        k.reveal_mnemonic("password").unwrap();
+        assert_eq!(
+            k.get_revealed_mnemonic().as_ref().unwrap().mnemonic,
+            stored.mnemonic
+        );
+        assert_eq!(k.active_identity().key_security, KeySecurity::Weak);
+        k.hide_mnemonic();
+        assert!(k.get_revealed_mnemonic().is_none());
+    }
+
+    #[test]
+    fn test_lock_unlock() {
+        let mut k = Keystore::new();
+        k.generate();
+        let npub = k.get_npub();
+        // not saved, cannot be locked
+        assert!(!k.can_lock());
+        assert_eq!(k.lock_all(), 0);
+        assert!(k.is_secret_key_set());
+
+        let encrypted = k
+            .get_secret()
+            .unwrap()
+            .with_secret_key(|sk| Encrypt::encrypt_key(sk, "password", 13, KeySecurity::Medium))
+            .unwrap()
+            .unwrap();
+        k.import_encrypted_secret_key(&hex::encode(encrypted), false)
+            .unwrap();
+        assert!(k.is_locked());
+        k.decrypt_secret_key("password").unwrap();
+        assert!(!k.is_locked());
+        assert!(k.get_signer().is_ok());
+        assert!(k.can_lock());
+
+        assert_eq!(k.lock_all(), 1);
+        assert!(k.is_locked());
+        assert!(!k.is_secret_key_set());
+        assert!(k.get_signer().is_err());
+        // public key is kept
+        assert_eq!(k.get_npub(), npub);
+
+        assert!(k.decrypt_secret_key("wrong").is_err());
+        k.active_identity_mut().subkeys.push(SubKey {
+            label: "app".to_string(),
+            account: 1,
+            npub: npub.clone(),
+        });
+        k.decrypt_secret_key("password").unwrap();
+        assert!(k.is_secret_key_set());
+        assert_eq!(k.get_npub(), npub);
+        // sub-keys are kept
+        assert_eq!(k.get_subkeys().len(), 1);
+    }
+
+    #[test]
+    fn test_frost() {
+        let mut k = Keystore::new();
+        assert!(k.generate_frost(3, 2).is_err());
+        k.generate_frost(2, 3).unwrap();
+        assert!(k.is_frost_key());
+        assert!(k.has_unsaved_change());
+        assert!(!k.is_secret_key_set());
+        assert!(k.can_sign());
+        assert_eq!(k.get_frost_info(), Some((2, 3, vec![1, 2, 3])));
+        let public_key = k.get_public_key().unwrap();
+
+        // signature of the group key, verifiable as usual
+        let hash = [7u8; 32];
+        let signer = k.get_signer().unwrap();
+        assert_eq!(signer.get_public_key(), public_key);
+        let signature = signer.sign(hash.to_vec()).unwrap();
+        SECP256K1
+            .verify_schnorr(
+                &signature,
+                &Message::from_slice(&hash).unwrap(),
+                &public_key,
+            )
+            .unwrap();
+        assert!(signer.sign(vec![1u8; 5]).is_err());
+        assert!(matches!(
+            signer.with_keys(|_keys| ()),
+            Err(Error::FrostNoSingleKey)
+        ));
+
+        // as saved: shares encrypted one by one, then lock and unlock
+        let encrypted = k
+            .encrypt_frost_shares("password", &KdfParams::Scrypt { log2_rounds: 13 })
+            .unwrap();
+        assert_eq!(encrypted.len(), 3);
+        let identity = k.active_identity_mut();
+        identity.encrypted_frost_shares = encrypted.into_iter().map(|(_, data)| data).collect();
+        identity.has_unsaved_change = false;
+        assert!(k.can_lock());
+        assert_eq!(k.lock_all(), 1);
+        assert!(k.is_locked());
+        assert!(!k.can_sign());
+        assert!(k.get_signer().is_err());
+        assert!(k.decrypt_frost_shares("wrong").is_err());
+        k.decrypt_frost_shares("password").unwrap();
+        assert!(!k.is_locked());
+        assert_eq!(k.get_public_key().unwrap(), public_key);
+        let signature = k.get_signer().unwrap().sign(hash.to_vec()).unwrap();
+        SECP256K1
+            .verify_schnorr(
+                &signature,
+                &Message::from_slice(&hash).unwrap(),
+                &public_key,
+            )
+            .unwrap();
+
+        k.clear();
+        assert!(!k.is_frost_key());
+        assert!(k.get_frost_info().is_none());
+    }
+
+    #[test]
+    fn test_shamir_split_restore() {
+        let mut k = Keystore::new();
+        assert!(k.split_secret_key(2, 3).is_err());
+        k.import_secret_key(
+            "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae",
+            false,
+        )
+        .unwrap();
+        k.split_secret_key(2, 3).unwrap();
+        let shares: Vec<String> = k
+            .get_shamir_shares()
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(shares.len(), 3);
+
+        let mut other = Keystore::new();
+        other.generate();
+        other.split_secret_key(2, 3).unwrap();
+        let other_share = other.get_shamir_shares()[0].to_string();
+
+        let mut r = Keystore::new();
+        r.shamir_restore_input
+            .set(format!("{}, {}", shares[0], other_share));
+        r.shamir_restore_action(&mut StatusMessages::new());
+        assert!(r.get_shamir_restored_npub().is_none());
+        r.shamir_restore_input.set(shares[2].clone());
+        r.shamir_restore_action(&mut StatusMessages::new());
+        assert!(r.get_shamir_restored_npub().is_none());
+
+        r.shamir_restore_input
+            .set(format!("{}\n{}", shares[2], shares[0]));
+        r.shamir_restore_action(&mut StatusMessages::new());
+        assert_eq!(
+            r.get_shamir_restored_npub().unwrap(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+        assert!(!r.is_secret_key_set());
+        r.accept_restored_secret_key().unwrap();
+        assert_eq!(r.get_npub(), k.get_npub());
+        assert!(r.has_unsaved_change());
+        assert!(r.get_shamir_restored_npub().is_none());
+    }
+
+    #[test]
+    fn test_mnemonic_account_limit() {
+        let mnemonic =
+            "leader monkey parrot ring guide accident before fence cannon height naive bean";
+        let mut k = Keystore::new();
+        assert!(matches!(
+            k.import_mnemonic(mnemonic, "", u32::MAX, true),
+            Err(Error::MnemonicInvalidAccount)
+        ));
+
+        let mut status = StatusMessages::new();
+        k.mnemonic_input.set(mnemonic.to_string());
+        k.mnemonic_account_input = u32::MAX.to_string();
+        k.import_mnemonic_action(&mut status);
+        assert!(status.get_last().starts_with("Error: "));
+        assert!(!k.is_secret_key_set());
+
+        // no sub-key after the last account
+        k.import_mnemonic(mnemonic, "", MAX_ACCOUNT, true).unwrap();
+        assert!(matches!(
+            k.add_subkey("bot"),
+            Err(Error::MnemonicInvalidAccount)
+        ));
+    }
+
+    #[test]
+    fn test_change_password_unsaved() {
+        let mut k = Keystore::new();
+        k.generate();
+        assert!(matches!(
+            k.change_password("old", "new", None),
+            Err(Error::KeyUnsavedChange)
+        ));
+    }
+
+    #[test]
+    fn test_weak_stored_kdf() {
+        let mut k = Keystore::new();
+        let mut settings = SecuritySettings::default();
+        assert!(k.get_weak_stored_kdf(&settings).is_none());
+
+        let weak = KdfParams::Scrypt {
+            log2_rounds: settings.kdf_log2_rounds - 1,
+        };
+        k.active_identity_mut().stored_kdf = Some(weak);
+        assert_eq!(k.get_weak_stored_kdf(&settings), Some(weak));
+        k.active_identity_mut().stored_kdf = Some(settings.kdf_params());
+        assert!(k.get_weak_stored_kdf(&settings).is_none());
+
+        // scrypt is weak when Argon2id is set
+        settings.kdf = KdfKind::Argon2id;
+        assert!(k.get_weak_stored_kdf(&settings).is_some());
+        k.active_identity_mut().stored_kdf = Some(KdfParams::default_argon2id());
+        assert!(k.get_weak_stored_kdf(&settings).is_none());
+
+        // not tracked after the key changes
+        k.active_identity_mut().stored_kdf = Some(weak);
+        k.generate();
+        k.clear();
+        assert!(k.get_weak_stored_kdf(&settings).is_none());
+    }
+
+    #[test]
+    fn test_import_ncryptsec() {
+        let mut k = Keystore::new();
+        // Test vector from the NIP-49 spec
+        k.import_ncryptsec("ncryptsec1qgg9947rlpvqu76pj5ecreduf9jxhselq2nae2kghhvd5g7dgjtcxfqtd67p9m0w57lspw8gsq6yphnm8623nsl8xn9j4jdzz84zm3frztj3z7s35vpzmqf6ksu8r89qk5z2zxfmu5gv8th8wclt0h4p", "nostr", true).unwrap();
+        assert!(k.is_secret_key_set());
+        assert!(k.has_unsaved_change());
+        assert_eq!(
+            hex::encode(
+                k.get_secret()
+                    .unwrap()
+                    .with_secret_key(|sk| sk.secret_bytes())
+                    .unwrap()
+            ),
+            "3501454135014541350145413501453fefb02227e449e57cf4d3a3ce05378683"
+        );
+        assert_eq!(k.active_identity().key_security, KeySecurity::Weak);
+    }
+
+    #[test]
+    fn test_export_import_ncryptsec() {
+        let mut k = Keystore::new();
+        k.generate();
+        let npub = k.get_npub();
+        let ncryptsec = k
+            .export_ncryptsec("password", Encrypt::min_log2_rounds())
+            .unwrap();
+        assert!(ncryptsec.starts_with("ncryptsec1"));
+
+        k.clear();
+        assert!(k.import_ncryptsec(&ncryptsec, "wrong", true).is_err());
+        k.import_ncryptsec(&ncryptsec, "password", true).unwrap();
+        assert_eq!(k.get_npub(), npub);
+        assert_eq!(k.active_identity().key_security, KeySecurity::Medium);
+    }
+
+    #[test]
+    fn test_export_ncryptsec_log2_rounds() {
+        let mut k = Keystore::new();
+        k.generate();
+        let mut settings = SecuritySettings::default();
+        for log2_rounds in [Encrypt::min_log2_rounds(), Encrypt::min_log2_rounds() + 1] {
+            settings.kdf_log2_rounds = log2_rounds;
+            k.ncryptsec_password_input.set("password".to_string());
+            k.export_ncryptsec_action(&settings, &mut StatusMessages::new());
+            // the scrypt cost is stored after the version byte
+            let (_hrp, data, _variant) = bech32::decode(k.get_ncryptsec()).unwrap();
+            let encrypted = Vec::<u8>::from_base32(&data).unwrap();
+            assert_eq!(encrypted[1], log2_rounds);
+        }
+    }
+
+    #[test]
+    fn test_export_paper_backup() {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "keystr-test-paper-backup-{}.svg",
+            std::process::id()
+        ));
+        let mut k = Keystore::new();
+        assert!(k.export_paper_backup(&path, false).is_err());
+        k.import_mnemonic(
+            "leader monkey parrot ring guide accident before fence cannon height naive bean",
+            "",
+            0,
+            true,
+        )
+        .unwrap();
+        // nothing exported or revealed yet
+        assert!(matches!(
+            k.export_paper_backup(&path, false),
+            Err(Error::PaperBackupNoSecret)
+        ));
+        assert!(k.export_paper_backup(&path, true).is_err());
+
+        let mut status = StatusMessages::new();
+        let mut settings = SecuritySettings::default();
+        settings.kdf_log2_rounds = Encrypt::min_log2_rounds();
+        k.ncryptsec_password_input.set("password".to_string());
+        k.export_ncryptsec_action(&settings, &mut status);
+        k.export_paper_backup(&path, false).unwrap();
+        let svg = fs::read_to_string(&path).unwrap();
+        assert!(svg.contains(&k.get_npub()));
+        assert!(svg.contains(&k.get_ncryptsec()[0..40]));
+        assert!(!svg.contains("leader"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_import_key_file() {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "keystr-test-import-key-file-{}",
+            std::process::id()
+        ));
+        let mut k = Keystore::new();
+        assert!(matches!(
+            k.import_key_file(&path, ""),
+            Err(Error::ImportFileNotFound)
+        ));
+
+        fs::write(
+            &path,
+            r#"{"name": "test", "keys": {"nsec": "nsec1ktekw0hr5evjs0n9nyyquz4sue568snypy2rwk5mpv6hl2hq3vtsk0kpae"}}"#,
+        )
+        .unwrap();
+        assert_eq!(k.import_key_file(&path, "").unwrap(), KeyFileFormat::Json);
+        assert!(k.is_secret_key_set());
+        assert_eq!(
+            k.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+
+        // Keystr encrypted key, as saved on another machine
+        let encrypted = k
+            .get_secret()
+            .unwrap()
+            .with_secret_key(|sk| {
+                Encrypt::encrypt_key(
+                    sk,
+                    "password",
+                    Encrypt::min_log2_rounds(),
+                    KeySecurity::Medium,
+                )
+            })
+            .unwrap()
+            .unwrap();
+        fs::write(&path, hex::encode(encrypted)).unwrap();
+        let mut k = Keystore::new();
+        assert!(k.import_key_file(&path, "wrong").is_err());
+        assert!(!k.is_secret_key_set());
+        assert_eq!(
+            k.import_key_file(&path, "password").unwrap(),
+            KeyFileFormat::KeystrEncrypted
+        );
+        assert_eq!(
+            k.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+        assert!(k.has_unsaved_change());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_subkeys() {
+        let mut k = Keystore::new();
+        assert!(matches!(k.add_subkey("app"), Err(Error::SubkeyNoMnemonic)));
+        k.import_mnemonic(
+            "leader monkey parrot ring guide accident before fence cannon height naive bean",
+            "",
+            0,
+            true,
+        )
+        .unwrap();
+        k.add_subkey("app").unwrap();
+        k.add_subkey(" other app ").unwrap();
+        assert!(k.add_subkey("app").is_err());
+        assert!(k.add_subkey("").is_err());
+
+        let subkeys = k.get_subkeys().clone();
+        assert_eq!(subkeys.len(), 2);
+        assert_eq!(subkeys[0].account, 1);
+        assert_eq!(subkeys[1].label, "other app");
+        assert_eq!(subkeys[1].account, 2);
+        assert_ne!(subkeys[0].npub, k.get_npub());
+
+        // same as importing the mnemonic on that account
+        let mut other = Keystore::new();
+        other
+            .import_mnemonic(
+                "leader monkey parrot ring guide accident before fence cannon height naive bean",
+                "",
+                1,
+                true,
+            )
+            .unwrap();
+        assert_eq!(other.get_npub(), subkeys[0].npub);
+        assert_eq!(
+            k.export_subkey_nsec(0).unwrap().as_str(),
+            other.get_secret().unwrap().to_nsec().unwrap().as_str()
+        );
+
+        // a removed account is reused
+        k.remove_subkey(0).unwrap();
+        assert!(k.remove_subkey(5).is_err());
+        k.add_subkey("third").unwrap();
+        assert_eq!(k.get_subkeys()[1].account, 1);
+    }
+
+    #[test]
+    fn test_subkeys_passphrase() {
+        let mut k = Keystore::new();
+        k.import_mnemonic(
+            "oil oil oil oil oil oil oil oil oil oil oil oil",
+            "25th word",
+            3,
+            true,
+        )
+        .unwrap();
+        assert!(matches!(
+            k.add_subkey("app"),
+            Err(Error::SubkeyPassphraseMismatch)
+        ));
+        k.mnemonic_passphrase_input.set("25th word".to_string());
+        k.add_subkey("app").unwrap();
+        assert_eq!(k.get_subkeys()[0].account, 4);
+        k.mnemonic_passphrase_input.clear();
+        assert!(k.export_subkey_nsec(0).is_err());
+    }
+
+    #[test]
+    fn test_add_select_identity() {
+        let mut k = Keystore::new();
+        assert_eq!(k.identity_names(), vec!["main".to_string()]);
+        k.import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+
+        // new identity is active, and empty
+        k.add_identity("bot-1").unwrap();
+        assert_eq!(k.active_identity_name(), "bot-1");
+        assert_eq!(k.keys_is_set(), false);
+        assert!(k.any_keys_set());
+        k.generate();
+        let bot_npub = k.get_npub();
+
+        // switching back gives the first key
+        k.select_identity("main").unwrap();
+        assert_eq!(
+            k.get_npub(),
+            "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4"
+        );
+        k.select_identity("bot-1").unwrap();
+        assert_eq!(k.get_npub(), bot_npub);
+
+        assert!(k.select_identity("nonexistent").is_err());
+        assert_eq!(k.active_identity_name(), "bot-1");
+    }
+
+    #[test]
+    fn test_rotate_identity() {
+        let settings = security_settings(SecurityLevel::PersistOptionalPassword);
+        let mut k = Keystore::new();
+        k.import_public_key("npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4")
+            .unwrap();
+        // signing is needed
+        assert!(matches!(
+            k.rotate_identity("new", &settings),
+            Err(Error::KeyNotSet)
+        ));
+        k.generate();
+        let old_npub = k.get_npub();
+        let old_public_key = k.get_public_key().unwrap();
+        assert!(k.rotate_identity("invalid name", &settings).is_err());
+        assert_eq!(k.active_identity_name(), "main");
+        assert!(k.get_retirement().is_none());
+
+        let migration_event = k.rotate_identity("new", &settings).unwrap();
+        assert_eq!(k.active_identity_name(), "new");
+        assert!(k.is_secret_key_set());
+        assert!(k.has_unsaved_change());
+        assert!(k.get_retirement().is_none());
+        let new_npub = k.get_npub();
+        assert_ne!(new_npub, old_npub);
+
+        let event = serde_json::from_str::<nostr::Event>(&migration_event).unwrap();
+        assert!(event.verify().is_ok());
+        assert_eq!(event.pubkey, old_public_key);
+        assert!(event.content.contains(&new_npub));
+
+        // old identity is kept, with its key, as retired
+        k.select_identity("main").unwrap();
+        assert_eq!(k.get_npub(), old_npub);
+        assert!(k.is_secret_key_set());
+        let retirement = k.get_retirement().unwrap();
+        assert_eq!(retirement.successor, new_npub);
+        assert_eq!(retirement.migration_event, migration_event);
+        assert!(matches!(
+            k.rotate_identity("newer", &settings),
+            Err(Error::IdentityRetired)
+        ));
+    }
+
+    #[test]
+    fn test_rotate_identity_saved() {
+        let settings = security_settings(SecurityLevel::PersistMandatoryPassword);
+        let memory = Arc::new(MemoryStorage::default());
+        let storage: Arc<dyn StorageBackend> = memory.clone();
+        let mut k = Keystore::new_with_storage(storage.clone());
+        k.generate();
+        k.save_password_input.set("password".to_string());
+        k.save_repeat_password_input.set("password".to_string());
+        k.save_keys(&settings.kdf_params()).unwrap();
+        k.save_password_input.clear();
+        k.save_repeat_password_input.clear();
+        let old_npub = k.get_npub();
+
+        // the new key is to be saved, its password is needed
+        assert!(matches!(
+            k.rotate_identity("new", &settings),
+            Err(Error::KeyEncryptionPasswordMissing)
+        ));
+        assert_eq!(k.identity_names(), vec!["main"]);
+
+        // saving the new key fails: no retirement, in memory or in storage
+        k.save_password_input.set("password".to_string());
+        k.save_repeat_password_input.set("password".to_string());
+        memory.fail_writes_after(Some(0));
+        assert!(k.rotate_identity("new", &settings).is_err());
+        memory.fail_writes_after(None);
+        assert_eq!(k.active_identity_name(), "main");
+        assert_eq!(k.identity_names(), vec!["main"]);
+        assert!(k.get_retirement().is_none());
+        let mut loaded = Keystore::new_with_storage(storage.clone());
+        loaded.load_keys().unwrap();
+        assert_eq!(loaded.identity_names(), vec!["main"]);
+        assert!(loaded.get_retirement().is_none());
+
+        k.rotate_identity("new", &settings).unwrap();
+        assert_eq!(k.active_identity_name(), "new");
+        assert!(!k.has_unsaved_change());
+        assert!(k.save_password_input.is_empty());
+        let new_npub = k.get_npub();
+
+        // both the new key and the retirement are persisted
+        let mut loaded = Keystore::new_with_storage(storage);
+        loaded.load_keys().unwrap();
+        assert_eq!(loaded.identity_names(), vec!["main", "new"]);
+        loaded.select_identity("new").unwrap();
+        loaded.decrypt_secret_key("password").unwrap();
+        assert_eq!(loaded.get_npub(), new_npub);
+        loaded.select_identity("main").unwrap();
+        assert_eq!(loaded.get_retirement().unwrap().successor, new_npub);
+        assert_ne!(new_npub, old_npub);
+    }
+
+    #[test]
+    fn test_add_identity_invalid() {
+        let mut k = Keystore::new();
+        assert!(k.add_identity("").is_err());
+        assert!(k.add_identity("../main").is_err());
+        assert!(k.add_identity("main").is_err());
+        assert_eq!(k.identity_names().len(), 1);
+    }
+
+    #[test]
+    fn test_remove_last_identity() {
+        let mut k = Keystore::new();
+        assert!(k.remove_active_identity().is_err());
+        assert_eq!(k.identity_names().len(), 1);
+    }
+
+    #[test]
+    fn test_save_load_action() {
+        for level in SECURITY_LEVELS {
+            let storage = MemoryStorage::new_shared();
+            let settings = security_settings(*level);
+            let mut status = StatusMessages::new();
+            let mut k = Keystore::new_with_storage(storage.clone());
+            k.import_secret_key(NSEC, true).unwrap();
+
+            k.save_action(&settings, &mut status);
+            match level {
+                SecurityLevel::Never => {
+                    assert_eq!(status.get_last(), error_status(Error::KeySaveNotAllowed));
+                    assert!(storage.list_files(Path::new("/")).unwrap().is_empty());
+                    k.load_action(&settings, &mut status);
+                    assert_eq!(status.get_last(), error_status(Error::KeyLoadNotAllowed));
+                    // keys in memory are kept
+                    assert!(k.is_secret_key_set());
+                    continue;
+                }
+                SecurityLevel::PersistMandatoryPassword => {
+                    assert_eq!(
+                        status.get_last(),
+                        error_status(Error::KeyEncryptionPasswordMissing)
+                    );
+                    assert!(k.has_unsaved_change());
+                    assert!(!storage.exists(&Storage::encrypted_secret_key_file("main")));
+                    k.save_password_input.set("password".to_string());
+                    k.save_repeat_password_input.set("password".to_string());
+                    k.save_action(&settings, &mut status);
+                }
+                SecurityLevel::PersistOptionalPassword => {}
+            }
+            assert_eq!(status.get_last(), "Secret key persisted to storage");
+            assert!(!k.has_unsaved_change());
+            assert!(k.save_password_input.is_empty());
+            let secret_path = Storage::encrypted_secret_key_file("main");
+            assert!(storage.is_private(&secret_path).unwrap());
+            assert_eq!(
+                storage
+                    .read_to_string(&Storage::public_key_file("main"))
+                    .unwrap(),
+                NPUB
+            );
+            assert!(storage.exists(&Storage::keyring_file()));
+
+            // load into a new keystore on the same storage
+            let mut loaded = Keystore::new_with_storage(storage.clone());
+            loaded.load_action(&settings, &mut status);
+            assert_eq!(
+                status.get_last(),
+                "Keys loaded from storage (may need decryption with password)"
+            );
+            assert!(!loaded.has_unsaved_change());
+            if *level == SecurityLevel::PersistOptionalPassword {
+                // no password, unlocked when loaded
+                assert!(loaded.is_secret_key_set());
+            } else {
+                // the public key is known only after unlocking
+                assert!(loaded.is_locked());
+                loaded.decrypt_secret_key("password").unwrap();
+            }
+            assert_eq!(loaded.get_npub(), NPUB);
+            assert_eq!(
+                loaded.get_secret().unwrap().to_nsec().unwrap().as_str(),
+                NSEC
+            );
+        }
+    }
+
+    #[test]
+    fn test_load_nothing_saved() {
+        let mut k = Keystore::new_with_storage(MemoryStorage::new_shared());
+        let mut status = StatusMessages::new();
+        k.load_action(
+            &security_settings(SecurityLevel::PersistMandatoryPassword),
+            &mut status,
+        );
+        assert!(status.get_last().starts_with("Error: "));
+        assert!(!k.any_keys_set());
+    }
+
+    #[test]
+    fn test_load_action_not_private() {
+        let storage = MemoryStorage::new_shared();
+        let settings = security_settings(SecurityLevel::PersistOptionalPassword);
+        let mut status = StatusMessages::new();
+        let mut k = Keystore::new_with_storage(storage.clone());
+        k.import_secret_key(NSEC, true).unwrap();
+        k.save_action(&settings, &mut status);
+        let path = Storage::encrypted_secret_key_file("main");
+        let contents = storage.read(&path).unwrap();
+        storage.write(&path, &contents, false).unwrap();
+
+        let mut loaded = Keystore::new_with_storage(storage);
+        loaded.load_action(&settings, &mut status);
+        assert!(status.get_last().contains("readable by other users"));
+        assert_eq!(loaded.get_npub(), NPUB);
+    }
+
+    #[test]
+    fn test_load_action_weak_kdf() {
+        let storage = MemoryStorage::new_shared();
+        let settings = security_settings(SecurityLevel::PersistOptionalPassword);
+        let mut status = StatusMessages::new();
+        let mut k = Keystore::new_with_storage(storage.clone());
+        k.import_secret_key(NSEC, true).unwrap();
+        k.save_action(&settings, &mut status);
+        k.add_identity("bot").unwrap();
+        k.generate();
+        k.save_action(&settings, &mut status);
+
+        let mut loaded = Keystore::new_with_storage(storage.clone());
+        loaded.load_action(&settings, &mut status);
+        assert!(!status.get_last().contains("weaker encryption"));
+
+        let mut stronger = security_settings(SecurityLevel::PersistOptionalPassword);
+        stronger.kdf_log2_rounds += 1;
+        let mut loaded = Keystore::new_with_storage(storage);
+        loaded.load_action(&stronger, &mut status);
+        // all weak identities are reported, in one message
+        let last = status.get_last();
+        assert!(last.contains("weaker encryption"));
+        assert!(last.contains("'main'"));
+        assert!(last.contains("'bot'"));
+    }
+
+    #[test]
+    fn test_restore_key_version() {
+        let storage = MemoryStorage::new_shared();
+        let kdf = security_settings(SecurityLevel::PersistOptionalPassword).kdf_params();
+        let mut k = Keystore::new_with_storage(storage.clone());
+        assert!(k.get_key_versions().is_empty());
+        k.import_secret_key(NSEC, true).unwrap();
+        k.save_keys(&kdf).unwrap();
+        // first save, nothing to keep
+        assert!(k.get_key_versions().is_empty());
+
+        k.generate();
+        let new_npub = k.get_npub();
+        k.save_keys(&kdf).unwrap();
+        let versions = k.get_key_versions();
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions[0].0, 1);
+        assert!(matches!(
+            k.restore_key_version(2),
+            Err(Error::KeyVersionNotFound)
+        ));
+
+        k.restore_key_version(1).unwrap();
+        assert_eq!(k.get_npub(), NPUB);
+        assert!(!k.has_unsaved_change());
+        // the replaced key is kept as the latest version
+        assert_eq!(k.get_key_versions().len(), 2);
+        k.restore_key_version(1).unwrap();
+        assert_eq!(k.get_npub(), new_npub);
+    }
+
+    #[test]
+    fn test_change_password_action() {
+        let storage = MemoryStorage::new_shared();
+        let settings = security_settings(SecurityLevel::PersistOptionalPassword);
+        let mut status = StatusMessages::new();
+        let mut k = Keystore::new_with_storage(storage.clone());
+        k.import_secret_key(NSEC, true).unwrap();
+        k.save_password_input.set("old".to_string());
+        k.save_repeat_password_input.set("old".to_string());
+        k.save_action(&settings, &mut status);
+
+        // mismatch: fails, the inputs are cleared nevertheless
+        k.change_old_password_input.set("old".to_string());
+        k.change_new_password_input.set("new".to_string());
+        k.change_repeat_password_input.set("newer".to_string());
+        k.change_password_action(&settings, &mut status);
+        assert_eq!(
+            status.get_last(),
+            format!(
+                "Error: Could not change password ({})!",
+                Error::KeyEncryptionPasswordMismatch
+            )
+        );
+        assert!(k.change_old_password_input.is_empty());
+        assert!(k.change_new_password_input.is_empty());
+        assert!(k.change_repeat_password_input.is_empty());
+
+        k.change_old_password_input.set("old".to_string());
+        k.change_new_password_input.set("new".to_string());
+        k.change_repeat_password_input.set("new".to_string());
+        k.change_password_action(&settings, &mut status);
+        assert!(status.get_last().starts_with("Password changed"));
+        assert!(k.change_old_password_input.is_empty());
+        assert!(k.change_new_password_input.is_empty());
+        assert!(k.change_repeat_password_input.is_empty());
+
+        // reload: the old password fails, the new one works
+        let mut loaded = Keystore::new_with_storage(storage);
+        loaded.load_action(&settings, &mut status);
+        assert!(!loaded.is_secret_key_set());
+        assert!(loaded.decrypt_secret_key("old").is_err());
+        loaded.decrypt_password_input.set("new".to_string());
+        loaded.unlock_secret_key_action(&settings, &mut status);
+        assert_eq!(status.get_last(), "Secret key decrypted");
+        assert_eq!(
+            loaded.get_secret().unwrap().to_nsec().unwrap().as_str(),
+            NSEC
+        );
+    }
+
+    #[test]
+    fn test_change_password_removes_versions() {
+        let storage = MemoryStorage::new_shared();
+        let kdf = security_settings(SecurityLevel::PersistOptionalPassword).kdf_params();
+        let mnemonic =
+            "leader monkey parrot ring guide accident before fence cannon height naive bean";
+        let mut k = Keystore::new_with_storage(storage.clone());
+        k.save_password_input.set("old".to_string());
+        k.save_repeat_password_input.set("old".to_string());
+        k.import_mnemonic(mnemonic, "", 0, true).unwrap();
+        k.save_keys(&kdf).unwrap();
+        k.import_mnemonic(mnemonic, "", 1, true).unwrap();
+        k.save_keys(&kdf).unwrap();
+        assert_eq!(k.get_key_versions().len(), 1);
+
+        k.change_password("old", "new", None).unwrap();
+        assert!(k.get_key_versions().is_empty());
+        let files = storage
+            .list_files(&Storage::identity_folder("main"))
+            .unwrap();
+        let mut encrypted_count = 0;
+        for path in files {
+            let encrypted = match hex::decode(storage.read_to_string(&path).unwrap().trim()) {
+                Ok(encrypted) => encrypted,
+                // not encrypted (npub, sub-keys)
+                Err(_) => continue,
+            };
+            assert!(
+                Encrypt::decrypt_data(&encrypted, "old").is_err(),
+                "{}",
+                path.display()
+            );
+            assert!(Encrypt::decrypt_data(&encrypted, "new").is_ok());
+            encrypted_count += 1;
+        }
+        // secret key and mnemonic
+        assert_eq!(encrypted_count, 2);
+    }
+
+    #[test]
+    fn test_remove_saved_identity() {
+        let storage = MemoryStorage::new_shared();
+        let kdf = security_settings(SecurityLevel::PersistOptionalPassword).kdf_params();
+        let mut k = Keystore::new_with_storage(storage.clone());
+        k.import_public_key(NPUB).unwrap();
+        k.save_keys(&kdf).unwrap();
+        k.add_identity("bot").unwrap();
+        k.generate();
+        k.save_keys(&kdf).unwrap();
+        assert!(storage.exists(&Storage::encrypted_secret_key_file("bot")));
+
+        k.remove_active_identity().unwrap();
+        assert!(storage
+            .list_files(&Storage::identity_folder("bot"))
+            .unwrap()
+            .is_empty());
+        let mut loaded = Keystore::new_with_storage(storage);
+        loaded.load_keys().unwrap();
+        assert_eq!(loaded.identity_names(), vec!["main".to_string()]);
+        assert_eq!(loaded.get_npub(), NPUB);
+    }
 }
@@ -1,36 +1,98 @@
 use crate::base::error::Error;
+use crate::base::storage::Storage;
+use crate::base::storage_backend::{FileStorage, StorageBackend};
+use crate::model::data_backup::DataBackup;
+use crate::model::delegation_ledger::DelegationLedger;
+use crate::model::delegation_verifier::DelegationVerifier;
 use crate::model::delegator::Delegator;
 use crate::model::keystore::Keystore;
+use crate::model::mnemonic_generator::{MnemonicBackupStage, MnemonicGenerator};
+use crate::model::rotation::Rotation;
 use crate::model::settings::Settings;
 use crate::model::signer::{ConnectionStatus, Signer};
 use crate::model::status_messages::StatusMessages;
+use crate::model::vanity::VanitySearch;
 
 use nostr::prelude::Keys;
 
 use crossbeam::channel;
 use iced::widget::qr_code;
 use once_cell::sync::Lazy;
+use zeroize::Zeroize;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Actions that can be triggerred from the UI
 #[derive(Clone, Debug)]
 pub(crate) enum Action {
+    BackupCreate,
+    BackupRestore,
+    BackupRestoreNoConfirm,
     DelegateDeeGenerate,
     DelegateSign,
+    DelegationsExport,
+    DelegationVerify,
+    IdentityAdd,
+    IdentityRemove,
+    IdentityRemoveNoConfirm,
+    IdentitySelect(String),
     KeysClearNoConfirm,
     KeysClear,
     KeysGenerate,
+    KeysGenerateMnemonic(usize),
+    KeysGenerateFrost,
     KeysImportPubkey,
     KeysImportSecretkey,
     KeysImportMnemonic,
+    KeysPreviewMnemonic,
+    KeysRevealMnemonic,
+    KeysHideMnemonic,
+    KeysImportNcryptsec,
+    KeysImportFile,
+    KeysExportNcryptsec,
+    KeysPaperBackup,
+    KeysPaperBackupMnemonic,
     KeysLoad,
+    KeysLoadNoConfirm,
     KeysSave,
     KeysUnlock,
+    KeysLock,
+    /// Periodic check for inactivity, locks the keys after the auto-lock timeout
+    KeysAutoLockCheck,
+    KeysChangePassword,
+    KeysUpgradeEncryption,
+    KeysShamirSplit,
+    KeysShamirSave,
+    KeysShamirRestore,
+    KeysShamirAccept,
+    KeysShamirHide,
+    KeysSubkeyAdd,
+    KeysSubkeyRemove(usize),
+    KeysSubkeyExport(usize),
+    KeysSubkeyHide,
+    KeysRotate,
+    KeysRotateNoConfirm,
+    KeysRotateHideEvent,
+    KeysRestoreVersion(u8),
+    KeysRestoreVersionNoConfirm(u8),
     ConfirmationYes,
     ConfirmationNo,
+    MnemonicBackupWordsNoted,
+    MnemonicBackupConfirm,
+    MnemonicBackupCancel,
+    SettingsSetKdfCost,
+    SettingsCalibrateKdf,
+    SettingsSetAutoLock,
     SignerConnect,
     SignerDisconnect,
     SignerPendingIgnoreFirst,
     SignerPendingProcessFirst,
+    VanityStart,
+    VanityCancel,
+    /// Periodic check of a running vanity search, takes the found key into use
+    VanityCheck,
+    VanityAccept,
 }
 
 /// Events that can affect the UI
@@ -46,6 +108,8 @@ pub enum Event {
 pub(crate) enum Modal {
     /// A simple confirmation dialog
     Confirmation(Confirmation),
+    /// Backup of a newly generated mnemonic (show words, confirm words)
+    MnemonicBackup(MnemonicBackupStage),
     /// Show a QR code in a dialog
     QRCode(String),
     /// An incoming signer request, including its description
@@ -55,13 +119,29 @@ pub(crate) enum Modal {
 #[derive(Clone)]
 pub(crate) enum Confirmation {
     KeysClearBeforeAction(Option<Action>),
+    /// Load the saved keyring, discarding the unsaved changes of its identities
+    KeysLoad,
+    /// Remove the identity with the given name
+    IdentityRemove(String),
+    /// Rotate the key of the identity with the given name
+    KeysRotate(String),
+    /// Restore the given previous version of the saved key of the active identity
+    KeysRestoreVersion(u8),
+    /// Restore the backup with the given description, overwriting the files of Keystr
+    BackupRestore(String),
 }
 
 #[readonly::make]
 pub(crate) struct KeystrModel {
     pub own_keys: Keystore,
+    pub mnemonic_generator: MnemonicGenerator,
     pub delegator: Delegator,
+    pub delegations: DelegationLedger,
+    pub verifier: DelegationVerifier,
     pub signer: Signer,
+    pub vanity: VanitySearch,
+    pub rotation: Rotation,
+    pub data_backup: DataBackup,
     pub status: StatusMessages,
     pub settings: Settings,
     #[readonly]
@@ -69,6 +149,10 @@ pub(crate) struct KeystrModel {
     // QR code content; State must be stored somewhere outside of the UI
     #[readonly]
     qr_code: Option<(String, qr_code::State)>,
+    /// Time of the last user activity, for auto-lock
+    last_activity: Instant,
+    /// Storage of keys and settings, shared by the parts of the model
+    storage: Arc<dyn StorageBackend>,
 }
 
 pub(crate) struct EventQueue {
@@ -86,29 +170,59 @@ pub trait EventSink {
 }
 
 impl KeystrModel {
+    /// A model on an in-memory storage, for tests
+    #[cfg(test)]
     pub fn new() -> Self {
+        Self::new_with_storage(crate::base::storage_backend::memory::MemoryStorage::new_shared())
+    }
+
+    pub fn new_with_storage(storage: Arc<dyn StorageBackend>) -> Self {
         let app_id = Keys::generate();
         let status = StatusMessages::new();
         Self {
-            own_keys: Keystore::new(),
+            own_keys: Keystore::new_with_storage(storage.clone()),
+            mnemonic_generator: MnemonicGenerator::new(),
             delegator: Delegator::new(),
+            delegations: DelegationLedger::new(storage.clone()),
+            verifier: DelegationVerifier::new(),
             signer: Signer::new(&app_id, status.clone()),
+            vanity: VanitySearch::new(),
+            rotation: Rotation::new(),
+            data_backup: DataBackup::new(storage.clone()),
             status,
-            settings: Settings::default(),
+            settings: Settings::new(storage.clone()),
             confirmation: None,
             qr_code: None,
+            last_activity: Instant::now(),
+            storage,
         }
     }
 
     // Create and init model
     pub fn init() -> Self {
-        let mut model = Self::new();
+        Self::init_with_storage(FileStorage::new_shared())
+    }
+
+    /// Create and init model, with settings and keys loaded from the given storage
+    pub fn init_with_storage(storage: Arc<dyn StorageBackend>) -> Self {
+        let mut model = Self::new_with_storage(storage);
 
         model.status.set("Keystr starting");
+        model.status.set(&format!(
+            "Data folder: {}",
+            Storage::get_storage_folder().display()
+        ));
         //. Try load settings
-        if let Ok(sett) = Settings::load() {
+        if let Ok(sett) = Settings::load(model.storage.clone()) {
             model.settings = sett;
         }
+        //. Try load delegation ledger
+        if let Err(e) = model.delegations.load() {
+            model.status.set_error(&format!(
+                "Could not load delegation ledger ({}), new delegations are not recorded",
+                e
+            ));
+        }
         //. Try load keys
         if model.settings.security.allows_persist() {
             model.action(Action::KeysLoad);
@@ -117,17 +231,73 @@ impl KeystrModel {
     }
 
     pub fn action(&mut self, action: Action) {
+        if !matches!(action, Action::KeysAutoLockCheck | Action::VanityCheck) {
+            self.record_activity();
+        }
         match action {
+            Action::BackupCreate => {
+                if self.own_keys.has_unsaved_change() {
+                    self.status.set_error_err(&Error::KeyUnsavedChange);
+                } else {
+                    self.data_backup
+                        .create_action(&self.settings.security.kdf_params(), &mut self.status);
+                }
+            }
+            Action::BackupRestore => {
+                if !self.settings.security.allows_persist() {
+                    self.status.set_error_err(&Error::KeySaveNotAllowed);
+                } else if let Some(description) =
+                    self.data_backup.prepare_restore_action(&mut self.status)
+                {
+                    self.confirmation = Some(Confirmation::BackupRestore(description));
+                }
+            }
+            Action::BackupRestoreNoConfirm => self.restore_backup(),
             Action::DelegateDeeGenerate => self.delegator.generate_random_delegatee(),
             Action::DelegateSign => {
-                match self.own_keys.get_keys() {
+                match self.own_keys.get_signer() {
                     Err(e) => self.status.set_error(&e.to_string()),
-                    Ok(keys) => match self.delegator.create_delegation(&keys) {
+                    Ok(key_signer) => match self.delegator.create_delegation(&key_signer) {
                         Err(e) => self.status.set_error(&e.to_string()),
-                        Ok(_) => self.status.set("Delegation created"),
+                        Ok(record) => {
+                            self.status.set("Delegation created");
+                            self.delegations.add_action(record, &mut self.status);
+                        }
                     },
                 };
             }
+            Action::DelegationsExport => self.delegations.export_action(&mut self.status),
+            Action::DelegationVerify => self.verifier.verify_action(&mut self.status),
+            Action::IdentityAdd => {
+                match self
+                    .own_keys
+                    .add_identity(&self.own_keys.identity_name_input.clone())
+                {
+                    Err(e) => self.status.set_error_err(&e),
+                    Ok(_) => {
+                        // cleanup
+                        self.own_keys.identity_name_input = String::new();
+                        self.on_identity_switched();
+                    }
+                }
+            }
+            Action::IdentityRemove => {
+                self.confirmation = Some(Confirmation::IdentityRemove(
+                    self.own_keys.active_identity_name().to_string(),
+                ));
+            }
+            Action::IdentityRemoveNoConfirm => match self.own_keys.remove_active_identity() {
+                Err(e) => self.status.set_error_err(&e),
+                Ok(_) => self.on_identity_switched(),
+            },
+            Action::IdentitySelect(name) => {
+                if name != self.own_keys.active_identity_name() {
+                    match self.own_keys.select_identity(&name) {
+                        Err(e) => self.status.set_error_err(&e),
+                        Ok(_) => self.on_identity_switched(),
+                    }
+                }
+            }
             Action::KeysClearNoConfirm => {
                 self.own_keys.clear();
                 self.status.set("Keys cleared");
@@ -150,6 +320,31 @@ impl KeystrModel {
                     self.status.set("New keypair generated");
                 }
             }
+            Action::KeysGenerateMnemonic(word_count) => {
+                if self.own_keys.keys_is_set() {
+                    self.confirmation = Some(Confirmation::KeysClearBeforeAction(Some(
+                        Action::KeysGenerateMnemonic(word_count),
+                    )));
+                } else {
+                    self.confirmation = None;
+                    match self.mnemonic_generator.start(word_count) {
+                        Err(e) => self.status.set_error_err(&e),
+                        Ok(_) => self
+                            .status
+                            .set("New mnemonic generated, write down the words"),
+                    }
+                }
+            }
+            Action::KeysGenerateFrost => {
+                if self.own_keys.keys_is_set() {
+                    self.confirmation = Some(Confirmation::KeysClearBeforeAction(Some(
+                        Action::KeysGenerateFrost,
+                    )));
+                } else {
+                    self.confirmation = None;
+                    self.own_keys.generate_frost_action(&mut self.status);
+                }
+            }
             Action::KeysImportPubkey => {
                 match self
                     .own_keys
@@ -167,22 +362,112 @@ impl KeystrModel {
             Action::KeysImportMnemonic => {
                 self.own_keys.import_mnemonic_action(&mut self.status);
             }
+            Action::KeysPreviewMnemonic => {
+                self.own_keys.preview_mnemonic_action(&mut self.status);
+            }
+            Action::KeysRevealMnemonic => self
+                .own_keys
+                .reveal_mnemonic_action(&self.settings.security, &mut self.status),
+            Action::KeysHideMnemonic => self.own_keys.hide_mnemonic(),
+            Action::KeysImportNcryptsec => {
+                self.own_keys.import_ncryptsec_action(&mut self.status);
+            }
+            Action::KeysImportFile => {
+                self.own_keys.import_key_file_action(&mut self.status);
+            }
+            Action::KeysExportNcryptsec => {
+                self.own_keys
+                    .export_ncryptsec_action(&self.settings.security, &mut self.status);
+            }
+            Action::KeysPaperBackup => {
+                self.own_keys.paper_backup_action(false, &mut self.status);
+            }
+            Action::KeysPaperBackupMnemonic => {
+                self.own_keys.paper_backup_action(true, &mut self.status);
+            }
             Action::KeysLoad => {
-                if self.own_keys.keys_is_set() {
-                    self.confirmation =
-                        Some(Confirmation::KeysClearBeforeAction(Some(Action::KeysLoad)));
+                // loading replaces the whole keyring, only unsaved changes would be lost
+                if self.own_keys.any_unsaved_change() {
+                    self.confirmation = Some(Confirmation::KeysLoad);
                 } else {
-                    self.own_keys
-                        .load_action(&self.settings.security, &mut self.status);
+                    self.action(Action::KeysLoadNoConfirm);
                 }
             }
+            Action::KeysLoadNoConfirm => {
+                self.own_keys
+                    .load_action(&self.settings.security, &mut self.status);
+            }
             Action::KeysSave => {
                 self.own_keys
                     .save_action(&self.settings.security, &mut self.status);
             }
-            Action::KeysUnlock => self
+            Action::KeysUnlock => {
+                self.own_keys
+                    .unlock_secret_key_action(&self.settings.security, &mut self.status);
+                if let Ok(key_signer) = self.own_keys.get_signer() {
+                    self.signer.unlock(key_signer, &mut self.status);
+                }
+            }
+            Action::KeysLock => {
+                self.own_keys.lock_action(&mut self.status);
+                self.on_keys_locked();
+            }
+            Action::KeysAutoLockCheck => {
+                if self.is_auto_lock_due(self.settings.security.auto_lock_timeout(), Instant::now())
+                {
+                    let count = self.own_keys.lock_all();
+                    self.status.set(&format!(
+                        "Locked {} secret key(s) after {} minutes of inactivity",
+                        count, self.settings.security.auto_lock_minutes
+                    ));
+                    self.on_keys_locked();
+                }
+            }
+            Action::KeysChangePassword => self
+                .own_keys
+                .change_password_action(&self.settings.security, &mut self.status),
+            Action::KeysUpgradeEncryption => self
                 .own_keys
-                .unlock_secret_key_action(&self.settings.security, &mut self.status),
+                .upgrade_encryption_action(&self.settings.security, &mut self.status),
+            Action::KeysShamirSplit => self.own_keys.shamir_split_action(&mut self.status),
+            Action::KeysShamirSave => self.own_keys.shamir_save_action(&mut self.status),
+            Action::KeysShamirRestore => self.own_keys.shamir_restore_action(&mut self.status),
+            Action::KeysShamirAccept => {
+                if self.own_keys.keys_is_set() {
+                    self.status
+                        .set_error("Clear the existing keys before accepting restored ones");
+                } else {
+                    self.own_keys.shamir_accept_action(&mut self.status);
+                }
+            }
+            Action::KeysShamirHide => self.own_keys.shamir_hide(),
+            Action::KeysSubkeyAdd => self
+                .own_keys
+                .add_subkey_action(&self.settings.security, &mut self.status),
+            Action::KeysSubkeyRemove(index) => {
+                self.own_keys
+                    .remove_subkey_action(index, &self.settings.security, &mut self.status)
+            }
+            Action::KeysSubkeyExport(index) => {
+                self.own_keys.export_subkey_action(index, &mut self.status)
+            }
+            Action::KeysSubkeyHide => self.own_keys.hide_subkey_nsec(),
+            Action::KeysRotate => {
+                self.confirmation = Some(Confirmation::KeysRotate(
+                    self.own_keys.active_identity_name().to_string(),
+                ));
+            }
+            Action::KeysRotateNoConfirm => self.rotate_key(),
+            Action::KeysRotateHideEvent => self.rotation.hide_migration_event(),
+            Action::KeysRestoreVersion(version) => {
+                self.confirmation = Some(Confirmation::KeysRestoreVersion(version));
+            }
+            Action::KeysRestoreVersionNoConfirm(version) => {
+                self.own_keys
+                    .restore_key_version_action(version, &mut self.status);
+                // the signer is bound to the replaced key
+                self.signer.disconnect_action(&mut self.status);
+            }
             Action::ConfirmationYes => {
                 if let Some(conf) = &self.confirmation {
                     match conf {
@@ -194,14 +479,59 @@ impl KeystrModel {
                                 self.action(next_action);
                             }
                         }
+                        Confirmation::KeysLoad => {
+                            self.confirmation = None;
+                            self.action(Action::KeysLoadNoConfirm);
+                        }
+                        Confirmation::IdentityRemove(_name) => {
+                            self.confirmation = None;
+                            self.action(Action::IdentityRemoveNoConfirm);
+                        }
+                        Confirmation::KeysRotate(_name) => {
+                            self.confirmation = None;
+                            self.action(Action::KeysRotateNoConfirm);
+                        }
+                        Confirmation::KeysRestoreVersion(version) => {
+                            let version = *version;
+                            self.confirmation = None;
+                            self.action(Action::KeysRestoreVersionNoConfirm(version));
+                        }
+                        Confirmation::BackupRestore(_description) => {
+                            self.confirmation = None;
+                            self.action(Action::BackupRestoreNoConfirm);
+                        }
                     }
                 }
             }
             Action::ConfirmationNo => {
                 if let Some(_conf) = &self.confirmation {
+                    if matches!(self.confirmation, Some(Confirmation::BackupRestore(_))) {
+                        self.data_backup.cancel_restore();
+                    }
                     self.confirmation = None;
                 }
             }
+            Action::MnemonicBackupWordsNoted => self.mnemonic_generator.words_noted(),
+            Action::MnemonicBackupConfirm => {
+                if let Some(mut phrase) = self.mnemonic_generator.confirm_action(&mut self.status) {
+                    match self.own_keys.import_mnemonic(&phrase, "", 0, true) {
+                        Err(e) => self.status.set_error_err(&e),
+                        Ok(_) => self
+                            .status
+                            .set("Backup confirmed, new keypair generated from mnemonic"),
+                    }
+                    phrase.zeroize();
+                }
+            }
+            Action::MnemonicBackupCancel => {
+                self.mnemonic_generator.reset();
+                self.status.set("Mnemonic generation cancelled");
+            }
+            Action::SettingsSetKdfCost => {
+                self.settings.set_kdf_log2_rounds_action(&mut self.status)
+            }
+            Action::SettingsCalibrateKdf => self.settings.calibrate_kdf_action(&mut self.status),
+            Action::SettingsSetAutoLock => self.settings.set_auto_lock_action(&mut self.status),
             Action::SignerConnect => match self.own_keys.get_signer() {
                 Err(_) => self.status.set("Key pair is not loaded or unlocked!"),
                 Ok(signer) => {
@@ -215,8 +545,160 @@ impl KeystrModel {
                 self.signer.pending_ignore_first_action(&mut self.status);
             }
             Action::SignerPendingProcessFirst => {
-                self.signer.pending_process_first_action(&mut self.status);
+                if let Some(record) = self.signer.pending_process_first_action(&mut self.status) {
+                    self.delegations.add_action(record, &mut self.status);
+                }
+            }
+            Action::VanityStart => {
+                if self.own_keys.keys_is_set() {
+                    self.confirmation = Some(Confirmation::KeysClearBeforeAction(Some(
+                        Action::VanityStart,
+                    )));
+                } else {
+                    self.confirmation = None;
+                    self.vanity.start_action(&mut self.status);
+                }
             }
+            Action::VanityCancel => self.vanity.cancel_action(&mut self.status),
+            Action::VanityCheck => {
+                if self.vanity.poll() {
+                    self.accept_vanity_key();
+                }
+            }
+            Action::VanityAccept => self.accept_vanity_key(),
+        }
+    }
+
+    /// The active identity has changed; the signer is bound to the previous one, disconnect it
+    fn on_identity_switched(&mut self) {
+        self.signer.disconnect_action(&mut self.status);
+        self.status.set(&format!(
+            "Identity '{}' selected",
+            self.own_keys.active_identity_name()
+        ));
+    }
+
+    /// Rotate the key of the active identity to a new identity (see Keystore::rotate_identity()),
+    /// and optionally re-issue the current delegation from the new key
+    fn rotate_key(&mut self) {
+        let old_name = self.own_keys.active_identity_name().to_string();
+        let old_npub = self.own_keys.get_npub();
+        match self
+            .own_keys
+            .rotate_identity(self.rotation.new_name_input.trim(), &self.settings.security)
+        {
+            Err(e) => self.status.set_error_err(&e),
+            Ok(migration_event) => {
+                self.rotation.set_migration_event(migration_event);
+                self.on_identity_switched();
+                let new_name = self.own_keys.active_identity_name();
+                let next_steps = if self.own_keys.has_unsaved_change() {
+                    format!("Save the new identity '{}', and publish", new_name)
+                } else {
+                    format!("The new identity '{}' is saved, publish", new_name)
+                };
+                self.status.set(&format!(
+                    "Key of '{}' rotated, it is kept as retired. {} the migration event",
+                    old_name, next_steps
+                ));
+                if self.rotation.reissue_delegations {
+                    self.reissue_delegations(&old_npub);
+                }
+            }
+        }
+    }
+
+    /// Re-issue the delegations of the retired key still in effect, from the new (active) key
+    fn reissue_delegations(&mut self, old_npub: &str) {
+        let signer = match self.own_keys.get_signer() {
+            Err(e) => {
+                self.status
+                    .set_error(&format!("Could not re-issue delegations ({})", e));
+                return;
+            }
+            Ok(signer) => signer,
+        };
+        let records = self
+            .delegations
+            .records_in_effect(old_npub, DelegationLedger::current_time());
+        let mut count = 0;
+        for record in records {
+            match record.reissue(&signer) {
+                Err(e) => self.status.set_error(&format!(
+                    "Could not re-issue delegation to {} ({})",
+                    record.delegatee, e
+                )),
+                Ok(new_record) => {
+                    self.delegations.add_action(new_record, &mut self.status);
+                    count += 1;
+                }
+            }
+        }
+        self.status
+            .set(&format!("{} delegations re-issued from the new key", count));
+    }
+
+    /// Restore the confirmed backup into the data folder, then reload settings and keys from it
+    fn restore_backup(&mut self) {
+        match self.data_backup.restore() {
+            Err(e) => self
+                .status
+                .set_error(&format!("Could not restore backup ({})", e)),
+            Ok(_) => {
+                if let Ok(settings) = Settings::load(self.storage.clone()) {
+                    self.settings = settings;
+                }
+                if let Err(e) = self.delegations.load() {
+                    self.status.set_error(&format!(
+                        "Could not load delegation ledger ({}), new delegations are not recorded",
+                        e
+                    ));
+                }
+                // the signer is bound to an identity that may be gone
+                self.signer.disconnect_action(&mut self.status);
+                self.own_keys
+                    .load_action(&self.settings.security, &mut self.status);
+                self.status.set("Backup restored");
+            }
+        }
+    }
+
+    /// Take the key found by the vanity search into use, as new unsaved key.
+    /// If keys were set meanwhile, confirmation is asked to clear them first.
+    fn accept_vanity_key(&mut self) {
+        if self.own_keys.keys_is_set() {
+            self.confirmation = Some(Confirmation::KeysClearBeforeAction(Some(
+                Action::VanityAccept,
+            )));
+        } else if let Some(secret_key) = self.vanity.take_found() {
+            self.confirmation = None;
+            self.own_keys.set_generated_secret_key(secret_key);
+            self.status
+                .set("Vanity key found and set, save it to keep it");
+        }
+    }
+
+    /// Note user activity (e.g. input), postpones auto-lock
+    pub fn record_activity(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    /// True if auto-lock is on (timeout is set), there is something to lock,
+    /// and the inactivity timeout has passed
+    fn is_auto_lock_due(&self, timeout: Option<Duration>, now: Instant) -> bool {
+        match timeout {
+            None => false,
+            Some(timeout) => {
+                self.own_keys.can_lock()
+                    && now.saturating_duration_since(self.last_activity) >= timeout
+            }
+        }
+    }
+
+    /// Keys have been locked; the signer cannot sign for the active identity until unlock
+    fn on_keys_locked(&mut self) {
+        if !self.own_keys.can_sign() {
+            self.signer.lock(&mut self.status);
         }
     }
 
@@ -225,11 +707,14 @@ impl KeystrModel {
         if let Some(conf) = &self.confirmation {
             Some(Modal::Confirmation(conf.clone()))
         } else if let ConnectionStatus::Connected(conn) = self.signer.get_connection_status() {
-            if conn.get_pending_count() > 0 {
+            // while locked, requests are only queued
+            if conn.get_pending_count() > 0 && !conn.is_locked() {
                 Some(Modal::SignerRequest(conn.get_first_request_description()))
             } else {
                 None
             }
+        } else if self.mnemonic_generator.get_stage() != MnemonicBackupStage::Inactive {
+            Some(Modal::MnemonicBackup(self.mnemonic_generator.get_stage()))
         } else if let Some((qr_content, _qr_code)) = &self.qr_code {
             Some(Modal::QRCode(qr_content.clone()))
         } else {
@@ -283,6 +768,12 @@ impl EventQueue {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::base::encrypt::{Encrypt, KeySecurity};
+    use crate::base::storage_backend::memory::MemoryStorage;
+    use crate::model::delegation_ledger::{DelegationSource, DelegationStatus};
+    use crate::model::security_settings::{SecurityLevel, SECURITY_LEVELS};
+    use nostr::prelude::SecretKey;
+    use nostr::secp256k1::rand;
 
     #[test]
     fn test_clear_generate_confirmation() {
@@ -320,4 +811,249 @@ mod test {
         assert_eq!(m.own_keys.keys_is_set(), false);
         assert!(m.confirmation.is_none());
     }
+
+    #[test]
+    fn test_generate_mnemonic_backup() {
+        let mut m = KeystrModel::new();
+        m.action(Action::KeysGenerateMnemonic(12));
+        assert!(matches!(
+            m.get_modal(),
+            Some(Modal::MnemonicBackup(MnemonicBackupStage::ShowWords))
+        ));
+        let words = m.mnemonic_generator.get_words().clone();
+        m.action(Action::MnemonicBackupWordsNoted);
+
+        // wrong words, no key yet
+        m.action(Action::MnemonicBackupConfirm);
+        assert_eq!(m.own_keys.keys_is_set(), false);
+
+        let numbers = m.mnemonic_generator.get_challenge_word_numbers();
+        for (i, n) in numbers.iter().enumerate() {
+            m.mnemonic_generator.challenge_inputs[i] = words[n - 1].clone();
+        }
+        m.action(Action::MnemonicBackupConfirm);
+        assert!(m.get_modal().is_none());
+        assert!(m.own_keys.is_secret_key_set());
+        assert!(m.own_keys.has_unsaved_change());
+
+        // key derived from the same words
+        let mut k = Keystore::new();
+        k.import_mnemonic(&words.join(" "), "", 0, true).unwrap();
+        assert_eq!(k.get_npub(), m.own_keys.get_npub());
+    }
+
+    #[test]
+    fn test_auto_lock_due() {
+        let mut m = KeystrModel::new();
+        let timeout = Some(Duration::from_secs(300));
+        let later = Instant::now() + Duration::from_secs(3600);
+        m.own_keys.generate();
+        // generated key is not saved, nothing to lock
+        assert!(!m.is_auto_lock_due(timeout, later));
+
+        // unlocked saved key
+        let sk = SecretKey::new(&mut rand::thread_rng());
+        let encrypted = Encrypt::encrypt_key(&sk, "", 13, KeySecurity::Medium).unwrap();
+        m.own_keys
+            .import_encrypted_secret_key(&hex::encode(encrypted), false)
+            .unwrap();
+        m.own_keys.decrypt_secret_key("").unwrap();
+        assert!(m.is_auto_lock_due(timeout, later));
+        // off
+        assert!(!m.is_auto_lock_due(None, later));
+        // recent activity
+        m.record_activity();
+        assert!(!m.is_auto_lock_due(timeout, Instant::now()));
+
+        m.action(Action::KeysAutoLockCheck);
+        assert!(m.own_keys.is_secret_key_set());
+        m.action(Action::KeysLock);
+        assert!(m.own_keys.is_locked());
+        assert!(!m.is_auto_lock_due(timeout, later));
+    }
+
+    #[test]
+    fn test_vanity_search() {
+        let mut m = KeystrModel::new();
+        m.vanity.pattern_input = "bob".to_string();
+        m.action(Action::VanityStart);
+        assert!(!m.vanity.is_running());
+
+        m.action(Action::KeysGenerate);
+        m.vanity.pattern_input = "q".to_string();
+        // existing keys have to be cleared first
+        m.action(Action::VanityStart);
+        assert!(!m.vanity.is_running());
+        assert!(m.confirmation.is_some());
+        m.action(Action::ConfirmationYes);
+        assert!(m.vanity.is_running());
+
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while !m.own_keys.keys_is_set() {
+            assert!(Instant::now() < deadline);
+            std::thread::sleep(Duration::from_millis(10));
+            m.action(Action::VanityCheck);
+        }
+        assert!(!m.vanity.is_running());
+        assert!(m.own_keys.get_npub().starts_with("npub1q"));
+        assert!(m.own_keys.has_unsaved_change());
+    }
+
+    #[test]
+    fn test_rotate_key() {
+        let mut m = KeystrModel::new_with_storage(MemoryStorage::new_shared());
+        m.action(Action::KeysGenerate);
+        let old_npub = m.own_keys.get_npub();
+        m.action(Action::DelegateDeeGenerate);
+        m.action(Action::DelegateSign);
+        let old_tag = m.delegator.delegation_tag.clone();
+        assert!(!old_tag.is_empty());
+        // an expired delegation is not re-issued
+        let mut expired = m.delegations.records()[0].clone();
+        expired.conditions = "kind=1&created_at<1678659553".to_string();
+        m.delegations.add(expired).unwrap();
+
+        m.rotation.new_name_input = "rotated".to_string();
+        m.rotation.reissue_delegations = true;
+        m.action(Action::KeysRotate);
+        assert!(matches!(m.confirmation, Some(Confirmation::KeysRotate(_))));
+        m.action(Action::ConfirmationYes);
+        assert_eq!(m.own_keys.active_identity_name(), "rotated");
+        assert_ne!(m.own_keys.get_npub(), old_npub);
+        assert!(m.rotation.get_migration_event().is_some());
+        assert!(m.rotation.new_name_input.is_empty());
+        // the delegation in effect is re-issued from the ledger, signed by the new key
+        let delegations = m.delegations.records();
+        assert_eq!(delegations.len(), 3);
+        assert_eq!(delegations[0].delegator, old_npub);
+        assert_eq!(delegations[2].delegator, m.own_keys.get_npub());
+        assert_eq!(delegations[2].delegatee, delegations[0].delegatee);
+        assert_eq!(delegations[2].conditions, delegations[0].conditions);
+        assert_eq!(
+            delegations[2].source,
+            DelegationSource::Rotation(old_npub.clone())
+        );
+        let new_public_key = m.own_keys.get_public_key().unwrap().to_string();
+        assert!(delegations[2].tag.contains(&new_public_key));
+        // the Delegate tab is left alone
+        assert_eq!(m.delegator.delegation_tag, old_tag);
+
+        m.action(Action::KeysRotateHideEvent);
+        assert!(m.rotation.get_migration_event().is_none());
+    }
+
+    #[test]
+    fn test_delegation_ledger() {
+        let storage = MemoryStorage::new_shared();
+        let mut m = KeystrModel::new_with_storage(storage.clone());
+        m.action(Action::KeysGenerate);
+        m.action(Action::DelegateDeeGenerate);
+        m.delegator.kind = Some(1);
+        m.delegator.time_set_duration("30d").unwrap();
+        m.action(Action::DelegateSign);
+        let delegations = m.delegations.records();
+        assert_eq!(delegations.len(), 1);
+        assert_eq!(delegations[0].delegatee, m.delegator.delegatee_npub_input);
+        assert_eq!(delegations[0].tag, m.delegator.delegation_tag);
+        // valid after its creation time ('created_at>'), from the next second on
+        assert_eq!(
+            delegations[0].status(DelegationLedger::current_time() + 1),
+            DelegationStatus::Active
+        );
+
+        // recorded also after a restart
+        let m = KeystrModel::init_with_storage(storage);
+        assert_eq!(m.delegations.records().len(), 1);
+    }
+
+    #[test]
+    fn test_identity_add_select() {
+        let mut m = KeystrModel::new();
+        m.action(Action::KeysGenerate);
+        let main_npub = m.own_keys.get_npub();
+
+        m.own_keys.identity_name_input = "bot".to_string();
+        m.action(Action::IdentityAdd);
+        assert_eq!(m.own_keys.active_identity_name(), "bot");
+        assert!(m.own_keys.identity_name_input.is_empty());
+        // generate does not need confirmation, new identity is empty
+        m.action(Action::KeysGenerate);
+        assert!(m.confirmation.is_none());
+        assert_ne!(m.own_keys.get_npub(), main_npub);
+
+        m.action(Action::IdentitySelect("main".to_string()));
+        assert_eq!(m.own_keys.get_npub(), main_npub);
+    }
+
+    #[test]
+    fn test_load_confirm_unsaved() {
+        let storage = MemoryStorage::new_shared();
+        let mut m = KeystrModel::new_with_storage(storage);
+        m.settings
+            .set_kdf_log2_rounds(Encrypt::min_log2_rounds())
+            .unwrap();
+        for name in ["main", "bot"] {
+            if name != m.own_keys.active_identity_name() {
+                m.own_keys.identity_name_input = name.to_string();
+                m.action(Action::IdentityAdd);
+            }
+            m.action(Action::KeysGenerate);
+            m.own_keys.save_password_input.set("password".to_string());
+            m.own_keys
+                .save_repeat_password_input
+                .set("password".to_string());
+            m.action(Action::KeysSave);
+            assert!(!m.own_keys.has_unsaved_change());
+        }
+
+        // both identities hold saved keys, nothing to lose
+        m.action(Action::KeysLoad);
+        assert!(m.confirmation.is_none());
+        assert_eq!(m.own_keys.identity_names(), vec!["main", "bot"]);
+
+        // an unsaved key in one identity is confirmed once, then the keyring is loaded
+        m.own_keys.identity_name_input = "third".to_string();
+        m.action(Action::IdentityAdd);
+        m.action(Action::KeysGenerate);
+        m.action(Action::KeysLoad);
+        assert!(matches!(m.confirmation, Some(Confirmation::KeysLoad)));
+        m.action(Action::ConfirmationYes);
+        assert!(m.confirmation.is_none());
+        assert!(!m.own_keys.any_unsaved_change());
+        assert_eq!(m.own_keys.identity_names(), vec!["main", "bot"]);
+    }
+
+    #[test]
+    fn test_save_init_load() {
+        for level in SECURITY_LEVELS {
+            let storage = MemoryStorage::new_shared();
+            let mut m = KeystrModel::new_with_storage(storage.clone());
+            m.settings.set_security_level(*level);
+            m.settings
+                .set_kdf_log2_rounds(Encrypt::min_log2_rounds())
+                .unwrap();
+            m.action(Action::KeysGenerate);
+            let npub = m.own_keys.get_npub();
+            m.own_keys.save_password_input.set("password".to_string());
+            m.own_keys
+                .save_repeat_password_input
+                .set("password".to_string());
+            m.action(Action::KeysSave);
+            assert_eq!(
+                m.own_keys.has_unsaved_change(),
+                *level == SecurityLevel::Never
+            );
+
+            // restart: settings and keys are loaded from the storage
+            let mut m = KeystrModel::init_with_storage(storage);
+            assert_eq!(m.settings.security.security_level, *level);
+            if *level == SecurityLevel::Never {
+                assert!(!m.own_keys.any_keys_set());
+            } else {
+                assert!(m.own_keys.is_locked());
+                m.own_keys.decrypt_secret_key("password").unwrap();
+                assert_eq!(m.own_keys.get_npub(), npub);
+            }
+        }
+    }
 }
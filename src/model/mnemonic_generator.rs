@@ -0,0 +1,175 @@
+use crate::base::error::Error;
+use crate::base::nip06::Nip06;
+use crate::model::status_messages::StatusMessages;
+
+use rand_core::{OsRng, RngCore};
+use zeroize::Zeroize;
+
+/// Number of words that have to be re-entered to confirm the backup
+const BACKUP_CHALLENGE_WORDS: usize = 3;
+
+/// Steps of generating a new mnemonic
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum MnemonicBackupStage {
+    /// No generation in progress
+    Inactive,
+    /// The words are shown (only once), user should write them down
+    ShowWords,
+    /// The words are hidden, user has to re-enter some of them
+    ConfirmWords,
+}
+
+/// Model for generating a new mnemonic-backed identity.
+/// The mnemonic is shown once, and only accepted after the user confirms the backup
+/// by re-entering some selected words.
+pub(crate) struct MnemonicGenerator {
+    stage: MnemonicBackupStage,
+    /// The generated words, kept until the backup is confirmed or cancelled
+    words: Vec<String>,
+    /// Positions (0-based) of the words to re-enter
+    challenge_indices: Vec<usize>,
+    /// Inputs for the re-entered words, one for each challenge position
+    pub challenge_inputs: Vec<String>,
+}
+
+impl MnemonicGenerator {
+    pub fn new() -> Self {
+        Self {
+            stage: MnemonicBackupStage::Inactive,
+            words: Vec::new(),
+            challenge_indices: Vec::new(),
+            challenge_inputs: Vec::new(),
+        }
+    }
+
+    pub fn get_stage(&self) -> MnemonicBackupStage {
+        self.stage
+    }
+
+    /// Warning: Security-sensitive method!
+    /// The generated words; should be shown only in the ShowWords stage
+    pub fn get_words(&self) -> &Vec<String> {
+        &self.words
+    }
+
+    /// The 1-based numbers of the words to be re-entered
+    pub fn get_challenge_word_numbers(&self) -> Vec<usize> {
+        self.challenge_indices.iter().map(|i| i + 1).collect()
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Generate a new mnemonic with the given number of words (12 or 24)
+    pub fn start(&mut self, word_count: usize) -> Result<(), Error> {
+        self.reset();
+        let mnemonic = Nip06::generate_mnemonic(word_count)?;
+        self.words = mnemonic.words().map(|w| w.to_string()).collect();
+        self.challenge_indices = Self::pick_challenge_indices(self.words.len());
+        self.challenge_inputs = vec![String::new(); self.challenge_indices.len()];
+        self.stage = MnemonicBackupStage::ShowWords;
+        Ok(())
+    }
+
+    /// Select distinct random word positions, in increasing order
+    fn pick_challenge_indices(word_count: usize) -> Vec<usize> {
+        let mut indices = Vec::new();
+        while indices.len() < BACKUP_CHALLENGE_WORDS.min(word_count) {
+            let idx = (OsRng.next_u32() as usize) % word_count;
+            if !indices.contains(&idx) {
+                indices.push(idx);
+            }
+        }
+        indices.sort();
+        indices
+    }
+
+    /// User has noted the words, hide them and ask for some of them
+    pub fn words_noted(&mut self) {
+        if self.stage == MnemonicBackupStage::ShowWords {
+            self.stage = MnemonicBackupStage::ConfirmWords;
+        }
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Check the re-entered words; if correct, return the mnemonic phrase, and finish generation.
+    pub fn confirm(&mut self) -> Result<String, Error> {
+        if self.stage != MnemonicBackupStage::ConfirmWords {
+            return Err(Error::MnemonicBackupNotConfirmed);
+        }
+        for (idx, input) in self
+            .challenge_indices
+            .iter()
+            .zip(self.challenge_inputs.iter())
+        {
+            if input.trim().to_lowercase() != self.words[*idx] {
+                return Err(Error::MnemonicBackupNotConfirmed);
+            }
+        }
+        let phrase = self.words.join(" ");
+        self.reset();
+        Ok(phrase)
+    }
+
+    /// Discard the generated mnemonic
+    pub fn reset(&mut self) {
+        self.words.iter_mut().for_each(|w| w.zeroize());
+        self.words = Vec::new();
+        self.challenge_inputs.iter_mut().for_each(|w| w.zeroize());
+        self.challenge_inputs = Vec::new();
+        self.challenge_indices = Vec::new();
+        self.stage = MnemonicBackupStage::Inactive;
+    }
+
+    /// Action to check the re-entered words and return the mnemonic phrase if they match
+    pub fn confirm_action(&mut self, status: &mut StatusMessages) -> Option<String> {
+        match self.confirm() {
+            Err(e) => {
+                status.set_error_err(&e);
+                None
+            }
+            Ok(phrase) => Some(phrase),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_generate_and_confirm() {
+        let mut g = MnemonicGenerator::new();
+        assert_eq!(g.get_stage(), MnemonicBackupStage::Inactive);
+        g.start(12).unwrap();
+        assert_eq!(g.get_stage(), MnemonicBackupStage::ShowWords);
+        assert_eq!(g.get_words().len(), 12);
+        let words = g.get_words().clone();
+
+        // cannot confirm before words are noted
+        assert!(g.confirm().is_err());
+        g.words_noted();
+        assert_eq!(g.get_stage(), MnemonicBackupStage::ConfirmWords);
+
+        // wrong words
+        assert!(g.confirm().is_err());
+        assert_eq!(g.get_stage(), MnemonicBackupStage::ConfirmWords);
+
+        let numbers = g.get_challenge_word_numbers();
+        assert_eq!(numbers.len(), 3);
+        for (i, n) in numbers.iter().enumerate() {
+            g.challenge_inputs[i] = format!(" {} ", words[n - 1].to_uppercase());
+        }
+        let phrase = g.confirm().unwrap();
+        assert_eq!(phrase, words.join(" "));
+        assert_eq!(g.get_stage(), MnemonicBackupStage::Inactive);
+        assert!(g.get_words().is_empty());
+    }
+
+    #[test]
+    fn test_generate_24() {
+        let mut g = MnemonicGenerator::new();
+        g.start(24).unwrap();
+        assert_eq!(g.get_words().len(), 24);
+        g.reset();
+        assert_eq!(g.get_stage(), MnemonicBackupStage::Inactive);
+    }
+}
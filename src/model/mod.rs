@@ -1,7 +1,13 @@
+pub mod data_backup;
+pub mod delegation_ledger;
+pub mod delegation_verifier;
 pub mod delegator;
 pub mod keystore;
 pub mod keystr_model;
+pub mod mnemonic_generator;
+pub mod rotation;
 pub mod security_settings;
 pub mod settings;
 pub mod signer;
 pub mod status_messages;
+pub mod vanity;
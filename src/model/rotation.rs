@@ -0,0 +1,87 @@
+use crate::base::error::Error;
+use crate::model::keystore::KeySigner;
+
+use nostr::prelude::{EventBuilder, Kind, Tag, ToBech32, XOnlyPublicKey};
+use nostr::Event;
+
+/// Kind of the migration event, as in the NIP-41 draft (key migration)
+pub(crate) const MIGRATION_EVENT_KIND: u64 = 1777;
+
+/// Model for rotating the key of an identity, e.g. when it is suspected compromised:
+/// a new identity with a new key takes its place, the old key announces the migration
+/// with a signed event, and the old identity is kept as retired (not deleted).
+pub(crate) struct Rotation {
+    /// Input for the name of the new identity
+    pub new_name_input: String,
+    /// Whether to re-issue the delegations of the old key still in effect (from the ledger),
+    /// signed by the new key
+    pub reissue_delegations: bool,
+    /// Migration event (JSON) of the last rotation, to be published
+    migration_event: Option<String>,
+}
+
+impl Rotation {
+    pub fn new() -> Self {
+        Self {
+            new_name_input: String::new(),
+            reissue_delegations: false,
+            migration_event: None,
+        }
+    }
+
+    /// Create the migration event: signed by the old key, it points to the new public key
+    /// (in a 'p' tag, and in the content as npub)
+    pub fn migration_event(
+        old_signer: &KeySigner,
+        new_public_key: &XOnlyPublicKey,
+    ) -> Result<Event, Error> {
+        let content = format!(
+            "This key has been retired, it is replaced by {}",
+            new_public_key.to_bech32()?
+        );
+        let unsigned = EventBuilder::new(
+            Kind::Custom(MIGRATION_EVENT_KIND),
+            content,
+            &[Tag::PubKey(*new_public_key, None)],
+        )
+        .to_unsigned_event(old_signer.get_public_key());
+        let signature = old_signer.sign(unsigned.id.as_bytes().to_vec())?;
+        Ok(unsigned.add_signature(signature)?)
+    }
+
+    /// A rotation was done, keep its migration event for showing
+    pub fn set_migration_event(&mut self, migration_event: String) {
+        self.new_name_input = String::new();
+        self.migration_event = Some(migration_event);
+    }
+
+    /// Migration event (JSON) of the last rotation, if not hidden
+    pub fn get_migration_event(&self) -> Option<&String> {
+        self.migration_event.as_ref()
+    }
+
+    pub fn hide_migration_event(&mut self) {
+        self.migration_event = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::secret::SecretKeyHolder;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_migration_event() {
+        let old_signer = KeySigner::new(Arc::new(SecretKeyHolder::generate()));
+        let new_public_key = SecretKeyHolder::generate().public_key();
+        let event = Rotation::migration_event(&old_signer, &new_public_key).unwrap();
+        assert!(event.verify().is_ok());
+        assert_eq!(event.pubkey, old_signer.get_public_key());
+        assert_eq!(event.kind, Kind::Custom(MIGRATION_EVENT_KIND));
+        assert_eq!(event.tags, vec![Tag::PubKey(new_public_key, None)]);
+        assert!(event
+            .content
+            .ends_with(&new_public_key.to_bech32().unwrap()));
+    }
+}
@@ -1,11 +1,54 @@
+use crate::base::encrypt::{Encrypt, KdfParams};
 use serde::{Deserialize, Serialize};
 
 use std::fmt;
+use std::time::Duration;
 
 /// Security-related settings
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct SecuritySettings {
     pub security_level: SecurityLevel,
+    /// Password-based key derivation function used for encrypting keys
+    #[serde(default)]
+    pub kdf: KdfKind,
+    /// Cost of password-based key derivation (scrypt log2 rounds) used for encrypting keys
+    #[serde(default = "SecuritySettings::default_kdf_log2_rounds")]
+    pub kdf_log2_rounds: u8,
+    /// Lock (forget the decrypted secret key) after this many minutes of inactivity, 0 for never
+    #[serde(default)]
+    pub auto_lock_minutes: u32,
+}
+
+/// Password-based key derivation function for encrypting keys; chosen by the user
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum KdfKind {
+    /// scrypt, with the cost from the settings (encrypted format version 1)
+    #[default]
+    Scrypt,
+    /// Argon2id, memory-hard, with default parameters (encrypted format version 2)
+    Argon2id,
+}
+
+impl fmt::Display for KdfKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KdfKind::Scrypt => write!(f, "scrypt"),
+            KdfKind::Argon2id => write!(f, "Argon2id"),
+        }
+    }
+}
+
+pub(crate) static KDF_KINDS: &[KdfKind] = &[KdfKind::Scrypt, KdfKind::Argon2id];
+
+impl Default for SecuritySettings {
+    fn default() -> Self {
+        Self {
+            security_level: SecurityLevel::default(),
+            kdf: KdfKind::default(),
+            kdf_log2_rounds: Self::default_kdf_log2_rounds(),
+            auto_lock_minutes: 0,
+        }
+    }
 }
 
 /// Security level regarding secret key handling/persistence; chosen by the user
@@ -47,6 +90,34 @@ impl SecuritySettings {
         }
     }
 
+    fn default_kdf_log2_rounds() -> u8 {
+        Encrypt::default_log2_rounds()
+    }
+
+    /// Key derivation function and parameters to use for encrypting keys
+    pub(crate) fn kdf_params(&self) -> KdfParams {
+        match self.kdf {
+            KdfKind::Scrypt => KdfParams::Scrypt {
+                log2_rounds: self.kdf_log2_rounds,
+            },
+            KdfKind::Argon2id => KdfParams::default_argon2id(),
+        }
+    }
+
+    /// True if the given key derivation is weaker than the one in the settings
+    pub(crate) fn is_kdf_weak(&self, kdf: &KdfParams) -> bool {
+        kdf.is_weaker_than(&self.kdf_params())
+    }
+
+    /// Inactivity period after which secret keys are locked, if auto-lock is on
+    pub fn auto_lock_timeout(&self) -> Option<Duration> {
+        if self.auto_lock_minutes == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(60 * self.auto_lock_minutes as u64))
+        }
+    }
+
     pub fn allows_persist(&self) -> bool {
         self.security_level == SecurityLevel::PersistMandatoryPassword
             || self.security_level == SecurityLevel::PersistOptionalPassword
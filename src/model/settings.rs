@@ -1,32 +1,173 @@
+use crate::base::encrypt::Encrypt;
 use crate::base::error::Error;
 use crate::base::storage::Storage;
-use crate::model::security_settings::{SecurityLevel, SecuritySettings};
+use crate::base::storage_backend::{FileStorage, StorageBackend};
+use crate::model::security_settings::{KdfKind, SecurityLevel, SecuritySettings};
+use crate::model::status_messages::StatusMessages;
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Decryption time targeted by the encryption cost calibration
+const KDF_CALIBRATION_TARGET: Duration = Duration::from_secs(1);
+/// Max auto-lock timeout, in minutes (one day)
+const AUTO_LOCK_MAX_MINUTES: u32 = 24 * 60;
 
 /// Settings
 #[readonly::make]
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct Settings {
     #[readonly]
     pub security: SecuritySettings,
+    /// Input for encryption cost (scrypt log2 rounds)
+    #[serde(skip)]
+    pub kdf_log2_rounds_input: String,
+    /// Input for auto-lock timeout (minutes)
+    #[serde(skip)]
+    pub auto_lock_minutes_input: String,
+    /// Where settings are saved to
+    #[serde(skip, default = "FileStorage::new_shared")]
+    storage: Arc<dyn StorageBackend>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::new(FileStorage::new_shared())
+    }
 }
 
 impl Settings {
+    pub fn new(storage: Arc<dyn StorageBackend>) -> Self {
+        Self {
+            security: SecuritySettings::default(),
+            kdf_log2_rounds_input: String::new(),
+            auto_lock_minutes_input: String::new(),
+            storage,
+        }
+    }
+
     pub fn set_security_level(&mut self, level: SecurityLevel) {
         self.security.security_level = level;
         let _res = self.save();
     }
 
+    pub fn set_kdf(&mut self, kdf: KdfKind) {
+        self.security.kdf = kdf;
+        let _res = self.save();
+    }
+
+    pub fn set_kdf_log2_rounds(&mut self, log2_rounds: u8) -> Result<(), Error> {
+        if log2_rounds < Encrypt::min_log2_rounds() || log2_rounds > Encrypt::max_log2_rounds() {
+            return Err(Error::SettingsInvalidKdfCost);
+        }
+        self.security.kdf_log2_rounds = log2_rounds;
+        self.save()
+    }
+
+    /// Action to set the encryption cost from the input
+    pub fn set_kdf_log2_rounds_action(&mut self, status: &mut StatusMessages) {
+        let res = match self.kdf_log2_rounds_input.trim().parse::<u8>() {
+            Err(_) => Err(Error::SettingsInvalidKdfCost),
+            Ok(log2_rounds) => self.set_kdf_log2_rounds(log2_rounds),
+        };
+        match res {
+            Err(e) => status.set_error_err(&e),
+            Ok(_) => {
+                self.kdf_log2_rounds_input = String::new();
+                status.set(&format!(
+                    "Encryption cost set to {}, used when saving keys",
+                    self.security.kdf_log2_rounds
+                ));
+            }
+        }
+    }
+
+    /// Action to measure key derivation speed, and suggest an encryption cost in the input
+    pub fn calibrate_kdf_action(&mut self, status: &mut StatusMessages) {
+        let log2_rounds = Encrypt::calibrate_log2_rounds(KDF_CALIBRATION_TARGET);
+        self.kdf_log2_rounds_input = log2_rounds.to_string();
+        status.set(&format!(
+            "Suggested encryption cost on this machine: {} (about {} s to decrypt), press Set to use it",
+            log2_rounds,
+            KDF_CALIBRATION_TARGET.as_secs()
+        ));
+    }
+
+    pub fn set_auto_lock_minutes(&mut self, minutes: u32) -> Result<(), Error> {
+        if minutes > AUTO_LOCK_MAX_MINUTES {
+            return Err(Error::SettingsInvalidAutoLock);
+        }
+        self.security.auto_lock_minutes = minutes;
+        self.save()
+    }
+
+    /// Action to set the auto-lock timeout from the input
+    pub fn set_auto_lock_action(&mut self, status: &mut StatusMessages) {
+        let res = match self.auto_lock_minutes_input.trim().parse::<u32>() {
+            Err(_) => Err(Error::SettingsInvalidAutoLock),
+            Ok(minutes) => self.set_auto_lock_minutes(minutes),
+        };
+        match res {
+            Err(e) => status.set_error_err(&e),
+            Ok(_) => {
+                self.auto_lock_minutes_input = String::new();
+                if self.security.auto_lock_minutes == 0 {
+                    status.set("Auto-lock turned off");
+                } else {
+                    status.set(&format!(
+                        "Secret keys will be locked after {} minutes of inactivity",
+                        self.security.auto_lock_minutes
+                    ));
+                }
+            }
+        }
+    }
+
     pub fn save(&self) -> Result<(), Error> {
         let str = serde_json::to_string(&self)?;
-        Storage::check_create_folder()?;
-        fs::write(Storage::settings_file(), str)?;
+        self.storage
+            .write(&Storage::settings_file(), str.as_bytes(), false)?;
         Ok(())
     }
 
-    pub fn load() -> Result<Self, Error> {
-        let str = fs::read_to_string(Storage::settings_file())?;
-        Ok(serde_json::from_str::<Self>(&str)?)
+    pub fn load(storage: Arc<dyn StorageBackend>) -> Result<Self, Error> {
+        let str = storage.read_to_string(&Storage::settings_file())?;
+        let mut settings = serde_json::from_str::<Self>(&str)?;
+        settings.storage = storage;
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::storage_backend::memory::MemoryStorage;
+    use crate::model::security_settings::SECURITY_LEVELS;
+
+    #[test]
+    fn test_save_load() {
+        let storage = MemoryStorage::new_shared();
+        assert!(Settings::load(storage.clone()).is_err());
+
+        for level in SECURITY_LEVELS {
+            let mut settings = Settings::new(storage.clone());
+            settings.set_security_level(*level);
+            settings.set_auto_lock_minutes(15).unwrap();
+            assert!(!storage.is_private(&Storage::settings_file()).unwrap());
+
+            let mut loaded = Settings::load(storage.clone()).unwrap();
+            assert_eq!(loaded.security.security_level, *level);
+            assert_eq!(
+                loaded.security.allows_persist(),
+                *level != SecurityLevel::Never
+            );
+            assert_eq!(loaded.security.auto_lock_minutes, 15);
+            // saved to the same storage
+            loaded.set_kdf(KdfKind::Argon2id);
+            assert_eq!(
+                Settings::load(storage.clone()).unwrap().security.kdf,
+                KdfKind::Argon2id
+            );
+        }
     }
 }
@@ -1,12 +1,13 @@
 use crate::base::error::Error;
+use crate::model::delegation_ledger::{DelegationRecord, DelegationSource};
 use crate::model::keystore::KeySigner;
 use crate::model::keystr_model::{Event, EVENT_QUEUE};
 use crate::model::status_messages::StatusMessages;
 
 use nostr::nips::nip46::{Message, Request};
 use nostr::prelude::{
-    DelegationResult, DelegationTag, EventBuilder, Filter, Keys, Kind, NostrConnectURI, ToBech32,
-    XOnlyPublicKey,
+    Conditions, DelegationResult, DelegationTag, EventBuilder, Filter, Keys, Kind, NostrConnectURI,
+    ToBech32, XOnlyPublicKey,
 };
 use nostr_sdk::prelude::{
     decrypt, Client, Options, RelayPoolNotification, RelayStatus, Response, Timestamp,
@@ -37,7 +38,10 @@ pub(crate) struct SignerConnection {
     status: StatusMessages,
     pub relay_str: String,
     relay_client: Client,
-    key_signer: KeySigner,
+    /// Public key of the signing identity
+    signer_pubkey: XOnlyPublicKey,
+    /// None while the secret key is locked; requests are queued until unlock
+    key_signer: Mutex<Option<KeySigner>>,
     /// Holds pending requests (mostly Sign requests), and can handle them
     requests: Mutex<Vec<SignatureReqest>>,
 }
@@ -85,7 +89,8 @@ impl Signer {
             client_pubkey: connect_client_id_pubkey,
             status: self.status.clone(),
             app_id_keys: self.app_id_keys.clone(),
-            key_signer: key_signer.clone(),
+            signer_pubkey: key_signer.get_public_key(),
+            key_signer: Mutex::new(Some(key_signer.clone())),
             requests: Mutex::new(Vec::new()),
         });
 
@@ -122,6 +127,26 @@ impl Signer {
         self.connection = None;
     }
 
+    /// The secret key got locked: stop processing, queue incoming requests
+    pub fn lock(&mut self, status: &mut StatusMessages) {
+        if let Some(conn) = &self.connection {
+            conn.lock();
+            status.set("Signer locked, requests are queued until unlock");
+        }
+    }
+
+    /// The secret key got unlocked: resume processing, incl. the queued requests
+    pub fn unlock(&mut self, key_signer: KeySigner, status: &mut StatusMessages) {
+        if let Some(conn) = &self.connection {
+            if conn.is_locked() && conn.unlock(key_signer) {
+                status.set(&format!(
+                    "Signer unlocked, {} pending request(s)",
+                    conn.get_pending_count()
+                ));
+            }
+        }
+    }
+
     pub fn get_connection_status(&self) -> ConnectionStatus {
         match &self.connection {
             None => ConnectionStatus::NotConnected,
@@ -141,12 +166,22 @@ impl Signer {
         }
     }
 
-    pub fn pending_process_first_action(&mut self, status: &mut StatusMessages) {
+    /// Action to process the first pending request; returns the delegation created, if any
+    pub fn pending_process_first_action(
+        &mut self,
+        status: &mut StatusMessages,
+    ) -> Option<DelegationRecord> {
         if let Some(conn) = &self.connection {
             let first_desc = conn.get_first_request_description();
-            conn.action_first_req_process();
-            status.set(&format!("Processed request '{}'", first_desc));
+            match conn.action_first_req_process() {
+                Err(e) => status.set_error_err(&e),
+                Ok(delegation) => {
+                    status.set(&format!("Processed request '{}'", first_desc));
+                    return delegation;
+                }
+            }
         }
+        None
     }
 
     pub fn pending_ignore_first_action(&mut self, status: &mut StatusMessages) {
@@ -200,51 +235,71 @@ impl SignerConnection {
         }
     }
 
-    pub fn action_first_req_process(&self) {
-        let mut locked = self.requests.lock().unwrap();
-        let first = locked.first();
-        if let Some(req) = first {
+    /// Current key signer, None if locked
+    fn get_key_signer(&self) -> Option<KeySigner> {
+        self.key_signer.lock().unwrap().clone()
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.key_signer.lock().unwrap().is_none()
+    }
+
+    fn lock(&self) {
+        *self.key_signer.lock().unwrap() = None;
+    }
+
+    /// Set the key signer again; returns false if it is not the key of this connection
+    fn unlock(&self, key_signer: KeySigner) -> bool {
+        if key_signer.get_public_key() != self.signer_pubkey {
+            return false;
+        }
+        *self.key_signer.lock().unwrap() = Some(key_signer);
+        true
+    }
+
+    /// Process the (first) pending request. It stays pending if the signer is locked.
+    /// Returns the delegation created, if it was a delegation request.
+    pub fn action_first_req_process(&self) -> Result<Option<DelegationRecord>, Error> {
+        let key_signer = match self.get_key_signer() {
+            None => return Err(Error::SignerLocked),
+            Some(ks) => ks,
+        };
+        let mut delegation = None;
+        let mut requests = self.requests.lock().unwrap();
+        if let Some(req) = requests.first() {
             if let Message::Request { id, .. } = &req.req {
                 if let Ok(request) = &req.req.to_request() {
-                    match request {
-                        Request::SignEvent(_unsigned_event) => {
-                            if let Ok(resp_opt) =
-                                response_for_message(id, request, &self.key_signer)
-                            {
-                                if let Some(response_msg) = resp_opt {
-                                    let _ = send_message_blocking(
-                                        &self.relay_client,
-                                        &response_msg,
-                                        &req.sender_pubkey,
-                                        tokio::runtime::Handle::current(),
-                                    );
-                                }
-                            }
-                        }
+                    // Any request: non-interactive ones are also queued while locked
+                    let response = match request {
                         Request::Delegate {
-                            public_key: _,
-                            conditions: _,
-                        } => {
-                            if let Ok(resp_opt) =
-                                response_for_message(id, request, &self.key_signer)
-                            {
-                                if let Some(response_msg) = resp_opt {
-                                    let _ = send_message_blocking(
-                                        &self.relay_client,
-                                        &response_msg,
-                                        &req.sender_pubkey,
-                                        tokio::runtime::Handle::current(),
-                                    );
-                                }
-                            }
-                        }
-                        // ignore other requests
-                        _ => {}
+                            public_key,
+                            conditions,
+                        } => delegate_response(
+                            id,
+                            public_key,
+                            conditions,
+                            &key_signer,
+                            &self.get_client_npub(),
+                        )
+                        .map(|(response_msg, record)| {
+                            delegation = Some(record);
+                            Some(response_msg)
+                        }),
+                        _ => response_for_message(id, request, &key_signer),
+                    };
+                    if let Ok(Some(response_msg)) = response {
+                        let _ = send_message_blocking(
+                            &self.relay_client,
+                            &response_msg,
+                            &req.sender_pubkey,
+                            tokio::runtime::Handle::current(),
+                        );
                     }
                 }
             }
+            let _ = requests.remove(0);
         }
-        let _ = locked.remove(0);
+        Ok(delegation)
     }
 
     /// Remove the (first) pending request
@@ -505,23 +560,44 @@ fn response_for_message(
             public_key,
             conditions,
         } => {
-            let delegation_tag =
-                DelegationTag::new(&key_signer.keys, public_key.clone(), conditions.clone())?;
-            let delegator_result = DelegationResult {
-                from: key_signer.get_public_key(),
-                to: public_key.clone(),
-                cond: conditions.clone(),
-                sig: delegation_tag.signature(),
-            };
-            Ok(Some(Message::response(
-                req_id.clone(),
-                Response::Delegate(delegator_result),
-            )))
+            let (response_msg, _record) =
+                delegate_response(req_id, public_key, conditions, key_signer, "")?;
+            Ok(Some(response_msg))
         }
         _ => Ok(None),
     }
 }
 
+/// Create the delegation for a Delegate request, and the response message for it.
+/// The delegation is also returned, for the ledger.
+fn delegate_response(
+    req_id: &String,
+    public_key: &XOnlyPublicKey,
+    conditions: &Conditions,
+    key_signer: &KeySigner,
+    client_npub: &str,
+) -> Result<(Message, DelegationRecord), Error> {
+    let delegation_tag = key_signer
+        .with_keys(|keys| DelegationTag::new(keys, public_key.clone(), conditions.clone()))??;
+    let record = DelegationRecord::new(
+        &key_signer.get_public_key(),
+        public_key,
+        &conditions.to_string(),
+        &delegation_tag,
+        DelegationSource::SignerRequest(client_npub.to_string()),
+    )?;
+    let delegator_result = DelegationResult {
+        from: key_signer.get_public_key(),
+        to: public_key.clone(),
+        cond: conditions.clone(),
+        sig: delegation_tag.signature(),
+    };
+    Ok((
+        Message::response(req_id.clone(), Response::Delegate(delegator_result)),
+        record,
+    ))
+}
+
 async fn handle_request(
     connection: Arc<SignerConnection>,
     msg: &Message,
@@ -552,8 +628,19 @@ async fn handle_request(
                 }
                 _ => {
                     // Non-interactive requests: try to create response, send it
-                    let key_signer = &connection.key_signer;
-                    let response_message = response_for_message(id, req, key_signer)?;
+                    let key_signer = match connection.get_key_signer() {
+                        None => {
+                            // Locked, queue it until unlock
+                            connection.add_request(msg.clone(), sender_pubkey.clone());
+                            EVENT_QUEUE.push(Event::SignerNewRequest)?;
+                            connection
+                                .status
+                                .set("Signer is locked, request queued until unlock");
+                            return Ok(());
+                        }
+                        Some(ks) => ks,
+                    };
+                    let response_message = response_for_message(id, req, &key_signer)?;
                     match response_message {
                         Some(m) => {
                             // We return a response message right away
@@ -577,7 +664,11 @@ async fn handle_request(
 
 #[cfg(test)]
 mod test {
-    use super::{response_for_message, KeySigner, Keys, Request, XOnlyPublicKey};
+    use super::{
+        delegate_response, response_for_message, Arc, DelegationSource, KeySigner, Request,
+        XOnlyPublicKey,
+    };
+    use crate::base::secret::SecretKeyHolder;
     use nostr::prelude::{
         Condition, Conditions, EventId, FromBech32, KeyPair, Secp256k1, SecretKey, Timestamp,
         UnsignedEvent,
@@ -592,9 +683,7 @@ mod test {
         let req: Request = Request::Describe;
         let req_id: String = "id001".to_string();
         let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
-        let key_signer: KeySigner = KeySigner {
-            keys: Keys::new(sk),
-        };
+        let key_signer: KeySigner = KeySigner::new(Arc::new(SecretKeyHolder::new(&sk)));
         let resp_msg = response_for_message(&req_id, &req, &key_signer).unwrap();
         assert!(resp_msg.is_some());
         assert_eq!(resp_msg.unwrap().as_json(), "{\"error\":null,\"id\":\"id001\",\"result\":[\"describe\",\"get_public_key\",\"sign_event\"]}");
@@ -605,9 +694,7 @@ mod test {
         let req: Request = Request::GetPublicKey;
         let req_id: String = "id001".to_string();
         let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
-        let key_signer: KeySigner = KeySigner {
-            keys: Keys::new(sk),
-        };
+        let key_signer: KeySigner = KeySigner::new(Arc::new(SecretKeyHolder::new(&sk)));
         let resp_msg = response_for_message(&req_id, &req, &key_signer).unwrap();
         assert!(resp_msg.is_some());
         assert_eq!(resp_msg.unwrap().as_json(), "{\"error\":null,\"id\":\"id001\",\"result\":\"dd73f1d141b01badbb4049c5bcaa2cd261501c0c356774fada3db425c7d6e413\"}");
@@ -629,9 +716,7 @@ mod test {
         let req: Request = Request::SignEvent(unisgned_event);
         let req_id: String = "id001".to_string();
         let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
-        let key_signer: KeySigner = KeySigner {
-            keys: Keys::new(sk),
-        };
+        let key_signer: KeySigner = KeySigner::new(Arc::new(SecretKeyHolder::new(&sk)));
         let resp_msg = response_for_message(&req_id, &req, &key_signer).unwrap();
         assert!(resp_msg.is_some());
         // Cannot compare json, as signature changes
@@ -669,9 +754,7 @@ mod test {
         };
         let req_id: String = "id001".to_string();
         let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
-        let key_signer: KeySigner = KeySigner {
-            keys: Keys::new(sk),
-        };
+        let key_signer: KeySigner = KeySigner::new(Arc::new(SecretKeyHolder::new(&sk)));
         let resp_msg = response_for_message(&req_id, &req, &key_signer).unwrap();
         assert!(resp_msg.is_some());
         // Cannot compare json, as signature changes
@@ -706,4 +789,32 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_delegate_response_record() {
+        let delegatee_pubkey = XOnlyPublicKey::from_bech32(NPUB2).unwrap();
+        let mut conditions: Conditions = Conditions::default();
+        conditions.add(Condition::Kind(1));
+        conditions.add(Condition::CreatedBefore(1686693500));
+        let sk: SecretKey = SecretKey::from_bech32(NSEC1).unwrap();
+        let key_signer: KeySigner = KeySigner::new(Arc::new(SecretKeyHolder::new(&sk)));
+        let (resp_msg, record) = delegate_response(
+            &"id001".to_string(),
+            &delegatee_pubkey,
+            &conditions,
+            &key_signer,
+            NPUB2,
+        )
+        .unwrap();
+        assert_eq!(resp_msg.is_request(), false);
+        assert_eq!(record.delegatee, NPUB2);
+        assert_eq!(record.conditions, "kind=1&created_at<1686693500");
+        assert_eq!(record.valid_until(), Some(1686693500));
+        assert_eq!(record.signature.len(), 128);
+        assert!(record.tag.contains(&record.signature));
+        assert_eq!(
+            record.source,
+            DelegationSource::SignerRequest(NPUB2.to_string())
+        );
+    }
 }
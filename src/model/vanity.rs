@@ -0,0 +1,353 @@
+use crate::base::error::Error;
+use crate::base::secret::SecretKeyHolder;
+use crate::model::status_messages::StatusMessages;
+
+use nostr::prelude::{SecretKey, ToBech32, SECP256K1};
+use nostr::secp256k1::rand;
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Characters allowed in bech32 strings (after the separator)
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+/// Human-readable part and separator of npub strings
+const NPUB_PREFIX: &str = "npub1";
+/// Max length of the pattern; each character makes the search 32 times longer
+const MAX_PATTERN_LEN: usize = 10;
+/// Number of keys a worker tries between updating the counter and checking for stop
+const BATCH_SIZE: u64 = 256;
+
+/// Where the pattern should be in the npub
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum VanityPosition {
+    /// Right after 'npub1'
+    #[default]
+    Prefix,
+    /// At the end (includes the checksum characters)
+    Suffix,
+}
+
+impl fmt::Display for VanityPosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VanityPosition::Prefix => write!(f, "starts with"),
+            VanityPosition::Suffix => write!(f, "ends with"),
+        }
+    }
+}
+
+pub(crate) static VANITY_POSITIONS: &[VanityPosition] =
+    &[VanityPosition::Prefix, VanityPosition::Suffix];
+
+/// Progress of a running search
+pub(crate) struct VanityProgress {
+    /// Number of keys tried so far
+    pub tried: u64,
+    pub keys_per_sec: f64,
+    /// Expected (average) number of keys to try
+    pub expected: f64,
+    /// Expected (average) total time of the search, at the current rate
+    pub expected_time: Option<Duration>,
+    pub elapsed: Duration,
+}
+
+/// State shared with the worker threads
+struct VanityRun {
+    stop: Arc<AtomicBool>,
+    tried: Arc<AtomicU64>,
+    result: Arc<Mutex<Option<SecretKeyHolder>>>,
+    pattern_len: usize,
+    started: Instant,
+}
+
+/// Model for searching a key whose npub starts (or ends) with a given pattern.
+/// The search runs on multiple threads, it can be cancelled, and progress is polled.
+pub(crate) struct VanitySearch {
+    pub pattern_input: String,
+    pub position: VanityPosition,
+    run: Option<VanityRun>,
+    /// The key found, until it is taken into use
+    found: Option<SecretKeyHolder>,
+}
+
+impl VanitySearch {
+    pub fn new() -> Self {
+        Self {
+            pattern_input: String::new(),
+            position: VanityPosition::default(),
+            run: None,
+            found: None,
+        }
+    }
+
+    /// Check that the pattern is not empty, not too long, and has only bech32 characters.
+    /// Returns it lowercase.
+    pub fn validate_pattern(pattern: &str) -> Result<String, Error> {
+        let pattern = pattern.trim().to_lowercase();
+        if pattern.is_empty()
+            || pattern.len() > MAX_PATTERN_LEN
+            || !pattern.chars().all(|c| BECH32_CHARSET.contains(c))
+        {
+            return Err(Error::VanityInvalidPattern);
+        }
+        Ok(pattern)
+    }
+
+    /// Check whether an npub matches the pattern
+    fn is_match(npub: &str, pattern: &str, position: VanityPosition) -> bool {
+        match position {
+            VanityPosition::Prefix => npub[NPUB_PREFIX.len()..].starts_with(pattern),
+            VanityPosition::Suffix => npub.ends_with(pattern),
+        }
+    }
+
+    /// Start the search on all available cores
+    pub fn start(&mut self) -> Result<(), Error> {
+        let threads = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        self.start_with_threads(threads)
+    }
+
+    fn start_with_threads(&mut self, threads: usize) -> Result<(), Error> {
+        let pattern = Self::validate_pattern(&self.pattern_input)?;
+        self.cancel();
+        self.found = None;
+        let run = VanityRun {
+            stop: Arc::new(AtomicBool::new(false)),
+            tried: Arc::new(AtomicU64::new(0)),
+            result: Arc::new(Mutex::new(None)),
+            pattern_len: pattern.len(),
+            started: Instant::now(),
+        };
+        for _ in 0..threads {
+            let stop = run.stop.clone();
+            let tried = run.tried.clone();
+            let result = run.result.clone();
+            let pattern = pattern.clone();
+            let position = self.position;
+            thread::spawn(move || Self::worker(&pattern, position, &stop, &tried, &result));
+        }
+        self.run = Some(run);
+        Ok(())
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Generate random keys until one matches, or stop is signalled
+    fn worker(
+        pattern: &str,
+        position: VanityPosition,
+        stop: &AtomicBool,
+        tried: &AtomicU64,
+        result: &Mutex<Option<SecretKeyHolder>>,
+    ) {
+        let mut rng = rand::thread_rng();
+        while !stop.load(Ordering::Relaxed) {
+            for _ in 0..BATCH_SIZE {
+                let secret_key = SecretKey::new(&mut rng);
+                let (public_key, _parity) = secret_key.x_only_public_key(SECP256K1);
+                let npub = match public_key.to_bech32() {
+                    Ok(npub) => npub,
+                    Err(_) => continue,
+                };
+                if Self::is_match(&npub, pattern, position) {
+                    if let Ok(mut result) = result.lock() {
+                        // first one wins
+                        if result.is_none() {
+                            *result = Some(SecretKeyHolder::new(&secret_key));
+                        }
+                    }
+                    stop.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
+            tried.fetch_add(BATCH_SIZE, Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.run.is_some()
+    }
+
+    /// Stop the search; the workers exit after their current batch
+    pub fn cancel(&mut self) {
+        if let Some(run) = self.run.take() {
+            run.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Check whether a matching key was found; if yes, the search is finished.
+    /// Returns true if a key was found now.
+    pub fn poll(&mut self) -> bool {
+        let result = self
+            .run
+            .as_ref()
+            .and_then(|run| run.result.lock().ok().and_then(|mut r| r.take()));
+        if result.is_none() {
+            return false;
+        }
+        self.cancel();
+        self.found = result;
+        true
+    }
+
+    /// Warning: Security-sensitive method!
+    /// Take the found key, to be used
+    pub fn take_found(&mut self) -> Option<SecretKeyHolder> {
+        self.found.take()
+    }
+
+    /// The npub of the found key, if there is one not yet used
+    pub fn get_found_npub(&self) -> Option<String> {
+        self.found
+            .as_ref()
+            .map(|secret_key| secret_key.public_key().to_bech32().unwrap_or_default())
+    }
+
+    pub fn get_progress(&self) -> Option<VanityProgress> {
+        self.run.as_ref().map(|run| {
+            let tried = run.tried.load(Ordering::Relaxed);
+            let elapsed = run.started.elapsed();
+            let keys_per_sec = if elapsed.as_secs_f64() > 0.0 {
+                tried as f64 / elapsed.as_secs_f64()
+            } else {
+                0.0
+            };
+            let expected = 32f64.powi(run.pattern_len as i32);
+            VanityProgress {
+                tried,
+                keys_per_sec,
+                expected,
+                expected_time: if keys_per_sec > 0.0 {
+                    Some(Duration::from_secs_f64(expected / keys_per_sec))
+                } else {
+                    None
+                },
+                elapsed,
+            }
+        })
+    }
+
+    pub fn start_action(&mut self, status: &mut StatusMessages) {
+        match self.start() {
+            Err(e) => status.set_error_err(&e),
+            Ok(_) => status.set(&format!(
+                "Searching for an npub that {} '{}'...",
+                self.position,
+                self.pattern_input.trim().to_lowercase()
+            )),
+        }
+    }
+
+    pub fn cancel_action(&mut self, status: &mut StatusMessages) {
+        if self.is_running() {
+            self.cancel();
+            status.set("Vanity search cancelled");
+        }
+    }
+}
+
+impl Drop for VanitySearch {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+impl VanityProgress {
+    /// Format a duration roughly, in the largest fitting unit
+    pub fn format_duration(duration: Duration) -> String {
+        let secs = duration.as_secs();
+        if secs < 120 {
+            format!("{} sec", secs)
+        } else if secs < 2 * 3600 {
+            format!("{} min", secs / 60)
+        } else if secs < 2 * 86400 {
+            format!("{} hours", secs / 3600)
+        } else if secs < 2 * 365 * 86400 {
+            format!("{} days", secs / 86400)
+        } else {
+            format!("{} years", secs / (365 * 86400))
+        }
+    }
+}
+
+impl fmt::Display for VanityProgress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Tried {} keys in {}, {:.0} keys/sec; expected ~{:.0} keys",
+            self.tried,
+            Self::format_duration(self.elapsed),
+            self.keys_per_sec,
+            self.expected
+        )?;
+        if let Some(expected_time) = self.expected_time {
+            write!(f, ", ~{} on average", Self::format_duration(expected_time))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_pattern() {
+        assert_eq!(VanitySearch::validate_pattern(" Sat ").unwrap(), "sat");
+        assert_eq!(VanitySearch::validate_pattern("q").unwrap(), "q");
+        // 'b', 'i', 'o', '1' are not in bech32
+        assert!(VanitySearch::validate_pattern("bob").is_err());
+        assert!(VanitySearch::validate_pattern("1").is_err());
+        assert!(VanitySearch::validate_pattern("").is_err());
+        assert!(VanitySearch::validate_pattern("qqqqqqqqqqq").is_err());
+    }
+
+    #[test]
+    fn test_is_match() {
+        let npub = "npub1rfze4zn25ezp6jqt5ejlhrajrfx0az72ed7cwvq0spr22k9rlnjq93lmd4";
+        assert!(VanitySearch::is_match(npub, "rfz", VanityPosition::Prefix));
+        assert!(!VanitySearch::is_match(
+            npub,
+            "npub",
+            VanityPosition::Prefix
+        ));
+        assert!(!VanitySearch::is_match(
+            npub,
+            "lmd4",
+            VanityPosition::Prefix
+        ));
+        assert!(VanitySearch::is_match(npub, "lmd4", VanityPosition::Suffix));
+        assert!(!VanitySearch::is_match(npub, "rfz", VanityPosition::Suffix));
+    }
+
+    #[test]
+    fn test_search() {
+        let mut v = VanitySearch::new();
+        v.pattern_input = "q".to_string();
+        v.position = VanityPosition::Suffix;
+        v.start_with_threads(2).unwrap();
+        assert!(v.is_running());
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while !v.poll() {
+            assert!(Instant::now() < deadline);
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!v.is_running());
+        assert!(v.get_found_npub().unwrap().ends_with('q'));
+        let secret_key = v.take_found().unwrap();
+        assert!(secret_key.public_key().to_bech32().unwrap().ends_with('q'));
+        assert!(v.get_found_npub().is_none());
+        assert!(!v.poll());
+
+        v.pattern_input = "qqqqqqqqqq".to_string();
+        v.start_with_threads(1).unwrap();
+        assert!(v.get_progress().is_some());
+        v.cancel();
+        assert!(!v.is_running());
+        assert!(v.get_progress().is_none());
+    }
+}
@@ -1,45 +1,107 @@
+use crate::base::paper_backup::PaperBackup;
+use crate::model::delegation_ledger::DelegationLedger;
+use crate::model::delegator::{Delegator, EVENT_KINDS};
+use crate::model::keystore::SubKey;
 use crate::model::keystr_model::{Action, Confirmation, Event, KeystrModel, Modal, EVENT_QUEUE};
-use crate::model::security_settings::{SecurityLevel, SECURITY_LEVELS};
+use crate::model::mnemonic_generator::MnemonicBackupStage;
+use crate::model::security_settings::{KdfKind, SecurityLevel, KDF_KINDS, SECURITY_LEVELS};
 use crate::model::signer::ConnectionStatus;
+use crate::model::vanity::{VanityPosition, VANITY_POSITIONS};
 use crate::ui::dialog::Dialog;
 
 use iced::widget::qr_code::QRCode;
-use iced::widget::{button, column, container, pick_list, row, text, text_input};
+use iced::widget::{
+    button, checkbox, column, container, pick_list, row, text, text_input, Column, Row,
+};
 use iced::{executor, subscription};
 use iced::{Alignment, Application, Command, Element, Length, Subscription, Theme};
 
+use std::time::Duration;
+
+/// How often inactivity is checked for auto-lock
+const AUTO_LOCK_CHECK_PERIOD: Duration = Duration::from_secs(10);
+/// How often a running vanity search is checked (also refreshes its progress)
+const VANITY_CHECK_PERIOD: Duration = Duration::from_millis(500);
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Tab {
     Keys,
+    SubKeys,
     Delegate,
     Signer,
 }
 
 #[derive(Debug, Clone)]
 pub(crate) enum Message {
+    BackupPathInput(String),
+    BackupPasswordInput(String),
+    BackupRepeatPasswordInput(String),
+    BackupRestorePathInput(String),
+    BackupRestorePasswordInput(String),
     ChangedReadonly(String),
     ModelAction(Action),
     ModelEvent(Event),
     NoOp,
     Refresh,
     SecurityLevelChange(SecurityLevel),
+    SettingsKdfChange(KdfKind),
+    SettingsKdfLog2RoundsInput(String),
+    SettingsAutoLockInput(String),
     TabSelect(Tab),
 
+    KeysIdentityNameInput(String),
     KeysPubkeyInput(String),
     KeysToggleHideSecretKey,
     KeysSecretkeyInput(String),
     KeysMnemonicInput(String),
+    KeysMnemonicPassphraseInput(String),
+    KeysMnemonicAccountInput(String),
+    KeysRevealMnemonicPasswordInput(String),
+    KeysNcryptsecInput(String),
+    KeysNcryptsecPasswordInput(String),
+    KeysImportFilePathInput(String),
+    KeysImportFilePasswordInput(String),
+    KeysPaperBackupPathInput(String),
     KeysDecryptPasswordInput(String),
+    KeysChangeOldPasswordInput(String),
+    KeysChangeNewPasswordInput(String),
+    KeysChangeRepeatPasswordInput(String),
+    KeysChangeLog2RoundsInput(String),
+    KeysUpgradePasswordInput(String),
     KeysSavePasswordInput(String),
     KeysSaveRepeatPasswordInput(String),
+    KeysShamirThresholdInput(String),
+    KeysShamirCountInput(String),
+    KeysShamirFolderInput(String),
+    KeysShamirRestoreInput(String),
+    KeysFrostThresholdInput(String),
+    KeysFrostCountInput(String),
+
+    KeysSubkeyLabelInput(String),
+    KeysRotateNameInput(String),
+    KeysRotateReissueToggled(bool),
 
     DelegateDeeChanged(String),
-    DelegateKindChanged(String),
+    DelegateSubkeySelected(SubKey),
+    DelegateKindToggled(u64, bool),
+    DelegateCustomKindInput(String),
+    DelegateCustomKindSelect,
+    DelegateTimeStartInput(String),
     DelegateTimeStartChanged(String),
+    DelegateTimeEndInput(String),
     DelegateTimeEndChanged(String),
-    DelegateTimeDaysChanged(String),
-    DelegateTimeDaysChangedNoUpdate(String),
+    DelegateTimeDurationInput(String),
+    DelegateTimeDurationChanged(String),
+    DelegationsExportPathInput(String),
+    DelegationVerifyTagInput(String),
+    DelegationVerifyDelegateeInput(String),
+    DelegationVerifyEventInput(String),
+    DelegationVerifyKindInput(String),
+    DelegationVerifyTimeInput(String),
 
+    MnemonicBackupWordInput(usize, String),
+    VanityPatternInput(String),
+    VanityPositionChange(VanityPosition),
     QRCode(String),
     QRCodeClose,
     SignerUriInput(String),
@@ -61,6 +123,7 @@ impl KeystrApp {
     fn tab_selector(&self) -> Element<Message> {
         row![
             button("Keys").on_press(Message::TabSelect(Tab::Keys)),
+            button("Sub-keys").on_press(Message::TabSelect(Tab::SubKeys)),
             button("Delegate").on_press(Message::TabSelect(Tab::Delegate)),
             button("Signer").on_press(Message::TabSelect(Tab::Signer)),
         ]
@@ -73,12 +136,12 @@ impl KeystrApp {
     fn tab_keys(&self) -> Element<Message> {
         let label_width = Length::Fixed(150.0);
 
-        let unlock_ui = if self.model.own_keys.is_encrypted_secret_key_set() {
+        let unlock_ui = if self.model.own_keys.is_locked() {
             column![row![
                 text("Password is needed to unlock secret key:").size(15),
                 text_input(
                     "enter password that was used for encrypting secret key",
-                    &self.model.own_keys.decrypt_password_input,
+                    self.model.own_keys.decrypt_password_input.as_str(),
                     Message::KeysDecryptPasswordInput,
                 )
                 .password()
@@ -95,9 +158,346 @@ impl KeystrApp {
         .spacing(5)
         .padding(0);
 
+        let lock_ui = if self.model.own_keys.can_lock() {
+            column![row![
+                text("Secret key is unlocked:").size(15),
+                button("Lock").on_press(Message::ModelAction(Action::KeysLock)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0)]
+        } else {
+            column![]
+        }
+        .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(0);
+
+        let mnemonic_preview_ui = Column::with_children(
+            self.model
+                .own_keys
+                .get_mnemonic_preview()
+                .iter()
+                .map(|(account, npub)| {
+                    row![
+                        text(format!("Account {}:", account))
+                            .size(15)
+                            .width(Length::Fixed(80.0)),
+                        text(npub).size(15),
+                        button("Use")
+                            .on_press(Message::KeysMnemonicAccountInput(account.to_string())),
+                    ]
+                    .align_items(Alignment::Center)
+                    .spacing(5)
+                    .into()
+                })
+                .collect::<Vec<Element<Message>>>(),
+        )
+        .spacing(5)
+        .padding(0);
+
+        let weak_encryption_ui = if let Some(kdf) = self
+            .model
+            .own_keys
+            .get_weak_stored_kdf(&self.model.settings.security)
+        {
+            column![row![
+                text(format!(
+                    "Saved key uses a weaker encryption ({}) than the setting:",
+                    kdf
+                ))
+                .size(15),
+                text_input(
+                    "password",
+                    self.model.own_keys.upgrade_password_input.as_str(),
+                    Message::KeysUpgradePasswordInput,
+                )
+                .password()
+                .size(15),
+                button("Upgrade").on_press(Message::ModelAction(Action::KeysUpgradeEncryption)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0)]
+        } else {
+            column![]
+        }
+        .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(0);
+
+        let key_versions = self.model.own_keys.get_key_versions();
+        let key_versions_ui = if key_versions.is_empty() {
+            column![]
+        } else {
+            column![
+                text("Previous versions of the saved key (restoring keeps the current one):")
+                    .size(15),
+                Column::with_children(
+                    key_versions
+                        .iter()
+                        .map(|(version, replaced_at)| {
+                            row![
+                                text(format!(
+                                    "Version {}, replaced {}",
+                                    version,
+                                    PaperBackup::format_date(*replaced_at)
+                                ))
+                                .size(15),
+                                button("Restore").on_press(Message::ModelAction(
+                                    Action::KeysRestoreVersion(*version)
+                                )),
+                            ]
+                            .align_items(Alignment::Center)
+                            .spacing(5)
+                            .padding(0)
+                            .into()
+                        })
+                        .collect::<Vec<Element<Message>>>(),
+                )
+                .spacing(5),
+            ]
+        }
+        .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(0);
+
+        let shamir_shares_ui = if self.model.own_keys.get_shamir_shares().is_empty() {
+            column![]
+        } else {
+            column![
+                text("Shares (keep them secret, store them in separate places!):").size(15),
+                Column::with_children(
+                    self.model
+                        .own_keys
+                        .get_shamir_shares()
+                        .iter()
+                        .enumerate()
+                        .map(|(i, share)| {
+                            row![
+                                text(format!("Share {}:", i + 1))
+                                    .size(15)
+                                    .width(Length::Fixed(60.0)),
+                                button("QR").on_press(Message::QRCode(share.to_string())),
+                                text_input("", share, Message::ChangedReadonly).size(15),
+                            ]
+                            .align_items(Alignment::Center)
+                            .spacing(5)
+                            .into()
+                        })
+                        .collect::<Vec<Element<Message>>>(),
+                )
+                .spacing(5)
+                .padding(0),
+                row![
+                    text_input(
+                        "folder to save the shares to, one file each",
+                        &self.model.own_keys.shamir_folder_input,
+                        Message::KeysShamirFolderInput,
+                    )
+                    .size(15),
+                    button("Save shares").on_press(Message::ModelAction(Action::KeysShamirSave)),
+                    button("Hide shares").on_press(Message::ModelAction(Action::KeysShamirHide)),
+                ]
+                .align_items(Alignment::Fill)
+                .spacing(5)
+                .padding(0),
+            ]
+        }
+        .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(0);
+
+        let shamir_restored_ui = if let Some(npub) = self.model.own_keys.get_shamir_restored_npub()
+        {
+            column![row![
+                text("Restored key:").size(15),
+                text(npub).size(15),
+                button("Accept").on_press(Message::ModelAction(Action::KeysShamirAccept)),
+                button("Cancel").on_press(Message::ModelAction(Action::KeysShamirHide)),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0)]
+        } else {
+            column![]
+        }
+        .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(0);
+
+        let vanity_status_ui = if let Some(progress) = self.model.vanity.get_progress() {
+            column![text(progress.to_string()).size(15)]
+        } else if let Some(npub) = self.model.vanity.get_found_npub() {
+            column![row![
+                text("Found:").size(15),
+                text(npub).size(15),
+                button("Use").on_press(Message::ModelAction(Action::VanityAccept)),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0)]
+        } else {
+            column![]
+        }
+        .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(0);
+
+        let frost_info_ui =
+            if let Some((threshold, count, indexes)) = self.model.own_keys.get_frost_info() {
+                let indexes: Vec<String> = indexes.iter().map(|i| i.to_string()).collect();
+                let can_sign_alone = indexes.len() >= threshold as usize;
+                column![
+                    text(format!(
+                        "FROST {}-of-{} group key, key shares held here: {}{}",
+                        threshold,
+                        count,
+                        indexes.join(", "),
+                        if can_sign_alone {
+                            ""
+                        } else {
+                            "  (not enough to sign here)"
+                        }
+                    ))
+                    .size(15),
+                    if can_sign_alone {
+                        text(format!(
+                            "Warning: at least {} shares are here, under the same password, so this folder alone \
+                            has full signing power. Move share files to other devices for the threshold to protect the key.",
+                            threshold
+                        ))
+                        .size(15)
+                    } else {
+                        text("")
+                    },
+                ]
+            } else {
+                column![]
+            }
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0);
+
+        let retirement_ui = if let Some(retirement) = self.model.own_keys.get_retirement() {
+            column![
+                text(format!(
+                    "Retired on {}, replaced by {}",
+                    PaperBackup::format_date(retirement.retired_at),
+                    retirement.successor
+                ))
+                .size(15),
+                row![
+                    text("Migration event:").size(15),
+                    button("QR").on_press(Message::QRCode(retirement.migration_event.clone())),
+                    text_input("", &retirement.migration_event, Message::ChangedReadonly).size(15),
+                ]
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(0),
+            ]
+        } else {
+            column![]
+        }
+        .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(0);
+
+        let migration_event_ui = if let Some(event) = self.model.rotation.get_migration_event() {
+            column![
+                text("Migration event, signed by the old key; publish it to your relays:").size(15),
+                row![
+                    button("QR").on_press(Message::QRCode(event.clone())),
+                    text_input("", event, Message::ChangedReadonly).size(15),
+                    button("Hide").on_press(Message::ModelAction(Action::KeysRotateHideEvent)),
+                ]
+                .align_items(Alignment::Center)
+                .spacing(5)
+                .padding(0),
+            ]
+        } else {
+            column![]
+        }
+        .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(0);
+
+        let stored_mnemonic_ui =
+            if let Some(mnemonic) = self.model.own_keys.get_revealed_mnemonic() {
+                column![
+                    text("Stored mnemonic (keep it secret!):").size(15),
+                    text(&mnemonic.mnemonic).size(15),
+                    text(format!(
+                        "Account: {}   Derivation path: {}{}",
+                        mnemonic.account,
+                        mnemonic.derivation_path,
+                        if mnemonic.has_passphrase {
+                            "   (with passphrase, not stored)"
+                        } else {
+                            ""
+                        }
+                    ))
+                    .size(15),
+                    button("Hide mnemonic")
+                        .on_press(Message::ModelAction(Action::KeysHideMnemonic)),
+                ]
+            } else if self.model.own_keys.has_stored_mnemonic() {
+                column![row![
+                    text("Mnemonic is stored:").size(15),
+                    text_input(
+                        "re-enter password to reveal mnemonic",
+                        self.model.own_keys.reveal_mnemonic_password_input.as_str(),
+                        Message::KeysRevealMnemonicPasswordInput,
+                    )
+                    .password()
+                    .size(15),
+                    button("Reveal mnemonic")
+                        .on_press(Message::ModelAction(Action::KeysRevealMnemonic)),
+                ]
+                .align_items(Alignment::Fill)
+                .spacing(5)
+                .padding(0)]
+            } else {
+                column![]
+            }
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0);
+
         column![
             text("Own Keys").size(25),
+            row![
+                column![text("Identity:").size(15)]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                pick_list(
+                    self.model.own_keys.identity_names(),
+                    Some(self.model.own_keys.active_identity_name().to_string()),
+                    |name| Message::ModelAction(Action::IdentitySelect(name)),
+                )
+                .text_size(15),
+                button("Remove").on_press(Message::ModelAction(Action::IdentityRemove)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                text_input(
+                    "name of new identity",
+                    &self.model.own_keys.identity_name_input,
+                    Message::KeysIdentityNameInput,
+                )
+                .size(15),
+                button("Add identity").on_press(Message::ModelAction(Action::IdentityAdd)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            iced::widget::rule::Rule::horizontal(5),
             unlock_ui,
+            lock_ui,
+            weak_encryption_ui,
             row![
                 column![text("Public key (npub):").size(15)]
                     .align_items(Alignment::Start)
@@ -141,12 +541,15 @@ impl KeystrApp {
             .align_items(Alignment::Fill)
             .spacing(5)
             .padding(0),
-            text(if self.model.own_keys.has_unsaved_change {
+            text(if self.model.own_keys.has_unsaved_change() {
                 "There are Unsaved changes!"
             } else {
                 "(no changes)"
             })
             .size(15),
+            frost_info_ui,
+            retirement_ui,
+            stored_mnemonic_ui,
             iced::widget::rule::Rule::horizontal(5),
             row![
                 button("Load").on_press(Message::ModelAction(Action::KeysLoad)),
@@ -157,6 +560,61 @@ impl KeystrApp {
             .align_items(Alignment::Fill)
             .spacing(5)
             .padding(0),
+            key_versions_ui,
+            row![
+                text("Generate with mnemonic:").size(15),
+                button("12 words").on_press(Message::ModelAction(Action::KeysGenerateMnemonic(12))),
+                button("24 words").on_press(Message::ModelAction(Action::KeysGenerateMnemonic(24))),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                text("Generate vanity npub, that").size(15),
+                pick_list(
+                    VANITY_POSITIONS,
+                    Some(self.model.vanity.position),
+                    Message::VanityPositionChange
+                )
+                .text_size(15),
+                text_input(
+                    "pattern, bech32 characters",
+                    &self.model.vanity.pattern_input,
+                    Message::VanityPatternInput,
+                )
+                .size(15),
+                if self.model.vanity.is_running() {
+                    button("Cancel").on_press(Message::ModelAction(Action::VanityCancel))
+                } else {
+                    button("Search").on_press(Message::ModelAction(Action::VanityStart))
+                },
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            vanity_status_ui,
+            row![
+                text("Generate FROST group key, signers needed:").size(15),
+                text_input(
+                    "2",
+                    &self.model.own_keys.frost_threshold_input,
+                    Message::KeysFrostThresholdInput,
+                )
+                .width(Length::Fixed(50.0))
+                .size(15),
+                text("of participants:").size(15),
+                text_input(
+                    "3",
+                    &self.model.own_keys.frost_count_input,
+                    Message::KeysFrostCountInput,
+                )
+                .width(Length::Fixed(50.0))
+                .size(15),
+                button("Generate").on_press(Message::ModelAction(Action::KeysGenerateFrost)),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
             text("Password to encrypt secret key:").size(15),
             row![
                 column![text("Password:").size(15),]
@@ -165,7 +623,7 @@ impl KeystrApp {
                     .padding(0),
                 text_input(
                     "enter password for encrypting secret key",
-                    &self.model.own_keys.save_password_input,
+                    self.model.own_keys.save_password_input.as_str(),
                     Message::KeysSavePasswordInput,
                 )
                 .password()
@@ -181,7 +639,7 @@ impl KeystrApp {
                     .padding(0),
                 text_input(
                     "repeat password",
-                    &self.model.own_keys.save_repeat_password_input,
+                    self.model.own_keys.save_repeat_password_input.as_str(),
                     Message::KeysSaveRepeatPasswordInput,
                 )
                 .password()
@@ -191,6 +649,62 @@ impl KeystrApp {
             .spacing(5)
             .padding(0),
             iced::widget::rule::Rule::horizontal(5),
+            text("Change password of the saved secret key:").size(15),
+            row![
+                column![text("Current password:").size(15),]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                text_input(
+                    "current password of saved secret key",
+                    self.model.own_keys.change_old_password_input.as_str(),
+                    Message::KeysChangeOldPasswordInput,
+                )
+                .password()
+                .size(15),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                column![text("New password:").size(15),]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                text_input(
+                    "new password",
+                    self.model.own_keys.change_new_password_input.as_str(),
+                    Message::KeysChangeNewPasswordInput,
+                )
+                .password()
+                .size(15),
+                text_input(
+                    "repeat new password",
+                    self.model.own_keys.change_repeat_password_input.as_str(),
+                    Message::KeysChangeRepeatPasswordInput,
+                )
+                .password()
+                .size(15),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                text("Encryption cost (log2 rounds, optional):").size(15),
+                text_input(
+                    "(keep)",
+                    &self.model.own_keys.change_log2_rounds_input,
+                    Message::KeysChangeLog2RoundsInput,
+                )
+                .width(Length::Fixed(60.0))
+                .size(15),
+                button("Change password")
+                    .on_press(Message::ModelAction(Action::KeysChangePassword)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            iced::widget::rule::Rule::horizontal(5),
             row![
                 text_input(
                     "npub or hex for public key import",
@@ -208,7 +722,7 @@ impl KeystrApp {
             row![
                 text_input(
                     "npub or hex for secret key import",
-                    &self.model.own_keys.secret_key_input,
+                    self.model.own_keys.secret_key_input.as_str(),
                     Message::KeysSecretkeyInput,
                 )
                 .password()
@@ -216,25 +730,330 @@ impl KeystrApp {
                 button("Import Secret key")
                     .on_press(Message::ModelAction(Action::KeysImportSecretkey)),
             ]
-            .align_items(Alignment::Fill)
-            .spacing(5)
-            .padding(0),
-            iced::widget::rule::Rule::horizontal(5),
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            iced::widget::rule::Rule::horizontal(5),
+            row![
+                text_input(
+                    "BIP39 mnemonic words to import secret key, NIP-06",
+                    self.model.own_keys.mnemonic_input.as_str(),
+                    Message::KeysMnemonicInput,
+                )
+                .password()
+                .size(15),
+                button("Import Mnemonic")
+                    .on_press(Message::ModelAction(Action::KeysImportMnemonic)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                text_input(
+                    "optional BIP39 passphrase",
+                    self.model.own_keys.mnemonic_passphrase_input.as_str(),
+                    Message::KeysMnemonicPassphraseInput,
+                )
+                .password()
+                .size(15),
+                text("Account:").size(15),
+                text_input(
+                    "0",
+                    &self.model.own_keys.mnemonic_account_input,
+                    Message::KeysMnemonicAccountInput,
+                )
+                .width(Length::Fixed(50.0))
+                .size(15),
+                button("Preview accounts")
+                    .on_press(Message::ModelAction(Action::KeysPreviewMnemonic)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            mnemonic_preview_ui,
+            iced::widget::rule::Rule::horizontal(5),
+            text("Encrypted secret key (NIP-49 ncryptsec), for export and import:").size(15),
+            row![
+                column![text("Password:").size(15),]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                text_input(
+                    "password for ncryptsec export/import",
+                    self.model.own_keys.ncryptsec_password_input.as_str(),
+                    Message::KeysNcryptsecPasswordInput,
+                )
+                .password()
+                .size(15),
+                button("Export").on_press(Message::ModelAction(Action::KeysExportNcryptsec)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                column![text("Exported ncryptsec:").size(15)]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                button("QR").on_press(Message::QRCode(
+                    self.model.own_keys.get_ncryptsec().to_string()
+                )),
+                text_input(
+                    "(export first)",
+                    self.model.own_keys.get_ncryptsec(),
+                    Message::ChangedReadonly,
+                )
+                .size(15),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                column![text("Paper backup:").size(15)]
+                    .align_items(Alignment::Start)
+                    .width(label_width)
+                    .padding(0),
+                text_input(
+                    "file path for printable backup (SVG)",
+                    &self.model.own_keys.paper_backup_path_input,
+                    Message::KeysPaperBackupPathInput,
+                )
+                .size(15),
+                button("With ncryptsec").on_press(Message::ModelAction(Action::KeysPaperBackup)),
+                button("With mnemonic")
+                    .on_press(Message::ModelAction(Action::KeysPaperBackupMnemonic)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                text_input(
+                    "ncryptsec for encrypted secret key import",
+                    &self.model.own_keys.ncryptsec_input,
+                    Message::KeysNcryptsecInput,
+                )
+                .size(15),
+                button("Import ncryptsec")
+                    .on_press(Message::ModelAction(Action::KeysImportNcryptsec)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                text_input(
+                    "key file path (ncryptsec, JSON export, nsec, Keystr .ncrypt)",
+                    &self.model.own_keys.import_file_path_input,
+                    Message::KeysImportFilePathInput,
+                )
+                .size(15),
+                text_input(
+                    "password (if encrypted)",
+                    self.model.own_keys.import_file_password_input.as_str(),
+                    Message::KeysImportFilePasswordInput,
+                )
+                .password()
+                .width(Length::Fixed(180.0))
+                .size(15),
+                button("Import file").on_press(Message::ModelAction(Action::KeysImportFile)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            iced::widget::rule::Rule::horizontal(5),
+            text("Shamir secret sharing backup, any M of N shares restore the secret key:")
+                .size(15),
+            row![
+                text("Needed (M):").size(15),
+                text_input(
+                    "2",
+                    &self.model.own_keys.shamir_threshold_input,
+                    Message::KeysShamirThresholdInput,
+                )
+                .width(Length::Fixed(50.0))
+                .size(15),
+                text("Shares (N):").size(15),
+                text_input(
+                    "3",
+                    &self.model.own_keys.shamir_count_input,
+                    Message::KeysShamirCountInput,
+                )
+                .width(Length::Fixed(50.0))
+                .size(15),
+                button("Create shares").on_press(Message::ModelAction(Action::KeysShamirSplit)),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            shamir_shares_ui,
+            row![
+                text_input(
+                    "nshare shares to restore from, separated by spaces",
+                    self.model.own_keys.shamir_restore_input.as_str(),
+                    Message::KeysShamirRestoreInput,
+                )
+                .password()
+                .size(15),
+                button("Restore").on_press(Message::ModelAction(Action::KeysShamirRestore)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            shamir_restored_ui,
+            iced::widget::rule::Rule::horizontal(5),
+            text("Key rotation, if the key may be compromised: a new identity replaces this one")
+                .size(15),
+            row![
+                text_input(
+                    "name of new identity",
+                    &self.model.rotation.new_name_input,
+                    Message::KeysRotateNameInput,
+                )
+                .size(15),
+                checkbox(
+                    "Re-issue delegations",
+                    self.model.rotation.reissue_delegations,
+                    Message::KeysRotateReissueToggled,
+                )
+                .text_size(15),
+                button("Rotate key").on_press(Message::ModelAction(Action::KeysRotate)),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5)
+            .padding(0),
+            migration_event_ui,
+            iced::widget::rule::Rule::horizontal(5),
+            text(
+                "Backup of all data (keys of all identities, settings), encrypted with a password:"
+            )
+            .size(15),
+            row![
+                text_input(
+                    "backup file path",
+                    &self.model.data_backup.path_input,
+                    Message::BackupPathInput,
+                )
+                .size(15),
+                text_input(
+                    "password",
+                    self.model.data_backup.password_input.as_str(),
+                    Message::BackupPasswordInput,
+                )
+                .password()
+                .width(Length::Fixed(120.0))
+                .size(15),
+                text_input(
+                    "repeat password",
+                    self.model.data_backup.repeat_password_input.as_str(),
+                    Message::BackupRepeatPasswordInput,
+                )
+                .password()
+                .width(Length::Fixed(120.0))
+                .size(15),
+                button("Backup").on_press(Message::ModelAction(Action::BackupCreate)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            row![
+                text_input(
+                    "backup file path to restore",
+                    &self.model.data_backup.restore_path_input,
+                    Message::BackupRestorePathInput,
+                )
+                .size(15),
+                text_input(
+                    "password",
+                    self.model.data_backup.restore_password_input.as_str(),
+                    Message::BackupRestorePasswordInput,
+                )
+                .password()
+                .width(Length::Fixed(120.0))
+                .size(15),
+                button("Restore").on_press(Message::ModelAction(Action::BackupRestore)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            iced::widget::rule::Rule::horizontal(5),
+        ]
+        .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(20)
+        .max_width(600)
+        .into()
+    }
+
+    fn tab_subkeys(&self) -> Element<Message> {
+        let subkeys_ui = Column::with_children(
+            self.model
+                .own_keys
+                .get_subkeys()
+                .iter()
+                .enumerate()
+                .map(|(i, subkey)| {
+                    column![
+                        text(subkey.to_string()).size(15),
+                        row![
+                            button("QR").on_press(Message::QRCode(subkey.npub.clone())),
+                            text_input("", &subkey.npub, Message::ChangedReadonly).size(15),
+                        ]
+                        .align_items(Alignment::Center)
+                        .spacing(5),
+                        row![
+                            button("Use as delegatee")
+                                .on_press(Message::DelegateSubkeySelected(subkey.clone())),
+                            button("Export nsec")
+                                .on_press(Message::ModelAction(Action::KeysSubkeyExport(i))),
+                            button("Remove")
+                                .on_press(Message::ModelAction(Action::KeysSubkeyRemove(i))),
+                        ]
+                        .spacing(5),
+                    ]
+                    .spacing(5)
+                    .into()
+                })
+                .collect::<Vec<Element<Message>>>(),
+        )
+        .spacing(10)
+        .padding(0);
+
+        let subkey_nsec_ui = if let Some((label, nsec)) = self.model.own_keys.get_subkey_nsec() {
+            column![
+                text(format!("Secret key of '{}' (keep it secret!):", label)).size(15),
+                text_input("", nsec, Message::ChangedReadonly).size(15),
+                button("Hide").on_press(Message::ModelAction(Action::KeysSubkeyHide)),
+            ]
+        } else {
+            column![]
+        }
+        .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(0);
+
+        column![
+            text("Sub-keys").size(25),
+            text("Labelled keys for applications, derived from the mnemonic of the identity (NIP-06), each on its own account index. They can be restored from the mnemonic.").size(15),
+            text(if self.model.own_keys.can_derive_subkeys() {
+                "Mnemonic is available (if it has a passphrase, enter it in the Keys tab)"
+            } else {
+                "To add or export sub-keys, reveal the stored mnemonic in the Keys tab"
+            })
+            .size(15),
             row![
                 text_input(
-                    "BIP39 mnemonic (passphrase) to generate secret key, NIP-06",
-                    &self.model.own_keys.mnemonic_input,
-                    Message::KeysMnemonicInput,
+                    "label of the new sub-key, e.g. application name",
+                    &self.model.own_keys.subkey_label_input,
+                    Message::KeysSubkeyLabelInput,
                 )
-                .password()
                 .size(15),
-                button("Import Mnemonic")
-                    .on_press(Message::ModelAction(Action::KeysImportMnemonic)),
+                button("Add sub-key").on_press(Message::ModelAction(Action::KeysSubkeyAdd)),
             ]
             .align_items(Alignment::Fill)
             .spacing(5)
             .padding(0),
             iced::widget::rule::Rule::horizontal(5),
+            subkeys_ui,
+            subkey_nsec_ui,
         ]
         .align_items(Alignment::Fill)
         .spacing(5)
@@ -245,6 +1064,136 @@ impl KeystrApp {
 
     fn tab_delegate(&self) -> Element<Message> {
         let label_width = Length::Fixed(150.0);
+        let now = DelegationLedger::current_time();
+        let delegations = self.model.delegations.records();
+        let delegations_ui = if delegations.is_empty() {
+            column![text("No delegations created yet").size(15)]
+        } else {
+            Column::with_children(
+                delegations
+                    .iter()
+                    .rev()
+                    .map(|d| {
+                        column![
+                            text(format!(
+                                "[{}]  to {}  --  {}",
+                                d.status(now),
+                                d.delegatee,
+                                if d.conditions.is_empty() {
+                                    "(no conditions)"
+                                } else {
+                                    d.conditions.as_str()
+                                }
+                            ))
+                            .size(15),
+                            text(format!(
+                                "   by {}, created {} in {}",
+                                d.delegator,
+                                PaperBackup::format_date(d.created_at),
+                                d.source
+                            ))
+                            .size(12),
+                        ]
+                        .spacing(0)
+                        .into()
+                    })
+                    .collect::<Vec<Element<Message>>>(),
+            )
+        }
+        .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(0);
+
+        // common kinds, and the selected custom one
+        let mut kind_options: Vec<u64> = EVENT_KINDS.iter().map(|(kind, _name)| *kind).collect();
+        kind_options.extend(
+            self.model
+                .delegator
+                .kind
+                .filter(|kind| Delegator::kind_name(*kind).is_none()),
+        );
+        let kinds_ui = Column::with_children(
+            kind_options
+                .chunks(4)
+                .map(|kinds| {
+                    Row::with_children(
+                        kinds
+                            .iter()
+                            .map(|kind| {
+                                let kind = *kind;
+                                checkbox(
+                                    Delegator::kind_description(kind),
+                                    self.model.delegator.kind == Some(kind),
+                                    move |b| Message::DelegateKindToggled(kind, b),
+                                )
+                                .text_size(15)
+                                .width(Length::Fill)
+                                .into()
+                            })
+                            .collect::<Vec<Element<Message>>>(),
+                    )
+                    .align_items(Alignment::Fill)
+                    .spacing(5)
+                    .padding(0)
+                    .into()
+                })
+                .collect::<Vec<Element<Message>>>(),
+        )
+        .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(0);
+        let kinds_note = match self.model.delegator.kind {
+            None => column![text("No kind selected, any kind is allowed").size(12)],
+            Some(_) => column![text(
+                "One kind per delegation: all conditions have to match, so an event can't match several kinds"
+            )
+            .size(12)],
+        };
+
+        let verification_ui = match self.model.verifier.get_result() {
+            None => column![],
+            Some(v) => {
+                let mut lines = vec![
+                    if v.signature_valid {
+                        "Delegation signature: valid".to_string()
+                    } else {
+                        "Delegation signature: INVALID".to_string()
+                    },
+                    format!("Delegator: {}", v.delegator),
+                    format!("Delegatee: {}", v.delegatee),
+                    format!("Conditions: {}", v.conditions),
+                ];
+                lines.extend(v.conditions_description.iter().cloned());
+                if let Some(valid) = v.event_signature_valid {
+                    lines.push(format!(
+                        "Event signature: {}",
+                        if valid { "valid" } else { "INVALID" }
+                    ));
+                }
+                if let Some(check) = &v.check {
+                    lines.push(check.clone());
+                }
+                Column::with_children(
+                    lines
+                        .into_iter()
+                        .map(|l| text(l).size(15).into())
+                        .collect::<Vec<Element<Message>>>(),
+                )
+            }
+        }
+        .align_items(Alignment::Fill)
+        .spacing(5)
+        .padding(0);
+
+        // a time limit in local time and as timestamp
+        let time_limit_ui = |time_cond: &str| {
+            if time_cond.is_empty() {
+                "(no limit)".to_string()
+            } else {
+                format!("{} ({})", Delegator::time_limit_local(time_cond), time_cond)
+            }
+        };
+
         column![
             text("Delegate").size(25),
             text("Delegatee -- npub to delegate to:").size(15),
@@ -259,22 +1208,43 @@ impl KeystrApp {
             ]
             .align_items(Alignment::Fill)
             .spacing(5),
+            row![
+                text("Or a sub-key:").size(15),
+                pick_list(
+                    self.model.own_keys.get_subkeys().clone(),
+                    self.model
+                        .own_keys
+                        .get_subkeys()
+                        .iter()
+                        .find(|s| s.npub == self.model.delegator.delegatee_npub_input)
+                        .cloned(),
+                    Message::DelegateSubkeySelected
+                )
+                .placeholder("(no sub-key selected)")
+                .text_size(15),
+            ]
+            .align_items(Alignment::Center)
+            .spacing(5),
             iced::widget::rule::Rule::horizontal(5),
+            text("Event kind:").size(15),
+            kinds_ui,
             row![
-                column![text("Event kinds (eg. 'kind=1'):").size(15),]
+                column![text("Other kind:").size(15),]
                     .align_items(Alignment::Start)
                     .width(label_width)
                     .padding(0),
                 text_input(
-                    "kind condition",
-                    &self.model.delegator.kind_condition_input,
-                    Message::DelegateKindChanged,
+                    "kind number, e.g. 30078",
+                    &self.model.delegator.custom_kind_input,
+                    Message::DelegateCustomKindInput,
                 )
                 .size(15),
+                button("Select").on_press(Message::DelegateCustomKindSelect),
             ]
             .align_items(Alignment::Center)
             .spacing(5)
             .padding(0),
+            kinds_note,
             iced::widget::rule::Rule::horizontal(5),
             row![
                 column![text("Time start:").size(15),]
@@ -282,11 +1252,14 @@ impl KeystrApp {
                     .width(label_width)
                     .padding(0),
                 text_input(
-                    "time start",
-                    &self.model.delegator.time_cond_start,
-                    Message::DelegateTimeStartChanged,
+                    "e.g. 2024-06-30 12:00, or unix timestamp",
+                    &self.model.delegator.time_start_input,
+                    Message::DelegateTimeStartInput,
                 )
                 .size(15),
+                button("Set").on_press(Message::DelegateTimeStartChanged(
+                    self.model.delegator.time_start_input.clone()
+                )),
             ]
             .align_items(Alignment::Center)
             .spacing(5)
@@ -297,33 +1270,42 @@ impl KeystrApp {
                     .width(label_width)
                     .padding(0),
                 text_input(
-                    "time end",
-                    &self.model.delegator.time_cond_end,
-                    Message::DelegateTimeEndChanged,
+                    "e.g. 2024-09-30T12:00:00Z, or unix timestamp",
+                    &self.model.delegator.time_end_input,
+                    Message::DelegateTimeEndInput,
                 )
                 .size(15),
+                button("Set").on_press(Message::DelegateTimeEndChanged(
+                    self.model.delegator.time_end_input.clone()
+                )),
             ]
             .align_items(Alignment::Center)
             .spacing(5)
             .padding(0),
             row![
-                column![text("Time days:").size(15),]
+                column![text("Duration from now:").size(15),]
                     .align_items(Alignment::Start)
                     .width(label_width)
                     .padding(0),
                 text_input(
-                    "time duration in days",
-                    &self.model.delegator.time_cond_days,
-                    Message::DelegateTimeDaysChangedNoUpdate,
+                    "e.g. 2h, 90d, 6 weeks",
+                    &self.model.delegator.time_cond_duration,
+                    Message::DelegateTimeDurationInput,
                 )
                 .size(15),
-                button("Set").on_press(Message::DelegateTimeDaysChanged(
-                    self.model.delegator.time_cond_days.clone()
+                button("Set").on_press(Message::DelegateTimeDurationChanged(
+                    self.model.delegator.time_cond_duration.clone()
                 )),
             ]
             .align_items(Alignment::Center)
             .spacing(5)
             .padding(0),
+            text(format!(
+                "Valid from: {}  --  until: {}",
+                time_limit_ui(&self.model.delegator.time_cond_start),
+                time_limit_ui(&self.model.delegator.time_cond_end)
+            ))
+            .size(12),
             iced::widget::rule::Rule::horizontal(5),
             row![
                 column![text("Condition string:").size(15),]
@@ -380,6 +1362,61 @@ impl KeystrApp {
                 Message::ChangedReadonly,
             )
             .size(15),
+            iced::widget::rule::Rule::horizontal(5),
+            text("Delegations created (newest first):").size(15),
+            delegations_ui,
+            row![
+                text_input(
+                    "export file path (JSON)",
+                    &self.model.delegations.export_path_input,
+                    Message::DelegationsExportPathInput,
+                )
+                .size(15),
+                button("Export").on_press(Message::ModelAction(Action::DelegationsExport)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            iced::widget::rule::Rule::horizontal(5),
+            text("Verify a delegation received -- the tag, and the delegatee or a signed event:")
+                .size(15),
+            text_input(
+                "delegation tag, e.g. [\"delegation\",\"<hex>\",\"kind=1\",\"<signature>\"]",
+                &self.model.verifier.tag_input,
+                Message::DelegationVerifyTagInput,
+            )
+            .size(15),
+            text_input(
+                "delegatee npub",
+                &self.model.verifier.delegatee_input,
+                Message::DelegationVerifyDelegateeInput,
+            )
+            .size(15),
+            text_input(
+                "optional: signed event JSON (by the delegatee, may contain the tag)",
+                &self.model.verifier.event_input,
+                Message::DelegationVerifyEventInput,
+            )
+            .size(15),
+            row![
+                text_input(
+                    "kind to check",
+                    &self.model.verifier.kind_input,
+                    Message::DelegationVerifyKindInput,
+                )
+                .size(15),
+                text_input(
+                    "time to check (date-time or unix timestamp)",
+                    &self.model.verifier.time_input,
+                    Message::DelegationVerifyTimeInput,
+                )
+                .size(15),
+                button("Verify").on_press(Message::ModelAction(Action::DelegationVerify)),
+            ]
+            .align_items(Alignment::Fill)
+            .spacing(5)
+            .padding(0),
+            verification_ui,
         ]
         .align_items(Alignment::Fill)
         .spacing(5)
@@ -475,9 +1512,14 @@ impl KeystrApp {
                         .padding(0)
                     },
                     text(&format!(
-                        "Status:  Connected, through relay '{}' to client '{}'",
+                        "Status:  Connected, through relay '{}' to client '{}'{}",
                         conn.relay_str,
                         conn.get_client_npub(),
+                        if conn.is_locked() {
+                            " -- LOCKED, requests are queued until the key is unlocked"
+                        } else {
+                            ""
+                        },
                     ))
                     .size(15),
                     button("Disconnect").on_press(Message::ModelAction(Action::SignerDisconnect)),
@@ -516,6 +1558,182 @@ impl KeystrApp {
             .spacing(5)
             .padding(20),
 
+            Modal::Confirmation(Confirmation::KeysLoad) => column![
+                text("Load saved keys?").size(25),
+                text("Unsaved changes of the identities will be lost!").size(15),
+                iced::widget::rule::Rule::horizontal(5),
+                row![
+                    button("Yes").on_press(Message::ModelAction(Action::ConfirmationYes)),
+                    button("No").on_press(Message::ModelAction(Action::ConfirmationNo)),
+                ]
+                .align_items(Alignment::Fill)
+                .width(Length::Fill)
+                .spacing(5)
+                .padding(0),
+            ]
+            .align_items(Alignment::Fill)
+            .width(Length::Fill)
+            .spacing(5)
+            .padding(20),
+
+            Modal::Confirmation(Confirmation::IdentityRemove(name)) => column![
+                text(format!("Remove identity '{}'?", name)).size(25),
+                text("Its keys will also be deleted from storage!").size(15),
+                iced::widget::rule::Rule::horizontal(5),
+                row![
+                    button("Yes").on_press(Message::ModelAction(Action::ConfirmationYes)),
+                    button("No").on_press(Message::ModelAction(Action::ConfirmationNo)),
+                ]
+                .align_items(Alignment::Fill)
+                .width(Length::Fill)
+                .spacing(5)
+                .padding(0),
+            ]
+            .align_items(Alignment::Fill)
+            .width(Length::Fill)
+            .spacing(5)
+            .padding(20),
+
+            Modal::Confirmation(Confirmation::KeysRestoreVersion(version)) => column![
+                text(format!("Restore version {} of the saved key?", version)).size(25),
+                text("The current saved key is replaced, it is kept as the latest previous version.")
+                    .size(15),
+                iced::widget::rule::Rule::horizontal(5),
+                row![
+                    button("Yes").on_press(Message::ModelAction(Action::ConfirmationYes)),
+                    button("No").on_press(Message::ModelAction(Action::ConfirmationNo)),
+                ]
+                .align_items(Alignment::Fill)
+                .width(Length::Fill)
+                .spacing(5)
+                .padding(0),
+            ]
+            .align_items(Alignment::Fill)
+            .width(Length::Fill)
+            .spacing(5)
+            .padding(20),
+
+            Modal::Confirmation(Confirmation::BackupRestore(description)) => column![
+                text("Restore backup?").size(25),
+                text(description).size(15),
+                text("All current keys and settings will be overwritten by the backup!").size(15),
+                iced::widget::rule::Rule::horizontal(5),
+                row![
+                    button("Yes").on_press(Message::ModelAction(Action::ConfirmationYes)),
+                    button("No").on_press(Message::ModelAction(Action::ConfirmationNo)),
+                ]
+                .align_items(Alignment::Fill)
+                .width(Length::Fill)
+                .spacing(5)
+                .padding(0),
+            ]
+            .align_items(Alignment::Fill)
+            .width(Length::Fill)
+            .spacing(5)
+            .padding(20),
+
+            Modal::Confirmation(Confirmation::KeysRotate(name)) => column![
+                text(format!("Rotate the key of identity '{}'?", name)).size(25),
+                text("A new identity with a new key will replace it; the old key signs a migration event, and is kept as retired.").size(15),
+                iced::widget::rule::Rule::horizontal(5),
+                row![
+                    button("Yes").on_press(Message::ModelAction(Action::ConfirmationYes)),
+                    button("No").on_press(Message::ModelAction(Action::ConfirmationNo)),
+                ]
+                .align_items(Alignment::Fill)
+                .width(Length::Fill)
+                .spacing(5)
+                .padding(0),
+            ]
+            .align_items(Alignment::Fill)
+            .width(Length::Fill)
+            .spacing(5)
+            .padding(20),
+
+            Modal::MnemonicBackup(MnemonicBackupStage::ShowWords) => {
+                let word_lines = self
+                    .model
+                    .mnemonic_generator
+                    .get_words()
+                    .chunks(4)
+                    .enumerate()
+                    .map(|(line, words)| {
+                        text(
+                            words
+                                .iter()
+                                .enumerate()
+                                .map(|(i, w)| format!("{}. {}", line * 4 + i + 1, w))
+                                .collect::<Vec<String>>()
+                                .join("   "),
+                        )
+                        .size(15)
+                        .into()
+                    })
+                    .collect::<Vec<Element<Message>>>();
+                column![
+                    text("New Mnemonic").size(25),
+                    text("Write down these words, in this order. They are shown only ONCE!")
+                        .size(15),
+                    iced::widget::rule::Rule::horizontal(5),
+                    Column::with_children(word_lines).spacing(5),
+                    iced::widget::rule::Rule::horizontal(5),
+                    row![
+                        button("I have written them down")
+                            .on_press(Message::ModelAction(Action::MnemonicBackupWordsNoted)),
+                        button("Cancel")
+                            .on_press(Message::ModelAction(Action::MnemonicBackupCancel)),
+                    ]
+                    .spacing(5)
+                    .padding(0),
+                ]
+                .align_items(Alignment::Fill)
+                .width(Length::Fill)
+                .spacing(5)
+                .padding(20)
+            }
+
+            Modal::MnemonicBackup(_) => {
+                let word_inputs = self
+                    .model
+                    .mnemonic_generator
+                    .get_challenge_word_numbers()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, n)| {
+                        row![
+                            text(format!("Word #{}:", n)).size(15),
+                            text_input(
+                                "",
+                                &self.model.mnemonic_generator.challenge_inputs[i],
+                                move |s| Message::MnemonicBackupWordInput(i, s),
+                            )
+                            .size(15),
+                        ]
+                        .align_items(Alignment::Center)
+                        .spacing(5)
+                        .into()
+                    })
+                    .collect::<Vec<Element<Message>>>();
+                column![
+                    text("Confirm Backup").size(25),
+                    text("Enter the following words of your mnemonic backup:").size(15),
+                    Column::with_children(word_inputs).spacing(5),
+                    iced::widget::rule::Rule::horizontal(5),
+                    row![
+                        button("Confirm")
+                            .on_press(Message::ModelAction(Action::MnemonicBackupConfirm)),
+                        button("Cancel")
+                            .on_press(Message::ModelAction(Action::MnemonicBackupCancel)),
+                    ]
+                    .spacing(5)
+                    .padding(0),
+                ]
+                .align_items(Alignment::Fill)
+                .width(Length::Fill)
+                .spacing(5)
+                .padding(20)
+            }
+
             Modal::QRCode(qr_content) => column![
                 text("QR Code").size(25),
                 QRCode::new(&self.model.get_qr_code().as_ref().unwrap().1).cell_size(6),
@@ -566,6 +1784,51 @@ impl KeystrApp {
                     Message::SecurityLevelChange
                 )
                 .text_size(15),
+                row![
+                    text("Key encryption:").size(15),
+                    pick_list(
+                        KDF_KINDS,
+                        Some(self.model.settings.security.kdf),
+                        Message::SettingsKdfChange
+                    )
+                    .text_size(15),
+                    text(format!(
+                        "scrypt cost (log2 rounds): {}",
+                        self.model.settings.security.kdf_log2_rounds
+                    ))
+                    .size(15),
+                    text_input(
+                        "new cost",
+                        &self.model.settings.kdf_log2_rounds_input,
+                        Message::SettingsKdfLog2RoundsInput,
+                    )
+                    .width(Length::Fixed(80.0))
+                    .size(15),
+                    button("Set").on_press(Message::ModelAction(Action::SettingsSetKdfCost)),
+                    button("Calibrate")
+                        .on_press(Message::ModelAction(Action::SettingsCalibrateKdf)),
+                ]
+                .align_items(Alignment::Fill)
+                .spacing(5)
+                .padding(0),
+                row![
+                    text(match self.model.settings.security.auto_lock_minutes {
+                        0 => "Auto-lock: off".to_string(),
+                        m => format!("Auto-lock after {} minutes of inactivity", m),
+                    })
+                    .size(15),
+                    text_input(
+                        "minutes, 0 for off",
+                        &self.model.settings.auto_lock_minutes_input,
+                        Message::SettingsAutoLockInput,
+                    )
+                    .width(Length::Fixed(120.0))
+                    .size(15),
+                    button("Set").on_press(Message::ModelAction(Action::SettingsSetAutoLock)),
+                ]
+                .align_items(Alignment::Fill)
+                .spacing(5)
+                .padding(0),
                 iced::widget::rule::Rule::horizontal(5),
                 self.tab_selector(),
                 iced::widget::rule::Rule::horizontal(5),
@@ -575,6 +1838,7 @@ impl KeystrApp {
                 iced::widget::rule::Rule::horizontal(5),
                 match self.current_tab {
                     Tab::Keys => self.tab_keys(),
+                    Tab::SubKeys => self.tab_subkeys(),
                     Tab::Delegate => self.tab_delegate(),
                     Tab::Signer => self.tab_signer(),
                 },
@@ -621,7 +1885,7 @@ impl Application for KeystrApp {
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        subscription::unfold(
+        let events = subscription::unfold(
             std::any::TypeId::of::<KeystrModel>(),
             SubscriptionState::Uninited,
             move |state| async move {
@@ -639,10 +1903,23 @@ impl Application for KeystrApp {
                     },
                 }
             },
-        )
+        );
+        let auto_lock = iced::time::every(AUTO_LOCK_CHECK_PERIOD)
+            .map(|_| Message::ModelAction(Action::KeysAutoLockCheck));
+        let vanity = if self.model.vanity.is_running() {
+            iced::time::every(VANITY_CHECK_PERIOD)
+                .map(|_| Message::ModelAction(Action::VanityCheck))
+        } else {
+            Subscription::none()
+        };
+        Subscription::batch(vec![events, auto_lock, vanity])
     }
 
     fn update(&mut self, message: Message) -> Command<Message> {
+        // model actions record activity themselves, events are not user activity
+        if !matches!(message, Message::ModelAction(_) | Message::ModelEvent(_)) {
+            self.model.record_activity();
+        }
         match message {
             Message::TabSelect(t) => {
                 self.current_tab = t;
@@ -650,47 +1927,131 @@ impl Application for KeystrApp {
             Message::ModelAction(action) => {
                 self.model.action(action);
             }
+            Message::KeysIdentityNameInput(s) => self.model.own_keys.identity_name_input = s,
             Message::KeysPubkeyInput(s) => self.model.own_keys.public_key_input = s,
-            Message::KeysToggleHideSecretKey => {
-                self.model.own_keys.hide_secret_key = !self.model.own_keys.hide_secret_key
+            Message::KeysToggleHideSecretKey => self.model.own_keys.toggle_hide_secret_key(),
+            Message::KeysSecretkeyInput(s) => self.model.own_keys.secret_key_input.set(s),
+            Message::KeysMnemonicInput(s) => self.model.own_keys.mnemonic_input.set(s),
+            Message::KeysMnemonicPassphraseInput(s) => {
+                self.model.own_keys.mnemonic_passphrase_input.set(s)
+            }
+            Message::KeysMnemonicAccountInput(s) => self.model.own_keys.mnemonic_account_input = s,
+            Message::KeysRevealMnemonicPasswordInput(s) => {
+                self.model.own_keys.reveal_mnemonic_password_input.set(s)
+            }
+            Message::KeysNcryptsecInput(s) => self.model.own_keys.ncryptsec_input = s,
+            Message::KeysNcryptsecPasswordInput(s) => {
+                self.model.own_keys.ncryptsec_password_input.set(s)
             }
-            Message::KeysSecretkeyInput(s) => self.model.own_keys.secret_key_input = s,
-            Message::KeysMnemonicInput(s) => self.model.own_keys.mnemonic_input = s,
-            Message::KeysDecryptPasswordInput(s) => self.model.own_keys.decrypt_password_input = s,
-            Message::KeysSavePasswordInput(s) => self.model.own_keys.save_password_input = s,
+            Message::KeysImportFilePathInput(s) => self.model.own_keys.import_file_path_input = s,
+            Message::KeysImportFilePasswordInput(s) => {
+                self.model.own_keys.import_file_password_input.set(s)
+            }
+            Message::KeysPaperBackupPathInput(s) => self.model.own_keys.paper_backup_path_input = s,
+            Message::KeysDecryptPasswordInput(s) => {
+                self.model.own_keys.decrypt_password_input.set(s)
+            }
+            Message::KeysChangeOldPasswordInput(s) => {
+                self.model.own_keys.change_old_password_input.set(s)
+            }
+            Message::KeysChangeNewPasswordInput(s) => {
+                self.model.own_keys.change_new_password_input.set(s)
+            }
+            Message::KeysChangeRepeatPasswordInput(s) => {
+                self.model.own_keys.change_repeat_password_input.set(s)
+            }
+            Message::KeysChangeLog2RoundsInput(s) => {
+                self.model.own_keys.change_log2_rounds_input = s
+            }
+            Message::KeysUpgradePasswordInput(s) => {
+                self.model.own_keys.upgrade_password_input.set(s)
+            }
+            Message::KeysSavePasswordInput(s) => self.model.own_keys.save_password_input.set(s),
             Message::KeysSaveRepeatPasswordInput(s) => {
-                self.model.own_keys.save_repeat_password_input = s
+                self.model.own_keys.save_repeat_password_input.set(s)
             }
+            Message::KeysShamirThresholdInput(s) => self.model.own_keys.shamir_threshold_input = s,
+            Message::KeysShamirCountInput(s) => self.model.own_keys.shamir_count_input = s,
+            Message::KeysShamirFolderInput(s) => self.model.own_keys.shamir_folder_input = s,
+            Message::KeysShamirRestoreInput(s) => self.model.own_keys.shamir_restore_input.set(s),
+            Message::KeysFrostThresholdInput(s) => self.model.own_keys.frost_threshold_input = s,
+            Message::KeysFrostCountInput(s) => self.model.own_keys.frost_count_input = s,
+            Message::KeysSubkeyLabelInput(s) => self.model.own_keys.subkey_label_input = s,
+            Message::KeysRotateNameInput(s) => self.model.rotation.new_name_input = s,
+            Message::KeysRotateReissueToggled(b) => self.model.rotation.reissue_delegations = b,
             Message::DelegateDeeChanged(s) => {
                 self.model.delegator.delegatee_npub_input = s;
                 if let Err(e) = self.model.delegator.validate_and_update() {
                     self.model.status.set_error(&e.to_string());
                 }
             }
-            Message::DelegateKindChanged(s) => {
-                self.model.delegator.kind_condition_input = s;
+            Message::DelegateSubkeySelected(subkey) => {
+                self.model.delegator.delegatee_npub_input = subkey.npub;
                 if let Err(e) = self.model.delegator.validate_and_update() {
                     self.model.status.set_error(&e.to_string());
                 }
+                self.current_tab = Tab::Delegate;
+            }
+            Message::DelegateKindToggled(kind, b) => {
+                if let Err(e) = self.model.delegator.set_kind(kind, b) {
+                    self.model.status.set_error(&e.to_string());
+                }
+            }
+            Message::DelegateCustomKindInput(s) => self.model.delegator.custom_kind_input = s,
+            Message::DelegateCustomKindSelect => {
+                if let Err(e) = self.model.delegator.set_custom_kind() {
+                    self.model.status.set_error(&e.to_string());
+                }
             }
+            Message::DelegationsExportPathInput(s) => self.model.delegations.export_path_input = s,
+            Message::DelegationVerifyTagInput(s) => self.model.verifier.tag_input = s,
+            Message::DelegationVerifyDelegateeInput(s) => self.model.verifier.delegatee_input = s,
+            Message::DelegationVerifyEventInput(s) => self.model.verifier.event_input = s,
+            Message::DelegationVerifyKindInput(s) => self.model.verifier.kind_input = s,
+            Message::DelegationVerifyTimeInput(s) => self.model.verifier.time_input = s,
+            Message::DelegateTimeStartInput(s) => self.model.delegator.time_start_input = s,
             Message::DelegateTimeStartChanged(s) => {
-                self.model.delegator.time_set_start(&s);
+                if let Err(e) = self.model.delegator.time_set_start(&s) {
+                    self.model.status.set_error(&e.to_string());
+                }
             }
+            Message::DelegateTimeEndInput(s) => self.model.delegator.time_end_input = s,
             Message::DelegateTimeEndChanged(s) => {
-                self.model.delegator.time_set_end(&s);
-            }
-            Message::DelegateTimeDaysChanged(s) => {
-                self.model.delegator.time_set_days(&s);
+                if let Err(e) = self.model.delegator.time_set_end(&s) {
+                    self.model.status.set_error(&e.to_string());
+                }
             }
-            Message::DelegateTimeDaysChangedNoUpdate(s) => {
-                self.model.delegator.time_cond_days = s;
+            Message::DelegateTimeDurationInput(s) => self.model.delegator.time_cond_duration = s,
+            Message::DelegateTimeDurationChanged(s) => {
+                if let Err(e) = self.model.delegator.time_set_duration(&s) {
+                    self.model.status.set_error(&e.to_string());
+                }
             }
             Message::SecurityLevelChange(l) => self.model.settings.set_security_level(l),
+            Message::SettingsKdfChange(k) => self.model.settings.set_kdf(k),
+            Message::VanityPatternInput(s) => self.model.vanity.pattern_input = s,
+            Message::VanityPositionChange(p) => self.model.vanity.position = p,
+            Message::SettingsKdfLog2RoundsInput(s) => self.model.settings.kdf_log2_rounds_input = s,
+            Message::SettingsAutoLockInput(s) => self.model.settings.auto_lock_minutes_input = s,
             Message::SignerUriInput(s) => self.model.signer.connect_uri_input = s,
+            Message::BackupPathInput(s) => self.model.data_backup.path_input = s,
+            Message::BackupPasswordInput(s) => self.model.data_backup.password_input.set(s),
+            Message::BackupRepeatPasswordInput(s) => {
+                self.model.data_backup.repeat_password_input.set(s)
+            }
+            Message::BackupRestorePathInput(s) => self.model.data_backup.restore_path_input = s,
+            Message::BackupRestorePasswordInput(s) => {
+                self.model.data_backup.restore_password_input.set(s)
+            }
             Message::ChangedReadonly(_s) => {}
             Message::ModelEvent(_) => {
                 // just do a refresh, no extra action needed here
             }
+            Message::MnemonicBackupWordInput(i, s) => {
+                if let Some(input) = self.model.mnemonic_generator.challenge_inputs.get_mut(i) {
+                    *input = s;
+                }
+            }
             Message::QRCode(qr_content) => self.model.set_qr_code(qr_content),
             Message::QRCodeClose => self.model.reset_qr_code(),
             Message::NoOp => {}